@@ -0,0 +1,29 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Generates the typed node references of the scenes in `godot/`, see
+//! `src/scenes/mod.rs`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[allow(dead_code)]
+#[path = "src/tscn/mod.rs"]
+mod tscn;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/tscn");
+    println!("cargo:rerun-if-changed=godot");
+
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut loader = tscn::SceneLoader::new(manifest_dir.join("godot"));
+    let code = tscn::codegen::generate(&mut loader)
+        .unwrap_or_else(|err| panic!("Unable to generate scene modules: {}", err));
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("scenes.rs");
+    fs::write(&out, code)
+        .unwrap_or_else(|err| panic!("Unable to write `{}`: {}", out.display(), err));
+}
@@ -0,0 +1,299 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Derive macros of the game crate.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Expr, Fields, Ident, Lit, Meta, NestedMeta};
+
+/// Implements `ExportGroup` for a struct with named fields, so a
+/// `NativeClass` that embeds the struct can register its fields as
+/// properties with a single call. Only fields with an `#[export]` attribute
+/// are registered. The field's type must implement `ExportValue`, or
+/// `ExportGroup` when it is a nested group.
+///
+/// Options of the `#[export(...)]` attribute:
+/// - `name = "..."`, name of the property, defaults to the field's name;
+/// - `default = ...`, default value, defaults to the value of the field in
+///   the default of the group;
+/// - `no_default`, registers the property without a default value;
+/// - `range(min, max)` or `range(min, max, step)`, a range hint;
+/// - `hint = "..."`, an expression of any other hint;
+/// - `group`, registers the fields of a nested `ExportGroup`;
+/// - `prefix = "..."`, prefix of the property names of a nested group.
+///
+/// The class is notified of changes to the properties when the group is
+/// registered with `register_group_with`.
+///
+/// ```ignore
+/// #[derive(Default, ExportGroup)]
+/// pub struct Weapon {
+///     #[export(range(0.0, 5.0, 0.1))]
+///     pub cooldown: f64,
+///     #[export(group, prefix = "ammo_")]
+///     pub ammo: Ammo,
+/// }
+/// ```
+#[proc_macro_derive(ExportGroup, attributes(export))]
+pub fn derive_export_group(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match export_group(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct ExportAttr {
+    name: Option<String>,
+    default: Option<Expr>,
+    no_default: bool,
+    range: Option<Vec<Lit>>,
+    hint: Option<Expr>,
+    group: bool,
+    prefix: Option<String>,
+}
+
+fn export_group(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "ExportGroup can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "ExportGroup can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut registrations = Vec::new();
+    for field in fields.iter() {
+        let attr = match parse_export_attr(&field.attrs)? {
+            Some(attr) => attr,
+            None => continue,
+        };
+
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        registrations.push(if attr.group {
+            register_group(ident, ty, &attr)?
+        } else {
+            register_field(ident, ty, &attr)?
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::utils::ExportGroup for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn register_group_with<C, G, M, N>(
+                builder: &::gdnative::prelude::ClassBuilder<C>,
+                prefix: &str,
+                default: &Self,
+                get: G,
+                get_mut: M,
+                changed: N,
+            ) where
+                C: ::gdnative::prelude::NativeClass,
+                C::UserData: ::gdnative::nativescript::Map + ::gdnative::prelude::user_data::MapMut,
+                G: Fn(&C) -> &Self + Clone + 'static,
+                M: Fn(&mut C) -> &mut Self + Clone + 'static,
+                N: Fn(&mut C, ::gdnative::prelude::TRef<C::Base>) + Clone + 'static,
+            {
+                #(#registrations)*
+            }
+        }
+    })
+}
+
+fn register_field(ident: &Ident, ty: &syn::Type, attr: &ExportAttr) -> syn::Result<TokenStream2> {
+    let name = attr.name.clone().unwrap_or_else(|| ident.to_string());
+    if attr.prefix.is_some() {
+        return Err(syn::Error::new(
+            ident.span(),
+            "`prefix` can only be used together with `group`",
+        ));
+    }
+
+    let default = match (&attr.default, attr.no_default) {
+        (Some(_), true) => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "`default` and `no_default` cannot be used together",
+            ))
+        }
+        (_, true) => quote! {},
+        (Some(value), false) => quote! {
+            .with_default({
+                let value: #ty = #value;
+                crate::utils::ExportValue::to_export(&value)
+            })
+        },
+        (None, false) => quote! {
+            .with_default(crate::utils::ExportValue::to_export(&default.#ident))
+        },
+    };
+
+    let hint = match (&attr.range, &attr.hint) {
+        (Some(_), Some(_)) => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "`range` and `hint` cannot be used together",
+            ))
+        }
+        (Some(range), None) => {
+            let (min, max) = (&range[0], &range[1]);
+            let step = match range.get(2) {
+                Some(step) => quote! { Some(#step as f64) },
+                None => quote! { None },
+            };
+            quote! {
+                .with_hint(<<#ty as crate::utils::ExportValue>::Export as crate::utils::RangeExport>::range_hint(
+                    #min as f64,
+                    #max as f64,
+                    #step,
+                ))
+            }
+        }
+        (None, Some(hint)) => quote! { .with_hint(#hint) },
+        (None, None) => quote! {},
+    };
+
+    Ok(quote! {
+        {
+            let get = get.clone();
+            let get_mut = get_mut.clone();
+            let changed = changed.clone();
+            builder
+                .add_property::<<#ty as crate::utils::ExportValue>::Export>(
+                    format!("{}{}", prefix, #name).as_str(),
+                )
+                #default
+                #hint
+                .with_setter(move |t: &mut C, owner, value| {
+                    crate::utils::ExportValue::set_export(&mut get_mut(t).#ident, value);
+                    changed(t, owner);
+                })
+                .with_getter(move |t: &C, _| crate::utils::ExportValue::to_export(&get(t).#ident))
+                .done();
+        }
+    })
+}
+
+fn register_group(ident: &Ident, ty: &syn::Type, attr: &ExportAttr) -> syn::Result<TokenStream2> {
+    if attr.name.is_some()
+        || attr.default.is_some()
+        || attr.no_default
+        || attr.range.is_some()
+        || attr.hint.is_some()
+    {
+        return Err(syn::Error::new(
+            ident.span(),
+            "only `prefix` can be used together with `group`",
+        ));
+    }
+
+    let prefix = attr.prefix.clone().unwrap_or_default();
+    Ok(quote! {
+        {
+            let get = get.clone();
+            let get_mut = get_mut.clone();
+            <#ty as crate::utils::ExportGroup>::register_group_with(
+                builder,
+                format!("{}{}", prefix, #prefix).as_str(),
+                &default.#ident,
+                crate::utils::field_ref(get, |group: &Self| &group.#ident),
+                crate::utils::field_mut(get_mut, |group: &mut Self| &mut group.#ident),
+                changed.clone(),
+            );
+        }
+    })
+}
+
+fn parse_export_attr(attrs: &[syn::Attribute]) -> syn::Result<Option<ExportAttr>> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("export")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    let mut export = ExportAttr::default();
+    let list = match attr.parse_meta()? {
+        Meta::Path(_) => return Ok(Some(export)),
+        Meta::List(list) => list,
+        meta => return Err(syn::Error::new(meta.span(), "expected `#[export(...)]`")),
+    };
+
+    for nested in list.nested.iter() {
+        let meta = match nested {
+            NestedMeta::Meta(meta) => meta,
+            NestedMeta::Lit(lit) => return Err(syn::Error::new(lit.span(), "unexpected literal")),
+        };
+
+        match meta {
+            Meta::Path(path) if path.is_ident("group") => export.group = true,
+            Meta::Path(path) if path.is_ident("no_default") => export.no_default = true,
+            Meta::NameValue(pair) if pair.path.is_ident("name") => {
+                export.name = Some(lit_str(&pair.lit)?);
+            }
+            Meta::NameValue(pair) if pair.path.is_ident("prefix") => {
+                export.prefix = Some(lit_str(&pair.lit)?);
+            }
+            Meta::NameValue(pair) if pair.path.is_ident("default") => {
+                export.default = Some(lit_expr(&pair.lit)?);
+            }
+            Meta::NameValue(pair) if pair.path.is_ident("hint") => {
+                export.hint = Some(lit_expr(&pair.lit)?);
+            }
+            Meta::List(range) if range.path.is_ident("range") => {
+                let values = range
+                    .nested
+                    .iter()
+                    .map(|value| match value {
+                        NestedMeta::Lit(lit) => Ok(lit.clone()),
+                        _ => Err(syn::Error::new(value.span(), "expected a number")),
+                    })
+                    .collect::<syn::Result<Vec<_>>>()?;
+
+                if values.len() != 2 && values.len() != 3 {
+                    return Err(syn::Error::new(
+                        range.span(),
+                        "expected `range(min, max)` or `range(min, max, step)`",
+                    ));
+                }
+                export.range = Some(values);
+            }
+            meta => return Err(syn::Error::new(meta.span(), "unknown export option")),
+        }
+    }
+    Ok(Some(export))
+}
+
+fn lit_str(lit: &Lit) -> syn::Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        _ => Err(syn::Error::new(lit.span(), "expected a string")),
+    }
+}
+
+/// A string literal is parsed as an expression, so values that are not
+/// literals can be used as well, e.g. `default = "-1.0"`.
+fn lit_expr(lit: &Lit) -> syn::Result<Expr> {
+    match lit {
+        Lit::Str(s) => s.parse(),
+        lit => Ok(syn::parse_quote!(#lit)),
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Converts a map scene into a level file, next to the scene by default, and
+//! checks that the written file reads back into the same level. Obstacles the
+//! obstacle atlas of the Godot project does not define are reported.
+//!
+//!     cargo run --bin export_level <scene.tscn> [output.level]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use game::level::{export_scene, Level, LEVEL_EXTENSION};
+use game::obstacle::{ObstacleAtlas, RES_OBSTACLE_ATLAS};
+use game::tscn::{SceneFile, SceneLoader};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let scene_path = match args.next() {
+        Some(path) => PathBuf::from(path),
+        None => {
+            eprintln!("usage: export_level <scene.tscn> [output.level]");
+            process::exit(2);
+        }
+    };
+    let level_path = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| scene_path.with_extension(LEVEL_EXTENSION));
+
+    let scene = fs::read_to_string(&scene_path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| text.parse::<SceneFile>().map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| {
+            eprintln!("{}: {}", scene_path.display(), err);
+            process::exit(2);
+        });
+    let export = export_scene(&scene).unwrap_or_else(|err| {
+        eprintln!("{}: {}", scene_path.display(), err);
+        process::exit(1);
+    });
+
+    for warning in export.warnings.iter() {
+        eprintln!("warning: {}", warning);
+    }
+    match read_atlas(scene_path.as_path()) {
+        Ok(atlas) => {
+            for name in export.level.unknown_obstacles(&atlas) {
+                eprintln!("warning: the obstacle atlas does not define `{}`", name);
+            }
+        }
+        Err(err) => eprintln!("warning: obstacles not checked, {}", err),
+    }
+
+    let text = export.level.to_string();
+    match text.parse::<Level>() {
+        Ok(level) if level == export.level => {}
+        Ok(_) => {
+            eprintln!("the written level differs from the exported level");
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("the written level cannot be read, {}", err);
+            process::exit(1);
+        }
+    }
+
+    if let Err(err) = fs::write(&level_path, text) {
+        eprintln!("{}: {}", level_path.display(), err);
+        process::exit(2);
+    }
+
+    let level = &export.level;
+    println!(
+        "{}: {}x{} tiles, {} path(s), {} turret(s), {} obstacle(s), {} pickup(s)",
+        level_path.display(),
+        level.tiles.width,
+        level.tiles.height,
+        level.paths.len(),
+        level.turrets.len(),
+        level.obstacles.len(),
+        level.pickups.len()
+    );
+}
+
+/// Reads the obstacle atlas of the Godot project `scene` is part of.
+fn read_atlas(scene: &Path) -> Result<ObstacleAtlas, String> {
+    let dir = scene
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("project.godot").is_file())
+        .ok_or_else(|| String::from("the scene is not part of a Godot project"))?;
+    let path = SceneLoader::new(dir).file_path(RES_OBSTACLE_ATLAS);
+    fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| text.parse::<ObstacleAtlas>().map_err(|err| err.to_string()))
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
@@ -0,0 +1,90 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Generates maps without the engine and checks that the same seed gives the
+//! same map, that every objective can be reached from the player spawn and
+//! that the map survives a round trip through the level format.
+//! Checks `count` seeds starting at `seed`, 1 and 100 by default, with the
+//! obstacle atlas of the Godot project in `godot`. The tests of
+//! `game::mapgen` make the same checks with a fixed atlas, this checks any
+//! range of seeds against the atlas the game uses.
+//!
+//!     cargo run --bin generate_map [seed] [count]
+
+use std::env;
+use std::fs;
+use std::process;
+
+use game::level::Level;
+use game::mapgen::{generate, GeneratorSettings};
+use game::obstacle::{ObstacleAtlas, RES_OBSTACLE_ATLAS};
+use game::tscn::SceneLoader;
+
+fn main() {
+    let mut args = env::args().skip(1).map(|arg| {
+        arg.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("`{}` is not a number", arg);
+            process::exit(2);
+        })
+    });
+    let first = args.next().unwrap_or(1);
+    let count = args.next().unwrap_or(100);
+
+    let atlas_path = SceneLoader::new("godot").file_path(RES_OBSTACLE_ATLAS);
+    let atlas = fs::read_to_string(&atlas_path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| text.parse::<ObstacleAtlas>().map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| {
+            eprintln!("{}: {}", atlas_path.display(), err);
+            process::exit(2);
+        });
+
+    let settings = GeneratorSettings::default();
+    let mut failures = 0;
+    for seed in first..first.saturating_add(count) {
+        let map = generate(seed, &settings, &atlas);
+        let again = generate(seed, &settings, &atlas);
+        if map != again || map.digest() != again.digest() {
+            eprintln!("seed {}: map differs between runs", seed);
+            failures += 1;
+        }
+
+        let unreachable = map.unreachable_objectives();
+        if !unreachable.is_empty() {
+            eprintln!(
+                "seed {}: {} objective(s) cannot be reached from the spawn",
+                seed,
+                unreachable.len()
+            );
+            failures += 1;
+        }
+
+        let level = map.to_level();
+        if level.to_string().parse::<Level>().as_ref() != Ok(&level) {
+            eprintln!("seed {}: level differs after writing and reading it", seed);
+            failures += 1;
+        }
+
+        if count == 1 {
+            println!(
+                "seed {}: digest {:016x}, {} obstacle(s), {} patrol(s), {} turret(s), {} objective(s)",
+                seed,
+                map.digest(),
+                map.obstacles.len(),
+                map.patrols.len(),
+                map.turrets.len(),
+                map.objectives.len()
+            );
+        }
+    }
+
+    if failures > 0 {
+        eprintln!("{} seed(s) checked, {} failure(s)", count, failures);
+        process::exit(1);
+    }
+    println!(
+        "{} seed(s) checked, all maps reproducible and reachable",
+        count
+    );
+}
@@ -0,0 +1,42 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Checks the scenes in the Godot project directory, `godot` by default,
+//! against the nodes the native classes expect.
+//!
+//!     cargo run --bin validate_scenes [godot dir]
+
+use std::env;
+use std::process;
+
+use game::validate::validate_project;
+
+fn main() {
+    let dir = env::args().nth(1).unwrap_or_else(|| String::from("godot"));
+    let report = match validate_project(dir.as_str()) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    for err in report.errors.iter() {
+        eprintln!("error: {}", err);
+    }
+    for mismatch in report.mismatches.iter() {
+        eprintln!("mismatch: {}", mismatch);
+    }
+
+    if !report.is_ok() {
+        eprintln!(
+            "{} scene(s) checked, {} mismatch(es), {} error(s)",
+            report.scenes,
+            report.mismatches.len(),
+            report.errors.len()
+        );
+        process::exit(1);
+    }
+    println!("{} scene(s) checked, no mismatches", report.scenes);
+}
@@ -4,11 +4,16 @@
 
 use std::ops::{Add, Mul};
 
-use gdnative::api::{AnimatedSprite, Area2D};
+use gdnative::api::Area2D;
 use gdnative::prelude::*;
 
+use crate::damage::{Damage, DamageType};
+use crate::events::{self, BulletHit};
+use crate::obstacle::GROUP_SHOOT_THROUGH;
+use crate::scenes::bullets::bullet_tscn;
+use crate::status::{parse_status_effect, StatusEffect};
 use crate::tank;
-use crate::utils::node::get_node_as;
+use crate::utils::node::try_get_child_as;
 use crate::utils::*;
 
 #[derive(NativeClass)]
@@ -18,9 +23,18 @@ pub struct Bullet {
     speed: f32,
     #[property(default = 10)]
     damage: u8,
+    /// One of `kinetic`, `impact`, `fire` or `energy`, defaults to `kinetic`.
+    #[property]
+    damage_type: String,
     #[property(default = 1.0)]
     lifetime: f64,
+    /// Status effect applied to the body that is hit, e.g. `burning:3:4`.
+    #[property]
+    status_effect: String,
 
+    typ: DamageType,
+    effect: Option<StatusEffect>,
+    shooter: Option<i64>,
     velocity: Vector2,
     exploding: bool,
 }
@@ -31,20 +45,45 @@ impl Bullet {
         Bullet {
             speed: 750.0,
             damage: 10,
+            damage_type: String::new(),
             lifetime: 1.0,
+            status_effect: String::new(),
+            typ: DamageType::default(),
+            effect: None,
+            shooter: None,
             velocity: Vector2::zero(),
             exploding: false,
         }
     }
 
-    pub fn start(&mut self, owner: TRef<Area2D>, position: Vector2, direction: Vector2) {
+    /// Fires the bullet, `shooter_id` is the instance id of the tank that
+    /// fired it, or zero when it is unknown.
+    pub fn start(
+        &mut self,
+        owner: TRef<Area2D>,
+        position: Vector2,
+        direction: Vector2,
+        shooter_id: i64,
+    ) {
+        self.shooter = if shooter_id != 0 {
+            Some(shooter_id)
+        } else {
+            None
+        };
         owner.set_position(position);
         owner.set_rotation(direction.y.atan2(direction.x) as f64);
         self.velocity = direction.mul(self.speed);
 
-        let timer = get_node_as::<Timer>(owner.as_ref(), "Lifetime");
-        timer.set_wait_time(self.lifetime);
-        timer.start(0.0);
+        match report_error(try_get_child_as::<bullet_tscn::LifetimeNode>(
+            owner.as_ref(),
+        )) {
+            Some(timer) => {
+                timer.set_wait_time(self.lifetime);
+                timer.start(0.0);
+            }
+            // without a lifetime a bullet that hits nothing flies forever
+            None => owner.queue_free(),
+        }
     }
 
     pub fn explode(&mut self, owner: TRef<Area2D>) -> bool {
@@ -56,18 +95,37 @@ impl Bullet {
         self.velocity.x = 0.0;
         self.velocity.y = 0.0;
 
-        let owner = owner.as_ref();
-        get_node_as::<Sprite>(owner, "Sprite").hide();
+        if let Some(sprite) =
+            report_error(try_get_child_as::<bullet_tscn::SpriteNode>(owner.as_ref()))
+        {
+            sprite.hide();
+        }
 
-        let explosion = get_node_as::<AnimatedSprite>(owner, "Explosion");
-        explosion.show();
-        explosion.play("smoke", false);
+        match report_error(try_get_child_as::<bullet_tscn::ExplosionNode>(
+            owner.as_ref(),
+        )) {
+            Some(explosion) => {
+                explosion.show();
+                explosion.play(bullet_tscn::ANIM_SMOKE, false);
+            }
+            // the bullet is freed once its explosion finishes
+            None => owner.queue_free(),
+        }
 
         true
     }
 
     #[export]
-    fn _ready(&self, _owner: TRef<Area2D>) {}
+    fn _ready(&mut self, _owner: TRef<Area2D>) {
+        self.typ = self.damage_type.parse().unwrap_or_else(|err| {
+            godot_warn!("{}", err);
+            DamageType::default()
+        });
+        self.effect = parse_status_effect(self.status_effect.as_str()).unwrap_or_else(|err| {
+            godot_warn!("{}", err);
+            None
+        });
+    }
 
     #[export]
     fn _process(&mut self, owner: TRef<Area2D>, delta: f32) {
@@ -77,8 +135,32 @@ impl Bullet {
     #[allow(non_snake_case)]
     #[export]
     fn _on_Bullet_body_entered(&mut self, owner: TRef<Area2D>, body: Ref<Node>) {
-        if self.explode(owner) {
-            tank::take_damage(body, self.damage);
+        if unsafe { body.assume_safe() }.is_in_group(GROUP_SHOOT_THROUGH) {
+            return;
+        }
+        if !self.explode(owner) {
+            return;
+        }
+
+        let target = unsafe { body.assume_safe() };
+        let is_damage_taker = target.is_in_group(tank::GROUP_DAMAGE_TAKER);
+        let target = target.get_instance_id();
+        if let Some(effect) = self.effect {
+            tank::apply_status_effect(body, effect);
+        }
+        tank::take_damage(
+            body,
+            Damage::new(self.damage, self.typ).with_source(self.shooter),
+        );
+
+        if is_damage_taker {
+            events::publish(
+                owner.as_ref(),
+                BulletHit {
+                    shooter: self.shooter.unwrap_or(0),
+                    target,
+                },
+            );
         }
     }
 
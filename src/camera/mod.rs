@@ -0,0 +1,295 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::api::{Camera2D, GlobalConstants, InputEvent, InputEventMouseButton, Sprite, OS};
+use gdnative::prelude::*;
+
+pub use rig::*;
+
+use crate::events::{self, TankDamaged, TankDestroyed, TankFired};
+use crate::scenes::tanks::tank_tscn;
+use crate::scenes::ChildNodeRef;
+use crate::units::{self, UnitKind};
+use crate::utils::node::try_get_node_as;
+use crate::utils::*;
+
+mod rig;
+
+/// Toggles the spectator camera, only in debug builds.
+pub const ACTION_SPECTATOR: &str = "camera_spectator";
+
+/// Limits that are large enough to never stop the spectator camera.
+const SPECTATOR_LIMITS: Rect2 = Rect2::new(
+    Point2::new(-10_000_000.0, -10_000_000.0),
+    Size2::new(20_000_000.0, 20_000_000.0),
+);
+
+const ACTION_PAN_LEFT: &str = "ui_left";
+const ACTION_PAN_RIGHT: &str = "ui_right";
+const ACTION_PAN_UP: &str = "ui_up";
+const ACTION_PAN_DOWN: &str = "ui_down";
+
+/// Follows the tank it is a child of, with screen shake on shots, hits and
+/// explosions, look-ahead towards where the turret aims and a zoom that
+/// depends on the tank's speed and the enemies around it.
+#[derive(NativeClass)]
+#[inherit(Camera2D)]
+#[register_with(Self::register)]
+pub struct CameraController {
+    settings: CameraSettings,
+
+    shake: Shake,
+    limits: Limits,
+    look_ahead: Vector2,
+    zoom: f32,
+    last_target_position: Option<Vector2>,
+    spectator: bool,
+    rest_position: Vector2,
+}
+
+#[methods]
+impl CameraController {
+    fn register(builder: &ClassBuilder<Self>) {
+        CameraSettings::register(
+            builder,
+            |c: &Self| &c.settings,
+            |c: &mut Self| &mut c.settings,
+        );
+    }
+
+    fn new(_owner: TRef<Camera2D>) -> Self {
+        let settings = CameraSettings::default();
+        CameraController {
+            zoom: settings.zoom.base,
+            settings,
+
+            shake: Shake::new(),
+            limits: Limits::new(),
+            look_ahead: Vector2::zero(),
+            last_target_position: None,
+            spectator: false,
+            rest_position: Vector2::zero(),
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: TRef<Camera2D>) {
+        self.zoom = self.settings.zoom.base;
+        self.rest_position = owner.position();
+        owner.set_zoom(Vector2::new(self.zoom, self.zoom));
+
+        let node = owner.upcast::<Node>().as_ref();
+        events::subscribe::<TankFired, _>(node, owner, "_on_Events_tank_fired", 0);
+        events::subscribe::<TankDamaged, _>(node, owner, "_on_Events_tank_damaged", 0);
+        events::subscribe::<TankDestroyed, _>(node, owner, "_on_Events_tank_destroyed", 0);
+    }
+
+    #[export]
+    fn _process(&mut self, owner: TRef<Camera2D>, delta: f32) {
+        if self.spectator {
+            self.move_spectator(owner, delta);
+        } else {
+            self.follow_target(owner, delta);
+        }
+
+        if let Some(limits) = self
+            .limits
+            .step(self.settings.limit_smoothing, delta)
+            .filter(|_| !self.spectator)
+        {
+            apply_limits(owner, limits);
+        }
+    }
+
+    #[export]
+    fn _unhandled_input(&mut self, owner: TRef<Camera2D>, event: Ref<InputEvent>) {
+        let event = unsafe { event.assume_safe() };
+        if event.is_action_pressed(ACTION_SPECTATOR, false)
+            && OS::godot_singleton().is_debug_build()
+        {
+            self.set_spectator(owner, !self.spectator);
+            return;
+        }
+        if !self.spectator || !event.is_pressed() {
+            return;
+        }
+
+        if let Some(button) = event.cast::<InputEventMouseButton>() {
+            let step = match button.button_index() {
+                GlobalConstants::BUTTON_WHEEL_UP => -self.settings.spectator.zoom_step,
+                GlobalConstants::BUTTON_WHEEL_DOWN => self.settings.spectator.zoom_step,
+                _ => return,
+            };
+            self.zoom = (self.zoom + step).max(0.1);
+            owner.set_zoom(Vector2::new(self.zoom, self.zoom));
+        }
+    }
+
+    /// Limits the camera to `limits`, the camera moves to them smoothly when
+    /// it already had limits.
+    #[export]
+    pub fn set_limits(&mut self, owner: TRef<Camera2D>, limits: Rect2) {
+        let first = self.limits.current().is_none();
+        self.limits.set_target(limits);
+        if first && !self.spectator {
+            apply_limits(owner, limits);
+        }
+    }
+
+    #[export]
+    pub fn add_trauma(&mut self, _owner: TRef<Camera2D>, amount: f32) {
+        self.shake.add_trauma(amount);
+    }
+
+    #[export]
+    pub fn is_spectator(&self, _owner: TRef<Camera2D>) -> bool {
+        self.spectator
+    }
+
+    /// Detaches the camera from the tank so it can be moved freely, or puts
+    /// it back.
+    #[export]
+    pub fn set_spectator(&mut self, owner: TRef<Camera2D>, enabled: bool) {
+        if self.spectator == enabled {
+            return;
+        }
+        self.spectator = enabled;
+
+        let position = owner.global_position();
+        owner.set_as_toplevel(enabled);
+        owner.set_offset(Vector2::zero());
+        if enabled {
+            owner.set_global_position(position);
+            apply_limits(owner, SPECTATOR_LIMITS);
+        } else {
+            owner.set_position(self.rest_position);
+            self.last_target_position = None;
+            if let Some(limits) = self.limits.current() {
+                apply_limits(owner, limits);
+            }
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_tank_fired(&mut self, owner: TRef<Camera2D>, event: TankFired) {
+        if is_target(owner, event.tank) {
+            self.shake.add_trauma(self.settings.shake.shot_trauma);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_tank_damaged(&mut self, owner: TRef<Camera2D>, event: TankDamaged) {
+        if event.amount > 0 && is_target(owner, event.tank) {
+            self.shake.add_trauma(self.settings.shake.hit_trauma);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_tank_destroyed(&mut self, owner: TRef<Camera2D>, event: TankDestroyed) {
+        let distance = owner.get_camera_screen_center().distance_to(event.position);
+
+        self.shake
+            .add_trauma(explosion_trauma(&self.settings.shake, distance));
+    }
+
+    fn follow_target(&mut self, owner: TRef<Camera2D>, delta: f32) {
+        let target = match target_of(owner) {
+            Some(target) => target,
+            None => return,
+        };
+
+        let position = target.global_position();
+        let speed = match self.last_target_position.replace(position) {
+            Some(last) if delta > 0.0 => last.distance_to(position) / delta,
+            _ => 0.0,
+        };
+
+        let settings = &self.settings;
+        let aim = try_get_node_as::<Sprite>(
+            target.upcast::<Node>().as_ref(),
+            tank_tscn::TurretNode::path(),
+        )
+        .map(|turret| {
+            Vector2::new(1.0, 0.0).rotated(Angle::radians(turret.global_rotation() as f32))
+        })
+        .unwrap_or_else(|_| Vector2::zero());
+
+        self.look_ahead = smooth_vector(
+            self.look_ahead,
+            aim * settings.look_ahead.distance,
+            settings.look_ahead.smoothing,
+            delta,
+        );
+        self.zoom = smooth(
+            self.zoom,
+            settings.zoom.target(
+                speed,
+                count_enemies_around(target, settings.zoom.enemy_radius),
+            ),
+            settings.zoom.smoothing,
+            delta,
+        );
+
+        let shake = self.shake.step(&settings.shake, delta);
+        owner.set_offset(self.look_ahead + shake);
+        owner.set_zoom(Vector2::new(self.zoom, self.zoom));
+    }
+
+    fn move_spectator(&mut self, owner: TRef<Camera2D>, delta: f32) {
+        let input = Input::godot_singleton();
+        let direction = Vector2::new(
+            input.get_action_strength(ACTION_PAN_RIGHT) as f32
+                - input.get_action_strength(ACTION_PAN_LEFT) as f32,
+            input.get_action_strength(ACTION_PAN_DOWN) as f32
+                - input.get_action_strength(ACTION_PAN_UP) as f32,
+        );
+        if direction == Vector2::zero() {
+            return;
+        }
+
+        owner.set_global_position(
+            owner.global_position()
+                + direction.normalize() * self.settings.spectator.speed * self.zoom * delta,
+        );
+    }
+}
+
+impl InstanceFrom<Self, Camera2D> for CameraController {}
+
+/// The tank the camera follows, which is its parent.
+#[inline]
+fn target_of(owner: TRef<'_, Camera2D>) -> Option<TRef<'_, Node2D>> {
+    owner
+        .get_parent()
+        .map(|parent| unsafe { parent.assume_safe() })
+        .and_then(|parent| parent.cast::<Node2D>())
+}
+
+#[inline]
+fn is_target(owner: TRef<Camera2D>, tank: i64) -> bool {
+    target_of(owner).is_some_and(|target| target.get_instance_id() == tank)
+}
+
+/// Counts the enemy units within `radius` of `target`.
+fn count_enemies_around(target: TRef<Node2D>, radius: f32) -> usize {
+    let position = target.global_position();
+    units::with_registry(target.upcast::<Node>().as_ref(), |registry| {
+        registry
+            .of_kind(UnitKind::Enemy)
+            .filter(|unit| unit.position().distance_to(position) <= radius)
+            .count()
+    })
+    .unwrap_or_default()
+}
+
+#[inline]
+fn apply_limits(camera: TRef<Camera2D>, limits: Rect2) {
+    camera.set_limit(GlobalConstants::MARGIN_LEFT, limits.min_x() as i64);
+    camera.set_limit(GlobalConstants::MARGIN_TOP, limits.min_y() as i64);
+    camera.set_limit(GlobalConstants::MARGIN_RIGHT, limits.max_x() as i64);
+    camera.set_limit(GlobalConstants::MARGIN_BOTTOM, limits.max_y() as i64);
+}
@@ -0,0 +1,296 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::prelude::*;
+
+use crate::utils::ExportGroup;
+
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct ShakeSettings {
+    /// Trauma that is lost per second.
+    #[export(range(0.0, 5.0, 0.05))]
+    pub decay: f32,
+    /// Offset in pixels at full trauma.
+    #[export]
+    pub max_offset: f32,
+    /// How fast the camera shakes, in cycles per second.
+    #[export(range(1.0, 60.0, 1.0))]
+    pub frequency: f32,
+    /// Trauma added when the followed tank fires.
+    #[export(range(0.0, 1.0, 0.05))]
+    pub shot_trauma: f32,
+    /// Trauma added when the followed tank takes damage.
+    #[export(range(0.0, 1.0, 0.05))]
+    pub hit_trauma: f32,
+    /// Trauma added by an explosion right next to the camera, it falls off
+    /// to zero at `explosion_range`.
+    #[export(range(0.0, 1.0, 0.05))]
+    pub explosion_trauma: f32,
+    #[export]
+    pub explosion_range: f32,
+}
+
+impl Default for ShakeSettings {
+    fn default() -> Self {
+        ShakeSettings {
+            decay: 1.5,
+            max_offset: 24.0,
+            frequency: 18.0,
+            shot_trauma: 0.15,
+            hit_trauma: 0.35,
+            explosion_trauma: 0.7,
+            explosion_range: 900.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct LookAheadSettings {
+    /// Distance in pixels the camera moves towards where the turret aims.
+    #[export]
+    pub distance: f32,
+    #[export(range(0.0, 20.0, 0.5))]
+    pub smoothing: f32,
+}
+
+impl Default for LookAheadSettings {
+    fn default() -> Self {
+        LookAheadSettings {
+            distance: 120.0,
+            smoothing: 4.0,
+        }
+    }
+}
+
+/// The zoom of a `Camera2D` is the size of the visible area, a larger zoom
+/// shows more of the map.
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct ZoomSettings {
+    #[export(range(0.5, 4.0, 0.05))]
+    pub base: f32,
+    #[export(range(0.5, 4.0, 0.05))]
+    pub min: f32,
+    #[export(range(0.5, 4.0, 0.05))]
+    pub max: f32,
+    /// Zoom added when the followed tank drives at `full_speed`.
+    #[export(range(0.0, 2.0, 0.05))]
+    pub speed_zoom: f32,
+    #[export]
+    pub full_speed: f32,
+    /// Zoom added per enemy within `enemy_radius`.
+    #[export(range(0.0, 1.0, 0.01))]
+    pub enemy_zoom: f32,
+    #[export]
+    pub enemy_radius: f32,
+    #[export(range(0.0, 20.0, 0.5))]
+    pub smoothing: f32,
+}
+
+impl Default for ZoomSettings {
+    fn default() -> Self {
+        ZoomSettings {
+            base: 1.4,
+            min: 1.0,
+            max: 2.2,
+            speed_zoom: 0.25,
+            full_speed: 200.0,
+            enemy_zoom: 0.1,
+            enemy_radius: 700.0,
+            smoothing: 1.5,
+        }
+    }
+}
+
+impl ZoomSettings {
+    /// The zoom for a tank driving at `speed` with `enemies` enemies nearby.
+    pub fn target(&self, speed: f32, enemies: usize) -> f32 {
+        let speed_ratio = if self.full_speed > 0.0 {
+            (speed / self.full_speed).min(1.0)
+        } else {
+            0.0
+        };
+
+        let zoom = self.base + self.speed_zoom * speed_ratio + self.enemy_zoom * enemies as f32;
+        zoom.max(self.min).min(self.max)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct SpectatorSettings {
+    /// Speed in pixels per second at a zoom of 1.
+    #[export]
+    pub speed: f32,
+    /// Zoom change per mouse wheel step.
+    #[export(range(0.0, 1.0, 0.01))]
+    pub zoom_step: f32,
+}
+
+impl Default for SpectatorSettings {
+    fn default() -> Self {
+        SpectatorSettings {
+            speed: 800.0,
+            zoom_step: 0.1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct CameraSettings {
+    #[export(group, prefix = "shake_")]
+    pub shake: ShakeSettings,
+    #[export(group, prefix = "look_ahead_")]
+    pub look_ahead: LookAheadSettings,
+    #[export(group, prefix = "zoom_")]
+    pub zoom: ZoomSettings,
+    #[export(group, prefix = "spectator_")]
+    pub spectator: SpectatorSettings,
+    /// How fast the limits move when the map changes, zero moves them
+    /// at once.
+    #[export(range(0.0, 20.0, 0.5))]
+    pub limit_smoothing: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            shake: ShakeSettings::default(),
+            look_ahead: LookAheadSettings::default(),
+            zoom: ZoomSettings::default(),
+            spectator: SpectatorSettings::default(),
+            limit_smoothing: 5.0,
+        }
+    }
+}
+
+/// Trauma based screen shake. Trauma is between zero and one, the shake is
+/// the square of the trauma so small amounts are barely noticeable.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Shake {
+    trauma: f32,
+    time: f32,
+}
+
+impl Shake {
+    pub fn new() -> Self {
+        Shake::default()
+    }
+
+    #[inline]
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    /// Advances the shake by `delta` seconds and returns the offset of the
+    /// camera.
+    pub fn step(&mut self, settings: &ShakeSettings, delta: f32) -> Vector2 {
+        if self.trauma <= 0.0 {
+            self.time = 0.0;
+            return Vector2::zero();
+        }
+
+        self.time += delta;
+        let strength = self.trauma * self.trauma * settings.max_offset;
+        let t = self.time * settings.frequency;
+        self.trauma = (self.trauma - settings.decay * delta).max(0.0);
+
+        Vector2::new(noise(t, 0.0), noise(t, 31.7)) * strength
+    }
+}
+
+/// Smooth noise between -1 and 1 made of a few sine waves, `seed` picks a
+/// different curve.
+#[inline]
+fn noise(t: f32, seed: f32) -> f32 {
+    (t + seed).sin() * 0.5 + (t * 2.3 + seed * 1.3).sin() * 0.3 + (t * 4.7 + seed * 0.7).sin() * 0.2
+}
+
+/// Trauma of an explosion at `distance` from the camera.
+#[inline]
+pub fn explosion_trauma(settings: &ShakeSettings, distance: f32) -> f32 {
+    if settings.explosion_range <= 0.0 || distance >= settings.explosion_range {
+        return 0.0;
+    }
+
+    let falloff = 1.0 - distance / settings.explosion_range;
+    settings.explosion_trauma * falloff * falloff
+}
+
+/// Moves `current` towards `target`, covering the same part of the distance
+/// each second regardless of the frame rate. A `rate` of zero or less
+/// returns `target`.
+#[inline]
+pub fn smooth(current: f32, target: f32, rate: f32, delta: f32) -> f32 {
+    if rate <= 0.0 {
+        return target;
+    }
+    current + (target - current) * (1.0 - (-rate * delta).exp())
+}
+
+#[inline]
+pub fn smooth_vector(current: Vector2, target: Vector2, rate: f32, delta: f32) -> Vector2 {
+    Vector2::new(
+        smooth(current.x, target.x, rate, delta),
+        smooth(current.y, target.y, rate, delta),
+    )
+}
+
+/// The area the camera is limited to, which moves smoothly to a new area
+/// when the map changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    current: Option<Rect2>,
+    target: Option<Rect2>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Limits::default()
+    }
+
+    #[inline]
+    pub fn current(&self) -> Option<Rect2> {
+        self.current
+    }
+
+    /// Sets the area to move to. The first area is used at once.
+    #[inline]
+    pub fn set_target(&mut self, target: Rect2) {
+        if self.current.is_none() {
+            self.current = Some(target);
+        }
+        self.target = Some(target);
+    }
+
+    /// Advances the limits by `delta` seconds. Returns the new limits when
+    /// they changed.
+    pub fn step(&mut self, rate: f32, delta: f32) -> Option<Rect2> {
+        let (current, target) = match (self.current, self.target) {
+            (Some(current), Some(target)) => (current, target),
+            _ => return None,
+        };
+        if current == target {
+            return None;
+        }
+
+        let edges = [
+            (current.min_x(), target.min_x()),
+            (current.min_y(), target.min_y()),
+            (current.max_x(), target.max_x()),
+            (current.max_y(), target.max_y()),
+        ];
+        let next = if edges.iter().all(|(from, to)| (to - from).abs() < 0.5) {
+            target
+        } else {
+            let [min_x, min_y, max_x, max_y] =
+                edges.map(|(from, to)| smooth(from, to, rate, delta));
+            Rect2::new(
+                Point2::new(min_x, min_y),
+                Size2::new(max_x - min_x, max_y - min_y),
+            )
+        };
+
+        self.current = Some(next);
+        Some(next)
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A single physical input that can trigger an action. Bindings are stored
+/// in the controls config file using their `Display` representation, e.g.
+/// `key:87`, `mouse_button:1`, `joypad_button:0` or `joypad_axis:1+`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(i64),
+    MouseButton(i64),
+    JoypadButton(i64),
+    JoypadAxis(i64, bool),
+}
+
+/// The kind of device a binding is on. An action has at most one binding of
+/// each device, so it can be rebound on one without losing the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Device {
+    Keyboard,
+    Mouse,
+    Joypad,
+}
+
+impl Binding {
+    #[inline]
+    pub fn device(&self) -> Device {
+        match self {
+            Self::Key(_) => Device::Keyboard,
+            Self::MouseButton(_) => Device::Mouse,
+            Self::JoypadButton(_) | Self::JoypadAxis(_, _) => Device::Joypad,
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(scancode) => write!(f, "key:{}", scancode),
+            Self::MouseButton(index) => write!(f, "mouse_button:{}", index),
+            Self::JoypadButton(index) => write!(f, "joypad_button:{}", index),
+            Self::JoypadAxis(axis, positive) => {
+                write!(
+                    f,
+                    "joypad_axis:{}{}",
+                    axis,
+                    if *positive { '+' } else { '-' }
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid binding `{}`", s))?;
+
+        let parse = |v: &str| {
+            v.parse::<i64>()
+                .map_err(|_| format!("Invalid value in binding `{}`", s))
+        };
+
+        match kind {
+            "key" => Ok(Self::Key(parse(value)?)),
+            "mouse_button" => Ok(Self::MouseButton(parse(value)?)),
+            "joypad_button" => Ok(Self::JoypadButton(parse(value)?)),
+            "joypad_axis" => {
+                let positive = match value.chars().last() {
+                    Some('+') => true,
+                    Some('-') => false,
+                    _ => return Err(format!("Missing axis direction in binding `{}`", s)),
+                };
+                Ok(Self::JoypadAxis(
+                    parse(&value[..value.len() - 1])?,
+                    positive,
+                ))
+            }
+            _ => Err(format!("Unknown binding kind `{}`", kind)),
+        }
+    }
+}
+
+/// Formats a list of bindings as it is written to the controls config file.
+#[inline]
+pub fn format_bindings(bindings: &[Binding]) -> String {
+    bindings
+        .iter()
+        .map(Binding::to_string)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Parses a list of bindings as written by `format_bindings`. Invalid
+/// entries are skipped and reported in the returned error list.
+pub fn parse_bindings(value: &str) -> (Vec<Binding>, Vec<String>) {
+    let mut bindings = Vec::new();
+    let mut errors = Vec::new();
+
+    for part in value.split(',').filter(|part| !part.trim().is_empty()) {
+        match part.parse::<Binding>() {
+            Ok(binding) => bindings.push(binding),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    (bindings, errors)
+}
+
+/// Keeps the bindings of all actions, in the order the actions are listed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BindingMap {
+    actions: Vec<(String, Vec<Binding>)>,
+}
+
+impl BindingMap {
+    pub fn new() -> Self {
+        BindingMap {
+            actions: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.iter().map(|(action, _)| action.as_str())
+    }
+
+    #[inline]
+    pub fn get(&self, action: &str) -> &[Binding] {
+        self.actions
+            .iter()
+            .find(|(a, _)| a == action)
+            .map(|(_, bindings)| bindings.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn set(&mut self, action: &str, bindings: Vec<Binding>) {
+        match self.actions.iter_mut().find(|(a, _)| a == action) {
+            Some((_, current)) => *current = bindings,
+            None => self.actions.push((action.to_string(), bindings)),
+        }
+    }
+
+    /// Binds `binding` to `action` in place of the bindings of its device, the
+    /// bindings of other devices are kept.
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let device = binding.device();
+        let mut bindings = self.get(action).to_vec();
+        let at = bindings
+            .iter()
+            .position(|b| b.device() == device)
+            .unwrap_or(bindings.len());
+        bindings.retain(|b| b.device() != device);
+        bindings.insert(at, binding);
+        self.set(action, bindings);
+    }
+
+    /// Returns the actions, other than `action`, that are already triggered
+    /// by `binding`.
+    pub fn conflicts(&self, action: &str, binding: Binding) -> Vec<String> {
+        self.actions
+            .iter()
+            .filter(|(a, bindings)| a != action && bindings.contains(&binding))
+            .map(|(a, _)| a.clone())
+            .collect()
+    }
+
+    /// Removes `binding` from every action and returns the names of the
+    /// actions that have changed.
+    pub fn unbind(&mut self, binding: Binding) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (action, bindings) in self.actions.iter_mut() {
+            let len = bindings.len();
+            bindings.retain(|b| *b != binding);
+            if bindings.len() != len {
+                changed.push(action.clone());
+            }
+        }
+
+        changed
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::api::{
+    ConfigFile, InputEvent, InputEventJoypadButton, InputEventJoypadMotion, InputEventKey,
+    InputEventMouseButton, InputMap, OS,
+};
+use gdnative::prelude::*;
+
+pub use binding::*;
+
+use crate::utils::SingletonInstance;
+
+mod binding;
+
+pub const ACTION_FORWARD: &str = "forward";
+pub const ACTION_BACK: &str = "back";
+pub const ACTION_TURN_LEFT: &str = "turn_left";
+pub const ACTION_TURN_RIGHT: &str = "turn_right";
+pub const ACTION_SHOOT: &str = "click";
+
+/// All actions that can be rebound, in the order they are shown to the user.
+pub const ACTIONS: &[&str] = &[
+    ACTION_FORWARD,
+    ACTION_BACK,
+    ACTION_TURN_LEFT,
+    ACTION_TURN_RIGHT,
+    ACTION_SHOOT,
+];
+
+pub const SIGNAL_BINDING_CHANGED: &str = "binding_changed";
+pub const SIGNAL_BINDING_CONFLICT: &str = "binding_conflict";
+
+const CONFIG_PATH: &str = "user://controls.cfg";
+const CONFIG_SECTION: &str = "bindings";
+
+/// Autoloaded node that restores the user's key bindings at startup and
+/// captures new bindings for the controls settings screen.
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct Controls {
+    bindings: BindingMap,
+    capture_action: Option<String>,
+    pending_binding: Option<(String, Binding)>,
+}
+
+#[methods]
+impl Controls {
+    fn register(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: SIGNAL_BINDING_CHANGED,
+            args: &[SignalArgument {
+                name: "action",
+                default: Variant::from_str(""),
+                export_info: ExportInfo::new(VariantType::GodotString),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+
+        builder.add_signal(Signal {
+            name: SIGNAL_BINDING_CONFLICT,
+            args: &[
+                SignalArgument {
+                    name: "action",
+                    default: Variant::from_str(""),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "binding",
+                    default: Variant::from_str(""),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "conflicts",
+                    default: Variant::from_array(&VariantArray::new_shared()),
+                    export_info: ExportInfo::new(VariantType::VariantArray),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+    }
+
+    fn new(_owner: &Node) -> Self {
+        Controls {
+            bindings: BindingMap::new(),
+            capture_action: None,
+            pending_binding: None,
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, _owner: &Node) {
+        self.read_input_map();
+        self.load();
+    }
+
+    #[export]
+    fn _input(&mut self, owner: TRef<Node>, event: Ref<InputEvent>) {
+        if self.capture_action.is_none() {
+            return;
+        }
+
+        let event = unsafe { event.assume_safe() };
+        if !event.is_pressed() {
+            return;
+        }
+
+        let binding = match binding_from_event(event) {
+            Some(binding) => binding,
+            None => return,
+        };
+
+        if let Some(tree) = owner.get_tree() {
+            unsafe { tree.assume_safe() }.set_input_as_handled();
+        }
+
+        let action = self.capture_action.take().unwrap();
+        let conflicts = self.bindings.conflicts(action.as_str(), binding);
+        if conflicts.is_empty() {
+            self.bind(owner, action.as_str(), binding);
+            return;
+        }
+
+        owner.emit_signal(
+            SIGNAL_BINDING_CONFLICT,
+            &[
+                Variant::from_str(action.as_str()),
+                Variant::from_str(binding_text(binding)),
+                conflicts.to_variant(),
+            ],
+        );
+        self.pending_binding = Some((action, binding));
+    }
+
+    #[export]
+    fn get_actions(&self, _owner: &Node) -> Vec<String> {
+        ACTIONS.iter().map(|action| action.to_string()).collect()
+    }
+
+    #[export]
+    fn get_binding_texts(&self, _owner: &Node, action: String) -> Vec<String> {
+        self.bindings
+            .get(action.as_str())
+            .iter()
+            .map(|binding| binding_text(*binding))
+            .collect()
+    }
+
+    /// Starts listening for the next key, mouse button or joypad event, which
+    /// then replaces the binding of `action` on the same device.
+    #[export]
+    pub fn start_capture(&mut self, _owner: &Node, action: String) -> bool {
+        if !ACTIONS.contains(&action.as_str()) {
+            godot_warn!("Cannot rebind unknown action `{}`", action);
+            return false;
+        }
+
+        self.capture_action = Some(action);
+        self.pending_binding = None;
+        true
+    }
+
+    #[export]
+    pub fn cancel_capture(&mut self, _owner: &Node) {
+        self.capture_action = None;
+        self.pending_binding = None;
+    }
+
+    #[export]
+    pub fn is_capturing(&self, _owner: &Node) -> bool {
+        self.capture_action.is_some()
+    }
+
+    /// Resolves the conflict reported by the `binding_conflict` signal. When
+    /// `replace` is true the binding is taken away from the conflicting
+    /// actions, otherwise the captured binding is discarded.
+    #[export]
+    fn resolve_conflict(&mut self, owner: TRef<Node>, replace: bool) {
+        let (action, binding) = match self.pending_binding.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+        if !replace {
+            return;
+        }
+
+        for changed in self.bindings.unbind(binding) {
+            self.apply(changed.as_str());
+            owner.emit_signal(SIGNAL_BINDING_CHANGED, &[Variant::from_str(changed)]);
+        }
+        self.bind(owner, action.as_str(), binding);
+    }
+
+    /// Restores the bindings from `project.godot` and saves them as the
+    /// user's bindings.
+    #[export]
+    fn reset_to_defaults(&mut self, owner: TRef<Node>) {
+        InputMap::godot_singleton().load_from_globals();
+        self.read_input_map();
+        self.save();
+
+        for action in ACTIONS {
+            owner.emit_signal(SIGNAL_BINDING_CHANGED, &[Variant::from_str(action)]);
+        }
+    }
+
+    fn bind(&mut self, owner: TRef<Node>, action: &str, binding: Binding) {
+        self.bindings.bind(action, binding);
+        self.apply(action);
+        self.save();
+
+        owner.emit_signal(SIGNAL_BINDING_CHANGED, &[Variant::from_str(action)]);
+    }
+
+    /// Copies the current events of the `InputMap` into the binding map.
+    fn read_input_map(&mut self) {
+        let input_map = InputMap::godot_singleton();
+        for action in ACTIONS {
+            if !input_map.has_action(*action) {
+                input_map.add_action(*action, 0.5);
+            }
+
+            let bindings = input_map
+                .get_action_list(*action)
+                .iter()
+                .filter_map(|event| event.try_to_object::<InputEvent>())
+                .filter_map(|event| binding_from_event(unsafe { event.assume_safe() }))
+                .collect();
+
+            self.bindings.set(action, bindings);
+        }
+    }
+
+    /// Replaces the events of `action` in the `InputMap` with its bindings.
+    fn apply(&self, action: &str) {
+        let input_map = InputMap::godot_singleton();
+        input_map.action_erase_events(action);
+        for binding in self.bindings.get(action) {
+            input_map.action_add_event(action, binding_to_event(*binding));
+        }
+    }
+
+    fn load(&mut self) {
+        let config = ConfigFile::new();
+        if config.load(CONFIG_PATH).is_err() {
+            // no custom bindings saved yet
+            return;
+        }
+
+        for action in ACTIONS {
+            if !config.has_section_key(CONFIG_SECTION, *action) {
+                continue;
+            }
+
+            let value = config
+                .get_value(CONFIG_SECTION, *action, Variant::new())
+                .try_to_string()
+                .unwrap_or_default();
+
+            let (bindings, errors) = parse_bindings(value.as_str());
+            for err in errors {
+                godot_warn!("{} in `{}`", err, CONFIG_PATH);
+            }
+
+            self.bindings.set(action, bindings);
+            self.apply(action);
+        }
+    }
+
+    fn save(&self) {
+        let config = ConfigFile::new();
+        for action in self.bindings.actions() {
+            config.set_value(
+                CONFIG_SECTION,
+                action,
+                format_bindings(self.bindings.get(action)),
+            );
+        }
+
+        if let Err(err) = config.save(CONFIG_PATH) {
+            godot_error!("Failed to save `{}`: {:?}", CONFIG_PATH, err);
+        }
+    }
+}
+
+impl SingletonInstance<Self, Node> for Controls {
+    #[inline]
+    fn node_path<'a>() -> &'a str {
+        "/root/Controls"
+    }
+}
+
+/// Returns true while the controls settings are waiting for a new binding,
+/// so gameplay code can ignore that input.
+#[inline]
+pub fn is_capturing(node: &Node) -> bool {
+    Controls::try_singleton(node)
+        .and_then(|controls| controls.map(|c, o| c.is_capturing(o.as_ref())).ok())
+        .unwrap_or(false)
+}
+
+fn binding_from_event(event: TRef<InputEvent>) -> Option<Binding> {
+    if let Some(key) = event.cast::<InputEventKey>() {
+        return Some(Binding::Key(key.scancode()));
+    }
+    if let Some(button) = event.cast::<InputEventMouseButton>() {
+        return Some(Binding::MouseButton(button.button_index()));
+    }
+    if let Some(button) = event.cast::<InputEventJoypadButton>() {
+        return Some(Binding::JoypadButton(button.button_index()));
+    }
+    if let Some(motion) = event.cast::<InputEventJoypadMotion>() {
+        return Some(Binding::JoypadAxis(
+            motion.axis(),
+            motion.axis_value() > 0.0,
+        ));
+    }
+
+    None
+}
+
+fn binding_to_event(binding: Binding) -> Ref<InputEvent, Unique> {
+    match binding {
+        Binding::Key(scancode) => {
+            let event = InputEventKey::new();
+            event.set_scancode(scancode);
+            event.upcast()
+        }
+        Binding::MouseButton(index) => {
+            let event = InputEventMouseButton::new();
+            event.set_button_index(index);
+            event.upcast()
+        }
+        Binding::JoypadButton(index) => {
+            let event = InputEventJoypadButton::new();
+            event.set_button_index(index);
+            event.upcast()
+        }
+        Binding::JoypadAxis(axis, positive) => {
+            let event = InputEventJoypadMotion::new();
+            event.set_axis(axis);
+            event.set_axis_value(if positive { 1.0 } else { -1.0 });
+            event.upcast()
+        }
+    }
+}
+
+/// Human readable text of a binding, to show on the controls settings screen.
+fn binding_text(binding: Binding) -> String {
+    match binding {
+        Binding::Key(scancode) => OS::godot_singleton()
+            .get_scancode_string(scancode)
+            .to_string(),
+        Binding::MouseButton(index) => format!("Mouse {}", index),
+        Binding::JoypadButton(index) => Input::godot_singleton()
+            .get_joy_button_string(index)
+            .to_string(),
+        Binding::JoypadAxis(axis, positive) => format!(
+            "{} {}",
+            Input::godot_singleton().get_joy_axis_string(axis),
+            if positive { '+' } else { '-' }
+        ),
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::str::FromStr;
+
+use gdnative::prelude::*;
+
+use crate::utils::{ExportGroup, ExportValue};
+
+/// The kind of damage a tank takes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DamageType {
+    #[default]
+    Kinetic,
+    Impact,
+    Fire,
+    Energy,
+}
+
+impl DamageType {
+    #[inline]
+    pub fn all() -> Vec<DamageType> {
+        vec![Self::Kinetic, Self::Impact, Self::Fire, Self::Energy]
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Kinetic => "kinetic",
+            Self::Impact => "impact",
+            Self::Fire => "fire",
+            Self::Energy => "energy",
+        }
+    }
+}
+
+impl FromStr for DamageType {
+    type Err = String;
+
+    /// Parses the name of a damage type, an empty string is the default type.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+
+        Self::all()
+            .into_iter()
+            .find(|typ| typ.name().eq(s))
+            .ok_or_else(|| format!("Unknown damage type `{}`", s))
+    }
+}
+
+impl ExportValue for Vec<DamageType> {
+    type Export = StringArray;
+
+    fn to_export(&self) -> StringArray {
+        StringArray::from_vec(
+            self.iter()
+                .map(|typ| GodotString::from_str(typ.name()))
+                .collect(),
+        )
+    }
+
+    fn set_export(&mut self, value: StringArray) {
+        self.clear();
+        for name in value.read().iter() {
+            match name.to_string().parse::<DamageType>() {
+                Ok(typ) => self.push(typ),
+                Err(err) => godot_warn!("{}", err),
+            }
+        }
+    }
+}
+
+/// A single hit on a tank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Damage {
+    pub amount: u8,
+    pub typ: DamageType,
+    /// Instance id of the tank that caused the damage, when known.
+    pub source: Option<i64>,
+}
+
+impl Damage {
+    #[inline]
+    pub fn new(amount: u8, typ: DamageType) -> Self {
+        Damage {
+            amount,
+            typ,
+            source: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_source(self, source: Option<i64>) -> Self {
+        Damage { source, ..self }
+    }
+}
+
+/// A layer on top of a tank's health that absorbs damage first. After not
+/// taking any hits for `regen_delay` seconds, it regenerates `regen_rate`
+/// points per second. Damage of one of the `breakers` types breaks the shield
+/// at once and passes through completely.
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct Shield {
+    #[export(name = "max_shield")]
+    pub capacity: u8,
+    #[export(name = "shield_regen_delay")]
+    pub regen_delay: f32,
+    #[export(name = "shield_regen_rate")]
+    pub regen_rate: f32,
+    #[export(name = "shield_breakers")]
+    pub breakers: Vec<DamageType>,
+
+    value: u8,
+    since_hit: f32,
+    regen_progress: f32,
+}
+
+impl Shield {
+    pub fn new() -> Self {
+        Shield {
+            capacity: 0,
+            regen_delay: 3.0,
+            regen_rate: 10.0,
+            breakers: vec![DamageType::Energy],
+
+            value: 0,
+            since_hit: 0.0,
+            regen_progress: 0.0,
+        }
+    }
+
+    /// A shield without capacity means the tank has no shield at all.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    #[inline]
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    #[inline]
+    pub fn percentage(&self) -> f64 {
+        if self.is_enabled() {
+            self.value as f64 / self.capacity as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Fully charges the shield.
+    pub fn reset(&mut self) {
+        self.value = self.capacity;
+        self.since_hit = 0.0;
+        self.regen_progress = 0.0;
+    }
+
+    /// Adds `amount` points to the shield and returns true when it was not
+    /// fully charged.
+    pub fn recharge(&mut self, amount: u8) -> bool {
+        if self.value >= self.capacity {
+            return false;
+        }
+
+        self.value = self.value.saturating_add(amount).min(self.capacity);
+        if self.value == self.capacity {
+            self.regen_progress = 0.0;
+        }
+        true
+    }
+
+    /// Absorbs as much of `amount` as possible and returns the damage that
+    /// passes through to the tank's health.
+    pub fn absorb(&mut self, amount: u8, typ: DamageType) -> u8 {
+        self.since_hit = 0.0;
+        self.regen_progress = 0.0;
+
+        if self.value == 0 {
+            return amount;
+        }
+        if self.breakers.contains(&typ) {
+            self.value = 0;
+            return amount;
+        }
+
+        let absorbed = amount.min(self.value);
+        self.value -= absorbed;
+        amount - absorbed
+    }
+
+    /// Regenerates the shield and returns true when its value has changed.
+    pub fn update(&mut self, delta: f32) -> bool {
+        if self.value >= self.capacity {
+            return false;
+        }
+
+        self.since_hit += delta;
+        if self.since_hit < self.regen_delay {
+            return false;
+        }
+
+        self.regen_progress += self.regen_rate * delta;
+        let gained = self
+            .regen_progress
+            .floor()
+            .min((self.capacity - self.value) as f32);
+        if gained < 1.0 {
+            return false;
+        }
+
+        self.regen_progress -= gained;
+        self.value += gained as u8;
+        if self.value == self.capacity {
+            self.regen_progress = 0.0;
+        }
+        true
+    }
+}
+
+impl Default for Shield {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::ops::Mul;
+
+use crate::utils::ExportGroup;
+
+/// Movement characteristics of a tank. All rates are in units (or radians)
+/// per second squared; a rate of zero or less changes the value instantly.
+/// Traction scales the acceleration, braking and rotation acceleration, but
+/// never makes them instant.
+#[derive(Clone, Copy, Debug, PartialEq, ExportGroup)]
+pub struct Drivetrain {
+    #[export]
+    pub max_speed: f32,
+    #[export]
+    pub acceleration: f32,
+    #[export]
+    pub braking: f32,
+    #[export]
+    pub rolling_friction: f32,
+    #[export(range(0.0, 1.0, 0.05))]
+    pub reverse_speed_ratio: f32,
+    #[export(range(0.0, 1.0, 0.05))]
+    pub traction: f32,
+    #[export]
+    pub rotation_speed: f32,
+    #[export]
+    pub rotation_acceleration: f32,
+}
+
+/// Multipliers applied on top of a drivetrain, for example by the terrain a
+/// tank is driving over.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DriveModifiers {
+    pub max_speed: f32,
+    pub traction: f32,
+    pub rotation_speed: f32,
+}
+
+impl Default for DriveModifiers {
+    fn default() -> Self {
+        DriveModifiers {
+            max_speed: 1.0,
+            traction: 1.0,
+            rotation_speed: 1.0,
+        }
+    }
+}
+
+impl Mul for DriveModifiers {
+    type Output = DriveModifiers;
+
+    #[inline]
+    fn mul(self, rhs: DriveModifiers) -> DriveModifiers {
+        DriveModifiers {
+            max_speed: self.max_speed * rhs.max_speed,
+            traction: self.traction * rhs.traction,
+            rotation_speed: self.rotation_speed * rhs.rotation_speed,
+        }
+    }
+}
+
+/// The part of a tank's motion that carries over between frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DriveState {
+    pub speed: f32,
+    pub angular_velocity: f32,
+}
+
+/// What the driver wants to do, with `throttle` and `steer` in the range
+/// `-1.0..=1.0`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DriveInput {
+    pub throttle: f32,
+    pub steer: f32,
+    pub brake: bool,
+}
+
+/// The distance and rotation covered during a single step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DriveStep {
+    pub distance: f32,
+    pub rotation: f32,
+}
+
+impl DriveStep {
+    /// The average speed during a step of `delta` seconds.
+    #[inline]
+    pub fn velocity(&self, delta: f32) -> f32 {
+        if delta > 0.0 {
+            self.distance / delta
+        } else {
+            0.0
+        }
+    }
+}
+
+const MAX_PHASES: usize = 4;
+
+/// The least traction a tank has, so it still accelerates and brakes on a
+/// surface without any grip.
+const MIN_TRACTION: f32 = 0.05;
+
+impl Drivetrain {
+    /// Returns a copy of the drivetrain with `modifiers` applied.
+    #[inline]
+    pub fn modified(&self, modifiers: &DriveModifiers) -> Drivetrain {
+        Drivetrain {
+            max_speed: self.max_speed * modifiers.max_speed,
+            traction: self.traction * modifiers.traction,
+            rotation_speed: self.rotation_speed * modifiers.rotation_speed,
+            ..*self
+        }
+    }
+
+    /// Advances `state` by `delta` seconds. Within a step the motion is split
+    /// in phases of constant acceleration, which are integrated exactly, so
+    /// the result does not depend on the frame rate.
+    pub fn step(&self, state: &mut DriveState, input: DriveInput, delta: f32) -> DriveStep {
+        if delta <= 0.0 {
+            return DriveStep::default();
+        }
+
+        let mut step = DriveStep::default();
+
+        let mut remaining = delta;
+        for _ in 0..MAX_PHASES {
+            let (target, rate) = self.speed_phase(state.speed, input);
+            let (speed, used, distance) = approach(state.speed, target, rate, remaining);
+
+            state.speed = speed;
+            step.distance += distance;
+            remaining -= used;
+            if remaining <= 0.0 {
+                break;
+            }
+            if rate <= 0.0 || speed == target && self.speed_phase(speed, input).0 == target {
+                // target speed is reached and kept for the rest of the step
+                step.distance += speed * remaining;
+                break;
+            }
+        }
+
+        let target = input.steer.clamp(-1.0, 1.0) * self.rotation_speed;
+        let rate = self.with_traction(self.rotation_acceleration);
+        let (angular_velocity, used, rotation) =
+            approach(state.angular_velocity, target, rate, delta);
+
+        state.angular_velocity = angular_velocity;
+        step.rotation = rotation + angular_velocity * (delta - used);
+        step
+    }
+
+    /// Returns the speed the tank is moving towards and the rate at which it
+    /// does so.
+    fn speed_phase(&self, speed: f32, input: DriveInput) -> (f32, f32) {
+        let braking = self.with_traction(self.braking);
+        if input.brake {
+            return (0.0, braking);
+        }
+
+        let throttle = input.throttle.clamp(-1.0, 1.0);
+        if throttle == 0.0 {
+            return (0.0, self.rolling_friction);
+        }
+
+        let target = if throttle > 0.0 {
+            throttle * self.max_speed
+        } else {
+            throttle * self.max_speed * self.reverse_speed_ratio
+        };
+
+        if speed != 0.0 && speed.signum() != target.signum() {
+            (0.0, braking)
+        } else if speed.abs() > target.abs() {
+            (target, self.rolling_friction)
+        } else {
+            (target, self.with_traction(self.acceleration))
+        }
+    }
+
+    /// Scales `rate` by the traction. A rate of zero or less stays instant.
+    #[inline]
+    fn with_traction(&self, rate: f32) -> f32 {
+        if rate <= 0.0 {
+            return rate;
+        }
+        rate * self.traction.max(MIN_TRACTION)
+    }
+}
+
+impl Default for Drivetrain {
+    fn default() -> Self {
+        Drivetrain {
+            max_speed: 200.0,
+            acceleration: 400.0,
+            braking: 800.0,
+            rolling_friction: 300.0,
+            reverse_speed_ratio: 0.5,
+            traction: 1.0,
+            rotation_speed: 1.0,
+            rotation_acceleration: 6.0,
+        }
+    }
+}
+
+/// Moves `value` towards `target` at a constant `rate`, for at most `time`
+/// seconds. Returns the new value, the time used and the integral of the
+/// value over that time.
+fn approach(value: f32, target: f32, rate: f32, time: f32) -> (f32, f32, f32) {
+    if rate <= 0.0 || value == target {
+        return (target, 0.0, 0.0);
+    }
+
+    let diff = target - value;
+    let needed = diff.abs() / rate;
+    if needed <= time {
+        return (target, needed, (value + target) * 0.5 * needed);
+    }
+
+    let value_end = value + diff.signum() * rate * time;
+    (value_end, time, (value + value_end) * 0.5 * time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 0.01;
+
+    /// Drives for one second at `fps` frames per second: full throttle and
+    /// steering for the first half, coasting for the second.
+    fn drive(drivetrain: &Drivetrain, fps: u32) -> (DriveState, DriveStep) {
+        let delta = 1.0 / fps as f32;
+        let mut state = DriveState::default();
+        let mut total = DriveStep::default();
+        for frame in 0..fps {
+            let input = if frame < fps / 2 {
+                DriveInput {
+                    throttle: 1.0,
+                    steer: 1.0,
+                    brake: false,
+                }
+            } else {
+                DriveInput::default()
+            };
+            let step = drivetrain.step(&mut state, input, delta);
+            total.distance += step.distance;
+            total.rotation += step.rotation;
+        }
+        (state, total)
+    }
+
+    fn assert_close(a: f32, b: f32, what: &str) {
+        let tolerance = TOLERANCE * a.abs().max(b.abs()).max(1.0);
+        assert!((a - b).abs() <= tolerance, "{}: {} != {}", what, a, b);
+    }
+
+    #[test]
+    fn step_is_frame_rate_independent() {
+        let drivetrain = Drivetrain::default();
+        let (slow_state, slow) = drive(&drivetrain, 30);
+        let (fast_state, fast) = drive(&drivetrain, 240);
+
+        assert_close(slow_state.speed, fast_state.speed, "speed");
+        assert_close(
+            slow_state.angular_velocity,
+            fast_state.angular_velocity,
+            "angular velocity",
+        );
+        assert_close(slow.distance, fast.distance, "distance");
+        assert_close(slow.rotation, fast.rotation, "rotation");
+        assert!(slow.distance > 0.0 && slow.rotation > 0.0);
+    }
+
+    #[test]
+    fn reverse_is_capped_by_reverse_speed_ratio() {
+        let drivetrain = Drivetrain::default();
+        let mut state = DriveState::default();
+        let input = DriveInput {
+            throttle: -1.0,
+            ..DriveInput::default()
+        };
+        for _ in 0..600 {
+            drivetrain.step(&mut state, input, 1.0 / 60.0);
+        }
+        assert_close(
+            state.speed,
+            -drivetrain.max_speed * drivetrain.reverse_speed_ratio,
+            "reverse speed",
+        );
+    }
+
+    #[test]
+    fn braking_stops_the_tank() {
+        let drivetrain = Drivetrain::default();
+        let mut state = DriveState {
+            speed: drivetrain.max_speed,
+            angular_velocity: 0.0,
+        };
+        let input = DriveInput {
+            throttle: 1.0,
+            steer: 0.0,
+            brake: true,
+        };
+        for _ in 0..60 {
+            drivetrain.step(&mut state, input, 1.0 / 60.0);
+        }
+        assert_eq!(state.speed, 0.0);
+    }
+
+    #[test]
+    fn rate_of_zero_applies_instantly() {
+        let drivetrain = Drivetrain {
+            acceleration: 0.0,
+            rotation_acceleration: -1.0,
+            ..Drivetrain::default()
+        };
+        let mut state = DriveState::default();
+        let input = DriveInput {
+            throttle: 1.0,
+            steer: -1.0,
+            brake: false,
+        };
+        let step = drivetrain.step(&mut state, input, 0.5);
+
+        assert_eq!(state.speed, drivetrain.max_speed);
+        assert_eq!(state.angular_velocity, -drivetrain.rotation_speed);
+        assert_close(step.distance, drivetrain.max_speed * 0.5, "distance");
+        assert_close(step.rotation, -drivetrain.rotation_speed * 0.5, "rotation");
+    }
+
+    #[test]
+    fn zero_traction_is_not_instant() {
+        let drivetrain = Drivetrain {
+            traction: 0.0,
+            ..Drivetrain::default()
+        };
+        let mut state = DriveState::default();
+        let input = DriveInput {
+            throttle: 1.0,
+            steer: 1.0,
+            brake: false,
+        };
+        drivetrain.step(&mut state, input, 0.1);
+        assert!(state.speed > 0.0 && state.speed < drivetrain.max_speed);
+        assert!(state.angular_velocity > 0.0 && state.angular_velocity < drivetrain.rotation_speed);
+
+        let speed = state.speed;
+        let input = DriveInput {
+            brake: true,
+            ..input
+        };
+        drivetrain.step(&mut state, input, 0.01);
+        assert!(state.speed > 0.0 && state.speed < speed);
+    }
+}
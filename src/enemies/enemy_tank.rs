@@ -4,17 +4,22 @@
 
 use std::borrow::{Borrow, BorrowMut};
 
-use gdnative::api::{CircleShape2D, CollisionShape2D, Node2D, PathFollow2D, RayCast2D};
+use gdnative::api::{CircleShape2D, Node2D, PathFollow2D, RayCast2D};
 use gdnative::prelude::*;
-use interpolation::Lerp;
 
+use crate::drivetrain::DriveInput;
 use crate::player;
-use crate::tank::{BasicTank, DamageTaker, TankProperties};
-use crate::utils::node::{get_node_as, get_parent_as, NodeRef};
+use crate::scenes::tanks::enemy_tank_tscn;
+use crate::tank::{BasicTank, DamageTaker, TankKind, TankProperties};
+use crate::utils::node::{get_parent_as, try_get_child_as, NodeRef};
 use crate::utils::*;
 
 use super::traits::TargetShooter;
 
+/// Rate at which a tank that is pushed off its path returns to it, per
+/// second.
+const PATH_RETURN_RATE: f64 = 2.0;
+
 #[derive(NativeClass)]
 #[inherit(KinematicBody2D)]
 #[register_with(Self::register)]
@@ -25,7 +30,6 @@ pub struct EnemyTank {
     detect_radius: f64,
 
     properties: TankProperties,
-    speed: f32,
     target: Option<Ref<Node2D>>,
 
     // child node(s)
@@ -46,11 +50,10 @@ impl EnemyTank {
             detect_radius: 500.0,
 
             properties: TankProperties::new(),
-            speed: 0.0,
             target: None,
 
-            lookahead1_node: NodeRef::new("LookAhead1"),
-            lookahead2_node: NodeRef::new("LookAhead2"),
+            lookahead1_node: NodeRef::of::<enemy_tank_tscn::LookAhead1Node>(),
+            lookahead2_node: NodeRef::of::<enemy_tank_tscn::LookAhead2Node>(),
         }
     }
 
@@ -59,13 +62,23 @@ impl EnemyTank {
         BasicTank::_ready(self, owner);
 
         let owner = owner.as_ref();
-        self.lookahead1_node.get_from(owner);
-        self.lookahead2_node.get_from(owner);
+        let lookahead1 = report_error(self.lookahead1_node.try_get_from(owner));
+        let lookahead2 = report_error(self.lookahead2_node.try_get_from(owner));
+        if lookahead1.is_none() || lookahead2.is_none() {
+            // without its look aheads the tank cannot drive
+            self.properties.alive = false;
+            owner.set_process(false);
+            owner.set_physics_process(false);
+        }
 
         let circle_shape = CircleShape2D::new();
         circle_shape.set_radius(self.detect_radius);
-        get_node_as::<CollisionShape2D>(owner, "DetectRadius/CollisionShape2D")
-            .set_shape(circle_shape);
+        if let Some(shape) = report_error(try_get_child_as::<
+            enemy_tank_tscn::DetectRadiusCollisionShape2DNode,
+        >(owner))
+        {
+            shape.set_shape(circle_shape);
+        }
     }
 
     //noinspection DuplicatedCode
@@ -117,6 +130,8 @@ impl EnemyTank {
 }
 
 impl BasicTank<Self> for EnemyTank {
+    const KIND: TankKind = TankKind::EnemyTank;
+
     #[inline]
     fn props(&self) -> &TankProperties {
         self.properties.borrow()
@@ -129,16 +144,24 @@ impl BasicTank<Self> for EnemyTank {
 
     #[inline]
     fn control(&mut self, owner: TRef<KinematicBody2D>, delta: f32) {
+        let mut input = DriveInput::default();
         if self.lookahead1_node.get_ref().is_colliding()
             || self.lookahead2_node.get_ref().is_colliding()
         {
-            self.speed = self.speed.lerp(&0.0, &0.1);
+            input.brake = true;
         } else {
-            self.speed = self.speed.lerp(&self.properties.max_speed, &0.05);
+            input.throttle = 1.0;
         }
 
+        let step = self.properties.step_drivetrain(input, delta);
+
         if let Some(parent) = unsafe { get_parent_as::<PathFollow2D>(owner.as_ref()) } {
-            parent.set_offset(parent.offset() + (self.speed * delta) as f64);
+            // a knockback moves the tank away from its path follow, which
+            // takes over the push, after which the tank returns to its path
+            let pushed = owner.position();
+            let v_offset = parent.v_offset() + pushed.y as f64;
+            parent.set_offset(parent.offset() + (step.distance + pushed.x) as f64);
+            parent.set_v_offset(v_offset * (-PATH_RETURN_RATE * delta as f64).exp());
             owner.set_position(Vector2::zero());
         }
     }
@@ -4,12 +4,13 @@
 
 use std::borrow::{Borrow, BorrowMut};
 
-use gdnative::api::{CircleShape2D, CollisionShape2D, Node2D};
+use gdnative::api::{CircleShape2D, Node2D};
 use gdnative::prelude::*;
 
 use crate::player;
-use crate::tank::{BasicTank, DamageTaker, TankProperties};
-use crate::utils::node::get_node_as;
+use crate::scenes::tanks::gun_turret_tscn;
+use crate::tank::{BasicTank, DamageTaker, TankKind, TankProperties};
+use crate::utils::node::try_get_child_as;
 use crate::utils::*;
 
 use super::traits::TargetShooter;
@@ -47,12 +48,16 @@ impl GunTurret {
     #[export]
     fn _ready(&mut self, owner: TRef<KinematicBody2D>) {
         BasicTank::_ready(self, owner);
-        self.properties.max_speed = 0.0;
+        self.properties.drivetrain.max_speed = 0.0;
 
         let circle_shape = CircleShape2D::new();
         circle_shape.set_radius(self.detect_radius);
-        get_node_as::<CollisionShape2D>(owner.as_ref(), "DetectRadius/CollisionShape2D")
-            .set_shape(circle_shape);
+        if let Some(shape) = report_error(try_get_child_as::<
+            gun_turret_tscn::DetectRadiusCollisionShape2DNode,
+        >(owner.as_ref()))
+        {
+            shape.set_shape(circle_shape);
+        }
     }
 
     //noinspection DuplicatedCode
@@ -67,6 +72,15 @@ impl GunTurret {
         }
     }
 
+    #[export]
+    fn _physics_process(&mut self, owner: TRef<KinematicBody2D>, delta: f32) {
+        // turrets do not move, but their shield and status effects are updated
+        if self.properties.alive {
+            self.update_shield(owner, delta);
+            self.update_status_effects(owner, delta);
+        }
+    }
+
     #[allow(non_snake_case)]
     #[export]
     fn _on_GunTimer_timeout(&mut self, owner: TRef<KinematicBody2D>) {
@@ -99,6 +113,8 @@ impl GunTurret {
 }
 
 impl BasicTank<Self> for GunTurret {
+    const KIND: TankKind = TankKind::GunTurret;
+
     #[inline]
     fn props(&self) -> &TankProperties {
         self.properties.borrow()
@@ -4,26 +4,40 @@
 
 use gdnative::prelude::*;
 
+use crate::fog::Team;
 use crate::tank::BasicTank;
+use crate::units;
 
 pub trait TargetShooter<C>: BasicTank<C>
 where
     C: NativeClass + BasicTank<C>,
 {
+    /// Aims at `target` and fires when the turret points at it. Targets the
+    /// enemies cannot see, e.g. behind an obstacle, are ignored.
     fn engage_target(
         &mut self,
         owner: TRef<KinematicBody2D>,
         target: TRef<Node2D>,
         rotation_speed: f32,
     ) {
+        if !units::is_visible_to(
+            owner.upcast::<Node>().as_ref(),
+            Team::Enemy,
+            target.global_position(),
+        ) {
+            return;
+        }
+
         let target_dir = (target.global_position() - owner.global_position()).normalize();
 
         let turret = self.props().turret_node.get_ref();
         let current_dir =
             Vector2::new(1.0, 0.0).rotated(Angle::radians(turret.global_rotation() as f32));
 
-        let vec = current_dir.lerp(target_dir, rotation_speed);
-        turret.set_global_rotation(vec.y.atan2(vec.x) as f64);
+        if !self.props().status_effects.turret_locked() {
+            let vec = current_dir.lerp(target_dir, rotation_speed);
+            turret.set_global_rotation(vec.y.atan2(vec.x) as f64);
+        }
 
         if target_dir.dot(current_dir) > 0.9 {
             self.shoot(owner);
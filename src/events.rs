@@ -0,0 +1,228 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::prelude::*;
+
+use crate::tank::TankKind;
+use crate::utils::SingletonInstance;
+
+/// An event that is sent through the `Events` autoload. Every event type has
+/// its own signal, which is emitted with the event as its only argument.
+/// Handlers receive the event as a typed argument, e.g.
+/// `fn _on_Events_tank_fired(&self, owner: &Node, event: TankFired)`.
+pub trait Event: ToVariant + FromVariant {
+    const SIGNAL: &'static str;
+}
+
+/// A map has finished loading.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct MapStarted {
+    pub map: String,
+    /// Instance id of the player's tank, zero when the map has no player.
+    pub player: i64,
+}
+
+impl Event for MapStarted {
+    const SIGNAL: &'static str = "map_started";
+}
+
+/// A tank fired a bullet, which still needs to be added to the map.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct TankFired {
+    pub tank: i64,
+    pub kind: TankKind,
+    pub bullet: Ref<PackedScene>,
+    pub position: Vector2,
+    pub direction: Vector2,
+}
+
+impl Event for TankFired {
+    const SIGNAL: &'static str = "tank_fired";
+}
+
+/// A tank took damage. `amount` is the damage that got through the shield.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct TankDamaged {
+    pub tank: i64,
+    pub kind: TankKind,
+    pub amount: u8,
+    pub damage_type: String,
+    /// Instance id of the tank that caused the damage, zero when unknown.
+    pub source: i64,
+}
+
+impl Event for TankDamaged {
+    const SIGNAL: &'static str = "tank_damaged";
+}
+
+/// The health of a tank changed, `health` is a percentage.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct TankHealthChanged {
+    pub tank: i64,
+    pub kind: TankKind,
+    pub health: f64,
+}
+
+impl Event for TankHealthChanged {
+    const SIGNAL: &'static str = "tank_health_changed";
+}
+
+/// The shield of a tank changed, `shield` is a percentage.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct TankShieldChanged {
+    pub tank: i64,
+    pub kind: TankKind,
+    pub shield: f64,
+}
+
+impl Event for TankShieldChanged {
+    const SIGNAL: &'static str = "tank_shield_changed";
+}
+
+/// A tank was destroyed and its explosion has finished.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct TankDestroyed {
+    pub tank: i64,
+    pub kind: TankKind,
+    /// Instance id of the tank that caused the last damage, zero when
+    /// unknown.
+    pub killer: i64,
+    pub position: Vector2,
+}
+
+impl Event for TankDestroyed {
+    const SIGNAL: &'static str = "tank_destroyed";
+}
+
+/// A destroyed tank dropped a pickup.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct LootDropped {
+    pub pickup_type: String,
+    pub position: Vector2,
+}
+
+impl Event for LootDropped {
+    const SIGNAL: &'static str = "loot_dropped";
+}
+
+/// A bullet hit a body that can take damage.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct BulletHit {
+    /// Instance id of the tank that fired the bullet, zero when unknown.
+    pub shooter: i64,
+    pub target: i64,
+}
+
+impl Event for BulletHit {
+    const SIGNAL: &'static str = "bullet_hit";
+}
+
+/// The player's score changed, `accuracy` is a percentage.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct ScoreChanged {
+    pub score: u32,
+    pub multiplier: f64,
+    pub accuracy: f64,
+}
+
+impl Event for ScoreChanged {
+    const SIGNAL: &'static str = "score_changed";
+}
+
+/// The resources of the preload manifest are loaded, `missing` of them could
+/// not be loaded.
+#[derive(Clone, Debug, ToVariant, FromVariant)]
+pub struct ResourcesPreloaded {
+    pub loaded: u32,
+    pub missing: u32,
+}
+
+impl Event for ResourcesPreloaded {
+    const SIGNAL: &'static str = "resources_preloaded";
+}
+
+/// Autoloaded node through which systems publish and subscribe to events,
+/// without having to know about each other or being wired up in scenes.
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct Events;
+
+#[methods]
+impl Events {
+    fn register(builder: &ClassBuilder<Self>) {
+        register_event::<MapStarted>(builder);
+        register_event::<TankFired>(builder);
+        register_event::<TankDamaged>(builder);
+        register_event::<TankHealthChanged>(builder);
+        register_event::<TankShieldChanged>(builder);
+        register_event::<TankDestroyed>(builder);
+        register_event::<LootDropped>(builder);
+        register_event::<BulletHit>(builder);
+        register_event::<ScoreChanged>(builder);
+        register_event::<ResourcesPreloaded>(builder);
+    }
+
+    fn new(_owner: &Node) -> Self {
+        Events
+    }
+}
+
+impl SingletonInstance<Self, Node> for Events {
+    #[inline]
+    fn node_path<'a>() -> &'a str {
+        "/root/Events"
+    }
+}
+
+fn register_event<E: Event>(builder: &ClassBuilder<Events>) {
+    builder.add_signal(Signal {
+        name: E::SIGNAL,
+        args: &[SignalArgument {
+            name: "event",
+            default: Variant::new(),
+            export_info: ExportInfo::new(VariantType::Dictionary),
+            usage: PropertyUsage::DEFAULT,
+        }],
+    });
+}
+
+/// Sends `event` to all of its subscribers. Does nothing when the `Events`
+/// autoload is not available, e.g. when a scene is run in the editor on its
+/// own.
+pub fn publish<E: Event>(node: &Node, event: E) {
+    if let Some(events) = Events::try_singleton(node) {
+        events.base().emit_signal(E::SIGNAL, &[event.to_variant()]);
+    }
+}
+
+/// Calls `method` on `target` for every event of type `E`. Use
+/// `Object::CONNECT_DEFERRED` as `flags` for handlers that change the scene
+/// tree while a physics step may be in progress.
+pub fn subscribe<E, T>(node: &Node, target: TRef<T>, method: &str, flags: i64)
+where
+    E: Event,
+    T: GodotObject + SubClass<Object>,
+{
+    let events = match Events::try_singleton(node) {
+        Some(events) => events,
+        None => {
+            godot_warn!("Cannot subscribe `{}` to `{}`", method, E::SIGNAL);
+            return;
+        }
+    };
+
+    if let Err(err) =
+        events
+            .base()
+            .connect(E::SIGNAL, target, method, VariantArray::new_shared(), flags)
+    {
+        godot_error!(
+            "Failed to subscribe `{}` to `{}`: {:?}",
+            method,
+            E::SIGNAL,
+            err
+        );
+    }
+}
@@ -0,0 +1,401 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::prelude::*;
+
+/// Size in pixels of the cells of the vision grid.
+pub const VISION_CELL_SIZE: f32 = 32.0;
+
+/// The sides that share what their units see.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Team {
+    Player,
+    Enemy,
+}
+
+impl Team {
+    #[inline]
+    fn index(&self) -> usize {
+        match self {
+            Self::Player => 0,
+            Self::Enemy => 1,
+        }
+    }
+}
+
+/// A grid of square cells over the map with the cells each team can see,
+/// the cells the player has explored and the cells that block the line of
+/// sight.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VisionGrid {
+    origin: Vector2,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+    visible: [Vec<bool>; 2],
+    explored: Vec<bool>,
+}
+
+impl VisionGrid {
+    /// Creates a grid that covers `bounds`, with nothing visible, explored or
+    /// blocked.
+    pub fn new(bounds: Rect2, cell_size: f32) -> Self {
+        let cell_size = cell_size.max(1.0);
+        let columns = (bounds.width() / cell_size).ceil().max(1.0) as usize;
+        let rows = (bounds.height() / cell_size).ceil().max(1.0) as usize;
+        let cells = columns * rows;
+
+        VisionGrid {
+            origin: bounds.origin.to_vector(),
+            cell_size,
+            columns,
+            rows,
+            blocked: vec![false; cells],
+            visible: [vec![false; cells], vec![false; cells]],
+            explored: vec![false; cells],
+        }
+    }
+
+    /// The area covered by the grid, which can be slightly larger than the
+    /// bounds it was created with.
+    #[inline]
+    pub fn bounds(&self) -> Rect2 {
+        Rect2::new(
+            self.origin.to_point(),
+            Size2::new(
+                self.columns as f32 * self.cell_size,
+                self.rows as f32 * self.cell_size,
+            ),
+        )
+    }
+
+    #[inline]
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// The column and row of the cell at `position`.
+    #[inline]
+    pub fn cell_at(&self, position: Vector2) -> Option<(usize, usize)> {
+        let local = (position - self.origin) / self.cell_size;
+        if local.x < 0.0 || local.y < 0.0 {
+            return None;
+        }
+
+        let (column, row) = (local.x as usize, local.y as usize);
+        if column < self.columns && row < self.rows {
+            Some((column, row))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn cell_center(&self, column: usize, row: usize) -> Vector2 {
+        self.origin + Vector2::new(column as f32 + 0.5, row as f32 + 0.5) * self.cell_size
+    }
+
+    #[inline]
+    fn index(&self, column: usize, row: usize) -> Option<usize> {
+        if column < self.columns && row < self.rows {
+            Some(row * self.columns + column)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn is_blocked(&self, column: usize, row: usize) -> bool {
+        self.index(column, row)
+            .is_some_and(|index| self.blocked[index])
+    }
+
+    #[inline]
+    pub fn is_visible(&self, team: Team, column: usize, row: usize) -> bool {
+        self.index(column, row)
+            .is_some_and(|index| self.visible[team.index()][index])
+    }
+
+    #[inline]
+    pub fn is_visible_at(&self, team: Team, position: Vector2) -> bool {
+        self.cell_at(position)
+            .is_some_and(|(column, row)| self.is_visible(team, column, row))
+    }
+
+    #[inline]
+    pub fn is_explored(&self, column: usize, row: usize) -> bool {
+        self.index(column, row)
+            .is_some_and(|index| self.explored[index])
+    }
+
+    /// Unblocks all cells.
+    #[inline]
+    pub fn clear_blocked(&mut self) {
+        self.blocked.iter_mut().for_each(|cell| *cell = false);
+    }
+
+    /// Blocks the line of sight through the cells with their center within
+    /// `rect`, and through the cell at the center of `rect` so small rects
+    /// block at least one cell.
+    pub fn block_rect(&mut self, rect: Rect2) {
+        if let Some((column, row)) = self.cell_at(rect.center().to_vector()) {
+            self.blocked[row * self.columns + column] = true;
+        }
+
+        let min = (rect.origin.to_vector() - self.origin) / self.cell_size;
+        let max = min + rect.size.to_vector() / self.cell_size;
+        let min_column = (min.x - 0.5).ceil().max(0.0) as usize;
+        let min_row = (min.y - 0.5).ceil().max(0.0) as usize;
+        let max_column = ((max.x - 0.5).floor() + 1.0).max(0.0) as usize;
+        let max_row = ((max.y - 0.5).floor() + 1.0).max(0.0) as usize;
+
+        for row in min_row..max_row.min(self.rows) {
+            for column in min_column..max_column.min(self.columns) {
+                self.blocked[row * self.columns + column] = true;
+            }
+        }
+    }
+
+    /// Hides all cells from all teams, call it before revealing the cells the
+    /// units currently see.
+    #[inline]
+    pub fn clear_visible(&mut self) {
+        for visible in self.visible.iter_mut() {
+            visible.iter_mut().for_each(|cell| *cell = false);
+        }
+    }
+
+    /// Makes the cells within `radius` of `center` that are in the line of
+    /// sight of `center` visible to `team`. Cells the player sees are
+    /// explored.
+    pub fn reveal(&mut self, team: Team, center: Vector2, radius: f32) {
+        let local = (center - self.origin) / self.cell_size;
+        let cells = radius / self.cell_size;
+        let min_column = (local.x - cells).floor().max(0.0) as usize;
+        let min_row = (local.y - cells).floor().max(0.0) as usize;
+        let max_column = ((local.x + cells).ceil().max(0.0) as usize).min(self.columns);
+        let max_row = ((local.y + cells).ceil().max(0.0) as usize).min(self.rows);
+
+        let radius_squared = radius * radius;
+        for row in min_row..max_row {
+            for column in min_column..max_column {
+                let index = row * self.columns + column;
+                if self.visible[team.index()][index] {
+                    continue;
+                }
+
+                let cell = self.cell_center(column, row);
+                if cell.distance_squared_to(center) <= radius_squared
+                    && self.has_line_of_sight(center, cell)
+                {
+                    self.visible[team.index()][index] = true;
+                    if team == Team::Player {
+                        self.explored[index] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether no blocked cell lies between `from` and `to`. The cells at
+    /// `from` and `to` themselves do not block, so a blocking cell is visible.
+    pub fn has_line_of_sight(&self, from: Vector2, to: Vector2) -> bool {
+        let (start, end) = match (self.cell_at(from), self.cell_at(to)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return false,
+        };
+
+        // walks the cells the line crosses, one cell boundary at a time
+        let a = (from - self.origin) / self.cell_size;
+        let delta = (to - from) / self.cell_size;
+        let (mut column, mut row) = (start.0 as i64, start.1 as i64);
+        let (end_column, end_row) = (end.0 as i64, end.1 as i64);
+        let step_column = if delta.x > 0.0 { 1 } else { -1 };
+        let step_row = if delta.y > 0.0 { 1 } else { -1 };
+        let (mut next_x, delta_x) = boundary(a.x, column, delta.x);
+        let (mut next_y, delta_y) = boundary(a.y, row, delta.y);
+
+        for _ in 0..(self.columns + self.rows) {
+            if column == end_column && row == end_row {
+                return true;
+            }
+            if next_x < next_y {
+                column += step_column;
+                next_x += delta_x;
+            } else {
+                row += step_row;
+                next_y += delta_y;
+            }
+            if column == end_column && row == end_row {
+                return true;
+            }
+            if column < 0 || row < 0 || self.is_blocked(column as usize, row as usize) {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// Runs of cells the player has not explored.
+    #[inline]
+    pub fn unexplored_runs(&self) -> Vec<(usize, usize, usize)> {
+        self.runs(|column, row| !self.is_explored(column, row))
+    }
+
+    /// Runs of cells the player has explored but does not see right now.
+    #[inline]
+    pub fn hidden_runs(&self) -> Vec<(usize, usize, usize)> {
+        self.runs(|column, row| {
+            self.is_explored(column, row) && !self.is_visible(Team::Player, column, row)
+        })
+    }
+
+    /// Runs of cells per row that match `predicate`, as `(row, first column,
+    /// length)`. Drawing a run at once is a lot cheaper than drawing each
+    /// cell.
+    fn runs<F>(&self, predicate: F) -> Vec<(usize, usize, usize)>
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let mut runs = Vec::new();
+        for row in 0..self.rows {
+            let mut start = None;
+            for column in 0..=self.columns {
+                let matches = column < self.columns && predicate(column, row);
+                match (matches, start) {
+                    (true, None) => start = Some(column),
+                    (false, Some(first)) => {
+                        runs.push((row, first, column - first));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        runs
+    }
+}
+
+/// The distance along a line, in units of its length, to the first cell
+/// boundary it crosses on one axis and between the boundaries after that.
+#[inline]
+fn boundary(start: f32, cell: i64, delta: f32) -> (f32, f32) {
+    if delta > 0.0 {
+        ((cell as f32 + 1.0 - start) / delta, 1.0 / delta)
+    } else if delta < 0.0 {
+        ((cell as f32 - start) / delta, -1.0 / delta)
+    } else {
+        (f32::INFINITY, f32::INFINITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: f32 = 10.0;
+
+    /// A grid of 10 by 10 cells.
+    fn grid() -> VisionGrid {
+        VisionGrid::new(
+            Rect2::new(Point2::new(0.0, 0.0), Size2::new(100.0, 100.0)),
+            CELL,
+        )
+    }
+
+    fn center(column: usize, row: usize) -> Vector2 {
+        grid().cell_center(column, row)
+    }
+
+    /// Blocks the single cell at `column` and `row`.
+    fn block(grid: &mut VisionGrid, column: usize, row: usize) {
+        let origin = Point2::new(column as f32 * CELL + 2.0, row as f32 * CELL + 2.0);
+        grid.block_rect(Rect2::new(origin, Size2::new(2.0, 2.0)));
+    }
+
+    #[test]
+    fn blocked_cell_blocks_the_line_of_sight() {
+        let mut grid = grid();
+        assert!(grid.has_line_of_sight(center(1, 5), center(8, 5)));
+
+        block(&mut grid, 5, 5);
+        assert!(grid.is_blocked(5, 5));
+        assert!(!grid.has_line_of_sight(center(1, 5), center(8, 5)));
+        assert!(!grid.has_line_of_sight(center(5, 1), center(5, 8)));
+        assert!(!grid.has_line_of_sight(center(3, 3), center(7, 7)));
+
+        // lines that pass the cell, and the blocking cell itself, are seen
+        assert!(grid.has_line_of_sight(center(1, 4), center(8, 4)));
+        assert!(grid.has_line_of_sight(center(1, 5), center(5, 5)));
+        assert!(grid.has_line_of_sight(center(5, 5), center(8, 5)));
+    }
+
+    #[test]
+    fn line_of_sight_outside_of_the_grid() {
+        let grid = grid();
+        assert!(!grid.has_line_of_sight(Vector2::new(-5.0, 5.0), center(1, 1)));
+        assert!(!grid.has_line_of_sight(center(1, 1), Vector2::new(50.0, 150.0)));
+    }
+
+    #[test]
+    fn block_rect_blocks_the_cells_it_covers() {
+        let mut grid = grid();
+        grid.block_rect(Rect2::new(Point2::new(12.0, 12.0), Size2::new(30.0, 10.0)));
+
+        let blocked = (0..10)
+            .flat_map(|row| (0..10).map(move |column| (column, row)))
+            .filter(|(column, row)| grid.is_blocked(*column, *row))
+            .collect::<Vec<_>>();
+        assert_eq!(blocked, [(1, 1), (2, 1), (3, 1)]);
+
+        grid.clear_blocked();
+        assert!(!grid.is_blocked(3, 1));
+    }
+
+    #[test]
+    fn reveal_stays_within_its_radius() {
+        let mut grid = grid();
+        grid.reveal(Team::Player, center(5, 5), 2.0 * CELL);
+
+        assert!(grid.is_visible(Team::Player, 5, 5));
+        assert!(grid.is_visible(Team::Player, 7, 5));
+        assert!(grid.is_visible(Team::Player, 5, 3));
+        assert!(!grid.is_visible(Team::Player, 8, 5));
+        assert!(!grid.is_visible(Team::Player, 7, 7));
+        assert!(!grid.is_visible(Team::Enemy, 5, 5));
+        assert!(grid.is_visible_at(Team::Player, Vector2::new(55.0, 55.0)));
+    }
+
+    #[test]
+    fn reveal_does_not_see_past_blocked_cells() {
+        let mut grid = grid();
+        block(&mut grid, 6, 5);
+        grid.reveal(Team::Enemy, center(5, 5), 3.0 * CELL);
+
+        assert!(grid.is_visible(Team::Enemy, 6, 5));
+        assert!(!grid.is_visible(Team::Enemy, 7, 5));
+        assert!(!grid.is_visible(Team::Enemy, 8, 5));
+        assert!(grid.is_visible(Team::Enemy, 4, 5));
+    }
+
+    #[test]
+    fn explored_cells_stay_explored() {
+        let mut grid = grid();
+        grid.reveal(Team::Enemy, center(1, 1), CELL);
+        assert!(!grid.is_explored(1, 1));
+
+        grid.reveal(Team::Player, center(1, 1), CELL);
+        grid.clear_visible();
+        assert!(!grid.is_visible(Team::Player, 1, 1));
+        assert!(grid.is_explored(1, 1));
+
+        // the cells around (1, 1) are explored but hidden, as runs per row
+        assert_eq!(grid.hidden_runs(), [(0, 1, 1), (1, 0, 3), (2, 1, 1)]);
+        let unexplored = grid.unexplored_runs();
+        assert!(unexplored.contains(&(1, 3, 7)));
+        assert!(unexplored.contains(&(3, 0, 10)));
+    }
+}
@@ -0,0 +1,356 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::api::{Curve2D, File, Path2D, PathFollow2D, TileMap};
+use gdnative::prelude::*;
+
+use crate::obstacle;
+use crate::pickup::{Pickup, RES_PICKUP_SCENE};
+use crate::player;
+use crate::resources;
+use crate::scenes::environment::obstacle_tscn;
+use crate::scenes::tanks::{enemy_tank_tscn, gun_turret_tscn};
+use crate::terrain;
+use crate::utils::node::try_get_node_as;
+use crate::utils::*;
+
+use super::{Level, LevelObstacle, LevelPath, LevelPickup, PathPoint, Placement, Tiles, Wave};
+
+/// Parent nodes of the nodes of a level, relative to the map scene.
+pub const OBSTACLES_NODE: &str = "Obstacles";
+pub const PATHS_NODE: &str = "Paths";
+pub const TURRETS_NODE: &str = "Turrets";
+pub const PICKUPS_NODE: &str = "Pickups";
+pub const WAVES_NODE: &str = "Waves";
+
+/// Distance along a path between enemies that start on the same path.
+const ENEMY_SPACING: f64 = 256.0;
+
+/// Reads the level file at `path`, e.g. `res://maps/Map01.level`.
+pub fn read_level(path: &str) -> Result<Level, String> {
+    let file = File::new();
+    file.open(path, File::READ)
+        .map_err(|err| format!("Failed to open `{}`: {:?}", path, err))?;
+    let text = file.get_as_text().to_string();
+    file.close();
+
+    let level = text
+        .parse::<Level>()
+        .map_err(|err| format!("Failed to read `{}`, {}", path, err))?;
+    let unknown = level.unknown_obstacles(obstacle::atlas());
+    if !unknown.is_empty() {
+        return Err(format!(
+            "`{}` uses unknown obstacle types: {}",
+            path,
+            unknown.join(", ")
+        ));
+    }
+    Ok(level)
+}
+
+/// Writes `level` to the file at `path`, e.g. `user://custom.level`. A level
+/// that could not be read back is not written.
+pub fn write_level(path: &str, level: &Level) -> Result<(), String> {
+    level
+        .validate()
+        .map_err(|err| format!("Failed to write `{}`: {}", path, err))?;
+
+    let file = File::new();
+    file.open(path, File::WRITE)
+        .map_err(|err| format!("Failed to write `{}`: {:?}", path, err))?;
+    file.store_string(level.to_string());
+    file.close();
+    Ok(())
+}
+
+/// Replaces the ground, obstacles, paths, turrets and pickups of the map scene
+/// `owner` with those of `level`, and moves the player to its spawn. Each wave
+/// gets a one shot timer that calls `_on_wave_timeout` on `owner` with the
+/// index of the wave.
+pub fn build_level(owner: TRef<Node2D>, level: &Level) -> NodeResult<()> {
+    let node = owner.upcast::<Node>();
+
+    let ground = try_get_node_as::<TileMap>(node.as_ref(), terrain::GROUND_NODE)?;
+    ground.clear();
+    ground.set_cell_size(Vector2::new(level.tile_size, level.tile_size));
+    for (x, y, tile) in level.tiles.iter() {
+        ground.set_cell(x, y, tile, false, false, false, Vector2::zero());
+    }
+
+    if let Ok(player) = try_get_node_as::<Node2D>(node.as_ref(), player::NAME) {
+        player.set_position(level.player.position);
+        player.set_rotation(level.player.rotation as f64);
+    }
+
+    let obstacle_scene =
+        resources::try_load::<PackedScene>(node.as_ref(), obstacle_tscn::RES_PATH)?;
+    let obstacles = clear_container(owner, OBSTACLES_NODE);
+    for obstacle in level.obstacles.iter() {
+        let instance =
+            try_instance_scene(obstacle_scene.clone(), PackedScene::GEN_EDIT_STATE_DISABLED)?;
+        let instance = unsafe { instance.assume_safe() };
+        set_random(instance.as_ref(), obstacle);
+        instance.set("type_name", obstacle.type_name.clone());
+        if let Some(body) = instance.cast::<Node2D>() {
+            body.set_position(obstacle.placement.position);
+            body.set_rotation(obstacle.placement.rotation as f64);
+        }
+        obstacles.add_child(instance, false);
+    }
+
+    let tank_scene = resources::try_load::<PackedScene>(node.as_ref(), enemy_tank_tscn::RES_PATH)?;
+    let paths = clear_container(owner, PATHS_NODE);
+    for level_path in level.paths.iter() {
+        let path = new_path(level_path);
+        let path = unsafe { path.into_shared().assume_safe() };
+        paths.add_child(path, false);
+        for i in 0..level_path.patrols {
+            add_enemy(path, tank_scene.clone(), i as f64 * ENEMY_SPACING)?;
+        }
+    }
+
+    let turret_scene =
+        resources::try_load::<PackedScene>(node.as_ref(), gun_turret_tscn::RES_PATH)?;
+    let turrets = clear_container(owner, TURRETS_NODE);
+    for turret in level.turrets.iter() {
+        let instance =
+            try_instance_scene(turret_scene.clone(), PackedScene::GEN_EDIT_STATE_DISABLED)?;
+        if let Some(body) = unsafe { instance.assume_safe() }.cast::<Node2D>() {
+            body.set_position(turret.position);
+            body.set_rotation(turret.rotation as f64);
+        }
+        turrets.add_child(instance, false);
+    }
+
+    let pickup_scene = resources::try_load::<PackedScene>(node.as_ref(), RES_PICKUP_SCENE)?;
+    let pickups = clear_container(owner, PICKUPS_NODE);
+    for level_pickup in level.pickups.iter() {
+        let instance =
+            try_instance_scene(pickup_scene.clone(), PackedScene::GEN_EDIT_STATE_DISABLED)?;
+        pickups.add_child(instance, false);
+
+        let pickup = Pickup::try_instance_from(instance)?;
+        let _ = pickup.map_mut(|pickup, node| {
+            pickup.set_type(node, level_pickup.typ);
+            node.set_position(level_pickup.position);
+            node.set("respawn_time", level_pickup.respawn_time as f64);
+        });
+    }
+
+    let waves = clear_container(owner, WAVES_NODE);
+    for (index, wave) in level.waves.iter().enumerate() {
+        let timer = Timer::new();
+        timer.set_one_shot(true);
+        timer.set_autostart(true);
+        timer.set_wait_time(wave.delay.max(0.001) as f64);
+
+        let binds = VariantArray::new();
+        binds.push(index as i64);
+        if let Err(err) =
+            timer.connect("timeout", owner, "_on_wave_timeout", binds.into_shared(), 0)
+        {
+            godot_error!("Failed to schedule wave {}: {:?}", index, err);
+        }
+        waves.add_child(timer, false);
+    }
+
+    Ok(())
+}
+
+/// Reads the level from the map scene `owner`, the opposite of `build_level`.
+/// Only the obstacles, paths, turrets and pickups within their parent nodes
+/// are part of the level.
+pub fn capture_level(owner: TRef<Node2D>) -> NodeResult<Level> {
+    let node = owner.upcast::<Node>();
+    let ground = try_get_node_as::<TileMap>(node.as_ref(), terrain::GROUND_NODE)?;
+    let used = ground.get_used_rect();
+    let mut tiles = Tiles::new(
+        used.min_x() as i64,
+        used.min_y() as i64,
+        used.width() as u32,
+        used.height() as u32,
+    );
+    for row in 0..tiles.height {
+        for column in 0..tiles.width {
+            let tile = ground.get_cell(tiles.x + column as i64, tiles.y + row as i64);
+            tiles.set(column, row, tile);
+        }
+    }
+
+    let mut level = Level {
+        name: owner.name().to_string(),
+        tile_size: ground.cell_size().x,
+        tiles,
+        ..Level::default()
+    };
+    if let Ok(player) = try_get_node_as::<Node2D>(node.as_ref(), player::NAME) {
+        level.player = Placement::new(player.position(), player.rotation() as f32);
+    }
+
+    for obstacle in children_of::<Node2D>(owner, OBSTACLES_NODE) {
+        let type_name = obstacle.get("type_name").to_string();
+        if !type_name.is_empty() {
+            let placement = Placement::new(obstacle.position(), obstacle.rotation() as f32);
+            level
+                .obstacles
+                .push(get_random(obstacle.upcast(), type_name, placement));
+        }
+    }
+
+    for path in children_of::<Path2D>(owner, PATHS_NODE) {
+        let curve = match path.curve() {
+            Some(curve) => curve,
+            None => continue,
+        };
+        let curve = unsafe { curve.assume_safe() };
+        level.paths.push(LevelPath {
+            position: path.position(),
+            points: (0..curve.get_point_count())
+                .map(|i| PathPoint {
+                    position: curve.get_point_position(i),
+                    handle_in: curve.get_point_in(i),
+                    handle_out: curve.get_point_out(i),
+                })
+                .collect(),
+            patrols: path.get_child_count() as u32,
+        });
+    }
+
+    for turret in children_of::<Node2D>(owner, TURRETS_NODE) {
+        level
+            .turrets
+            .push(Placement::new(turret.position(), turret.rotation() as f32));
+    }
+
+    for pickup in children_of::<Node2D>(owner, PICKUPS_NODE) {
+        if let Ok(typ) = pickup.get("pickup_type").to_string().parse() {
+            level.pickups.push(LevelPickup {
+                typ,
+                position: pickup.position(),
+                respawn_time: pickup.get("respawn_time").to_f64() as f32,
+            });
+        }
+    }
+
+    Ok(level)
+}
+
+/// Adds the enemies of `wave` to the start of its path.
+pub fn spawn_wave(owner: TRef<Node2D>, wave: &Wave) -> NodeResult<()> {
+    let node = owner.upcast::<Node>();
+    let path = try_get_node_as::<Node>(node.as_ref(), PATHS_NODE)?
+        .get_child(wave.path as i64)
+        .and_then(|path| unsafe { path.assume_safe() }.cast::<Path2D>())
+        .ok_or_else(|| {
+            NodeError::new(
+                NodeErrorKind::NotFound,
+                format!("{}/{}", PATHS_NODE, wave.path),
+                "Path2D",
+            )
+            .with_owner(node.as_ref())
+        })?;
+
+    let tank_scene = resources::try_load::<PackedScene>(node.as_ref(), enemy_tank_tscn::RES_PATH)?;
+    for i in 0..wave.enemies {
+        add_enemy(path, tank_scene.clone(), i as f64 * ENEMY_SPACING)?;
+    }
+    Ok(())
+}
+
+/// Sets the random pick and variation of an obstacle instance. The seed goes
+/// first, as the obstacle picks one when it has none but is random.
+fn set_random(instance: &Node, obstacle: &LevelObstacle) {
+    let variation = &obstacle.variation;
+    instance.set("random_seed", obstacle.seed);
+    instance.set("random_rotation", variation.rotation);
+    instance.set("random_scale_min", variation.scale_min);
+    instance.set("random_scale_max", variation.scale_max);
+    instance.set("random_flip_h", variation.flip_h);
+    instance.set("random_flip_v", variation.flip_v);
+    instance.set("random_tint_min", variation.tint_min);
+    instance.set("random_tint_max", variation.tint_max);
+    instance.set("random_category", obstacle.category.clone());
+}
+
+/// Reads the random pick and variation of an obstacle instance.
+fn get_random(instance: TRef<Node>, type_name: String, placement: Placement) -> LevelObstacle {
+    let mut obstacle = LevelObstacle::new(type_name, placement);
+    obstacle.seed = i64::from_variant(&instance.get("random_seed")).unwrap_or_default();
+    obstacle.category = instance.get("random_category").to_string();
+    let variation = &mut obstacle.variation;
+    let number = |key: &str| f32::from_variant(&instance.get(key)).ok();
+    let color = |key: &str| Color::from_variant(&instance.get(key)).ok();
+    variation.rotation = number("random_rotation").unwrap_or(variation.rotation);
+    variation.scale_min = number("random_scale_min").unwrap_or(variation.scale_min);
+    variation.scale_max = number("random_scale_max").unwrap_or(variation.scale_max);
+    variation.flip_h = instance.get("random_flip_h").to_bool();
+    variation.flip_v = instance.get("random_flip_v").to_bool();
+    variation.tint_min = color("random_tint_min").unwrap_or(variation.tint_min);
+    variation.tint_max = color("random_tint_max").unwrap_or(variation.tint_max);
+    obstacle
+}
+
+fn new_path(level_path: &LevelPath) -> Ref<Path2D, Unique> {
+    let curve = Curve2D::new();
+    for point in level_path.points.iter() {
+        curve.add_point(point.position, point.handle_in, point.handle_out, -1);
+    }
+
+    let path = Path2D::new();
+    path.set_position(level_path.position);
+    path.set_curve(curve);
+    path
+}
+
+/// Adds an enemy tank that follows `path`, starting `offset` pixels along it.
+fn add_enemy(path: TRef<Path2D>, scene: Ref<PackedScene>, offset: f64) -> NodeResult<()> {
+    let follow = PathFollow2D::new();
+    follow.add_child(
+        try_instance_scene(scene, PackedScene::GEN_EDIT_STATE_DISABLED)?,
+        false,
+    );
+    follow.set_offset(offset);
+    path.add_child(follow, false);
+    Ok(())
+}
+
+/// The children of type `T` of the node at `path`.
+fn children_of<'a, T>(owner: TRef<'a, Node2D>, path: &str) -> Vec<TRef<'a, T>>
+where
+    T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    let container = match owner.get_node_or_null(path) {
+        Some(container) => unsafe { container.assume_safe() },
+        None => return Vec::new(),
+    };
+
+    container
+        .get_children()
+        .iter()
+        .filter_map(|child| child.try_to_object::<T>())
+        .map(|child| unsafe { child.assume_safe() })
+        .collect()
+}
+
+/// Returns the node at `path`, without its children, or a new node at `path`
+/// when there is none.
+fn clear_container<'a>(owner: TRef<'a, Node2D>, path: &str) -> TRef<'a, Node> {
+    if let Some(container) = owner.get_node_or_null(path) {
+        let container = unsafe { container.assume_safe() };
+        for child in container.get_children().iter() {
+            if let Some(child) = child.try_to_object::<Node>() {
+                let child = unsafe { child.assume_safe() };
+                container.remove_child(child);
+                child.queue_free();
+            }
+        }
+        return container;
+    }
+
+    let container = Node::new();
+    container.set_name(path);
+    let container = container.into_shared();
+    owner.add_child(container, false);
+    unsafe { container.assume_safe() }
+}
@@ -0,0 +1,169 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::VecDeque;
+
+use gdnative::prelude::*;
+
+use super::Level;
+
+/// Number of edits that can be undone.
+const UNDO_LIMIT: usize = 100;
+
+/// Copies of a level from before each edit, so edits can be undone and
+/// redone.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    undo: VecDeque<Level>,
+    redo: Vec<Level>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    /// Remembers `before`, the level as it was before an edit, and forgets
+    /// the edits that were undone.
+    pub fn push(&mut self, before: Level) {
+        self.undo.push_back(before);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    /// Restores `level` to how it was before the last edit.
+    pub fn undo(&mut self, level: &mut Level) -> bool {
+        match self.undo.pop_back() {
+            Some(before) => {
+                self.redo.push(std::mem::replace(level, before));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the last undone edit to `level` again.
+    pub fn redo(&mut self, level: &mut Level) -> bool {
+        match self.redo.pop() {
+            Some(after) => {
+                self.undo.push_back(std::mem::replace(level, after));
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[inline]
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    #[inline]
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+}
+
+impl Level {
+    /// The tile at `column` and `row` of the tile map, `None` when it lies
+    /// outside of the tiles of the level.
+    pub fn tile_at(&self, column: i64, row: i64) -> Option<i64> {
+        let tiles = &self.tiles;
+        let (x, y) = (column - tiles.x, row - tiles.y);
+        if x < 0 || y < 0 || x >= tiles.width as i64 || y >= tiles.height as i64 {
+            None
+        } else {
+            Some(tiles.get(x as u32, y as u32))
+        }
+    }
+
+    /// Sets the tile at `column` and `row` of the tile map. Returns false when
+    /// it lies outside of the tiles of the level or already is `tile`.
+    pub fn set_tile_at(&mut self, column: i64, row: i64, tile: i64) -> bool {
+        match self.tile_at(column, row) {
+            Some(current) if current != tile => {
+                let (x, y) = (column - self.tiles.x, row - self.tiles.y);
+                self.tiles.set(x as u32, y as u32, tile);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Index of the obstacle nearest to `position`, within `radius`.
+    #[inline]
+    pub fn obstacle_at(&self, position: Vector2, radius: f32) -> Option<usize> {
+        nearest(
+            self.obstacles
+                .iter()
+                .map(|obstacle| obstacle.placement.position),
+            position,
+            radius,
+        )
+    }
+
+    /// Index of the turret nearest to `position`, within `radius`.
+    #[inline]
+    pub fn turret_at(&self, position: Vector2, radius: f32) -> Option<usize> {
+        nearest(
+            self.turrets.iter().map(|turret| turret.position),
+            position,
+            radius,
+        )
+    }
+
+    /// Index of the path with the point nearest to `position`, within
+    /// `radius`.
+    pub fn path_at(&self, position: Vector2, radius: f32) -> Option<usize> {
+        let points = self
+            .paths
+            .iter()
+            .enumerate()
+            .flat_map(|(i, path)| {
+                path.points
+                    .iter()
+                    .map(move |point| (i, path.position + point.position))
+            })
+            .collect::<Vec<_>>();
+
+        nearest(points.iter().map(|(_, point)| *point), position, radius)
+            .map(|index| points[index].0)
+    }
+
+    /// Removes the paths with less than two points, which a level cannot be
+    /// saved with, and returns whether there were any.
+    pub fn remove_short_paths(&mut self) -> bool {
+        let mut removed = false;
+        while let Some(index) = self.paths.iter().position(|path| path.points.len() < 2) {
+            self.remove_path(index);
+            removed = true;
+        }
+        removed
+    }
+
+    /// Removes the path at `index` and the waves that enter on it.
+    pub fn remove_path(&mut self, index: usize) {
+        self.paths.remove(index);
+        self.waves.retain(|wave| wave.path != index);
+        for wave in self.waves.iter_mut() {
+            if wave.path > index {
+                wave.path -= 1;
+            }
+        }
+    }
+}
+
+fn nearest<I>(positions: I, position: Vector2, radius: f32) -> Option<usize>
+where
+    I: Iterator<Item = Vector2>,
+{
+    positions
+        .enumerate()
+        .map(|(i, other)| (i, other.distance_squared_to(position)))
+        .filter(|(_, distance)| *distance <= radius * radius)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(i, _)| i)
+}
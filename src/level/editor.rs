@@ -0,0 +1,683 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+use gdnative::api::{
+    GlobalConstants, InputEvent, InputEventKey, InputEventMouseButton, InputEventMouseMotion,
+    Label, TileMap,
+};
+use gdnative::prelude::*;
+
+use crate::obstacle::{self, ObstacleDef};
+use crate::scenes::maps::level_editor_tscn;
+use crate::terrain;
+use crate::utils::node::NodeRef;
+use crate::utils::*;
+
+use super::{
+    build_level, write_level, History, Level, LevelObstacle, LevelPath, PathPoint, Placement,
+    EMPTY_TILE,
+};
+
+/// Switches between playing and editing the level of the map.
+pub const ACTION_LEVEL_EDITOR: &str = "level_editor";
+
+/// Rotation of obstacles, turrets and the player per key press.
+const ROTATION_STEP: f32 = (PI / 12.0) as f32;
+/// Distance from the mouse within which obstacles, turrets and paths are
+/// picked.
+const PICK_RADIUS: f32 = 48.0;
+/// Enemies that patrol a new path.
+const NEW_PATH_PATROLS: u32 = 1;
+
+const BOUNDS_COLOR: Color = Color {
+    r: 1.0,
+    g: 1.0,
+    b: 1.0,
+    a: 0.5,
+};
+const CURSOR_COLOR: Color = Color {
+    r: 1.0,
+    g: 1.0,
+    b: 0.4,
+    a: 0.8,
+};
+const PATH_COLOR: Color = Color {
+    r: 0.9,
+    g: 0.2,
+    b: 0.2,
+    a: 0.8,
+};
+const ACTIVE_PATH_COLOR: Color = Color {
+    r: 1.0,
+    g: 0.6,
+    b: 0.2,
+    a: 1.0,
+};
+const PLAYER_COLOR: Color = Color {
+    r: 0.3,
+    g: 0.8,
+    b: 1.0,
+    a: 0.8,
+};
+
+/// What the left and right mouse buttons do.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Tool {
+    Tiles,
+    Obstacles,
+    Paths,
+    Turrets,
+    Player,
+}
+
+impl Tool {
+    #[inline]
+    fn name(&self) -> &str {
+        match self {
+            Tool::Tiles => "Tiles",
+            Tool::Obstacles => "Obstacles",
+            Tool::Paths => "Paths",
+            Tool::Turrets => "Turrets",
+            Tool::Player => "Player",
+        }
+    }
+
+    #[inline]
+    fn from_scancode(scancode: i64) -> Option<Self> {
+        match scancode {
+            GlobalConstants::KEY_1 => Some(Tool::Tiles),
+            GlobalConstants::KEY_2 => Some(Tool::Obstacles),
+            GlobalConstants::KEY_3 => Some(Tool::Paths),
+            GlobalConstants::KEY_4 => Some(Tool::Turrets),
+            GlobalConstants::KEY_5 => Some(Tool::Player),
+            _ => None,
+        }
+    }
+}
+
+/// Edits the level of the `Map` it is added to. The ground is painted tile by
+/// tile, everything else is rebuilt from the level after each edit. Keys 1 to
+/// 5 select a tool, Q and E the tile or obstacle, R rotates what is under the
+/// mouse, Enter finishes a path, Ctrl+Z and Ctrl+Y undo and redo, and Ctrl+S
+/// saves the level.
+#[derive(NativeClass)]
+#[inherit(Node2D)]
+pub struct LevelEditor {
+    level: Level,
+    history: History,
+    save_path: String,
+    map: Option<Ref<Node2D>>,
+
+    tool: Tool,
+    tiles: Vec<i64>,
+    tile: usize,
+    obstacle_type: usize,
+    rotation: f32,
+    active_path: Option<usize>,
+    /// The level before the tiles painted since the mouse button was pressed.
+    stroke: Option<Level>,
+    message: String,
+
+    status_node: NodeRef<Label>,
+}
+
+#[methods]
+impl LevelEditor {
+    fn new(_owner: TRef<Node2D>) -> Self {
+        LevelEditor {
+            level: Level::default(),
+            history: History::new(),
+            save_path: String::new(),
+            map: None,
+
+            tool: Tool::Tiles,
+            tiles: Vec::new(),
+            tile: 0,
+            obstacle_type: 0,
+            rotation: 0.0,
+            active_path: None,
+            stroke: None,
+            message: String::new(),
+
+            status_node: NodeRef::of::<level_editor_tscn::OverlayStatusNode>(),
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: TRef<Node2D>) {
+        report_error(
+            self.status_node
+                .try_get_from(owner.upcast::<Node>().as_ref()),
+        );
+        owner.set_z_index(100);
+    }
+
+    /// Starts editing `level`, which is built in `map`. Saving writes the
+    /// level to `save_path`.
+    pub fn start(&mut self, owner: TRef<Node2D>, map: TRef<Node2D>, level: Level, save_path: &str) {
+        self.tiles = map
+            .get_node_or_null(terrain::GROUND_NODE)
+            .and_then(|ground| unsafe { ground.assume_safe() }.cast::<TileMap>())
+            .and_then(|ground| ground.tileset())
+            .map(|tileset| {
+                unsafe { tileset.assume_safe() }
+                    .get_tiles_ids()
+                    .iter()
+                    .map(|id| id.to_i64())
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.tiles.sort_unstable();
+
+        self.level = level;
+        self.history = History::new();
+        self.save_path = save_path.to_string();
+        self.map = Some(map.claim());
+        self.active_path = None;
+        self.stroke = None;
+        self.message = format!("Editing `{}`", self.level.name);
+        self.refresh(owner);
+    }
+
+    /// Stops editing and returns the edited level.
+    pub fn finish(&mut self) -> Level {
+        self.finish_path();
+        self.map = None;
+        std::mem::take(&mut self.level)
+    }
+
+    #[export]
+    fn _unhandled_input(&mut self, owner: TRef<Node2D>, event: Ref<InputEvent>) {
+        let event = unsafe { event.assume_safe() };
+        if event.is_action_pressed(ACTION_LEVEL_EDITOR, false) {
+            if let Some(map) = self.map.as_ref() {
+                unsafe {
+                    map.assume_safe()
+                        .call_deferred("set_editor_mode", &[Variant::from_bool(false)]);
+                }
+            }
+            if let Some(tree) = owner.get_tree() {
+                unsafe { tree.assume_safe() }.set_input_as_handled();
+            }
+            return;
+        }
+
+        let position = owner.get_local_mouse_position();
+        if let Some(key) = event.cast::<InputEventKey>() {
+            if key.is_pressed() && !key.is_echo() {
+                self.key_pressed(owner, key, position);
+            }
+        } else if let Some(button) = event.cast::<InputEventMouseButton>() {
+            self.button_changed(owner, button, position);
+        } else if let Some(motion) = event.cast::<InputEventMouseMotion>() {
+            if self.tool == Tool::Tiles && self.stroke.is_some() {
+                let tile = match motion.button_mask() {
+                    GlobalConstants::BUTTON_MASK_LEFT => self.selected_tile(),
+                    GlobalConstants::BUTTON_MASK_RIGHT => EMPTY_TILE,
+                    _ => return,
+                };
+                self.paint(position, tile);
+            }
+        } else {
+            return;
+        }
+
+        owner.update();
+    }
+
+    #[export]
+    fn _draw(&self, owner: TRef<Node2D>) {
+        let level = &self.level;
+        let size = level.tile_size;
+        let mouse = owner.get_local_mouse_position();
+
+        let tiles = &level.tiles;
+        owner.draw_rect(
+            Rect2::new(
+                Point2::new(tiles.x as f32 * size, tiles.y as f32 * size),
+                Size2::new(tiles.width as f32 * size, tiles.height as f32 * size),
+            ),
+            BOUNDS_COLOR,
+            false,
+            2.0,
+            false,
+        );
+
+        for (i, path) in level.paths.iter().enumerate() {
+            let color = if self.active_path == Some(i) {
+                ACTIVE_PATH_COLOR
+            } else {
+                PATH_COLOR
+            };
+            let points = path
+                .points
+                .iter()
+                .map(|point| path.position + point.position)
+                .collect::<Vec<_>>();
+            if points.len() > 1 {
+                let mut polyline = Vector2Array::new();
+                points.iter().for_each(|point| polyline.push(*point));
+                owner.draw_polyline(polyline, color, 3.0, true);
+            }
+            for point in points {
+                owner.draw_circle(point, 8.0, color);
+            }
+        }
+
+        owner.draw_circle(level.player.position, PICK_RADIUS as f64, PLAYER_COLOR);
+        owner.draw_line(
+            level.player.position,
+            level.player.position
+                + Vector2::new(PICK_RADIUS * 1.5, 0.0)
+                    .rotated(Angle::radians(level.player.rotation)),
+            PLAYER_COLOR,
+            3.0,
+            true,
+        );
+
+        match self.tool {
+            Tool::Tiles => {
+                let cell = (mouse / size).floor() * size;
+                owner.draw_rect(
+                    Rect2::new(cell.to_point(), Size2::new(size, size)),
+                    CURSOR_COLOR,
+                    false,
+                    2.0,
+                    false,
+                );
+            }
+            Tool::Obstacles => {
+                let extents = self
+                    .selected_obstacle()
+                    .map_or_else(Vector2::zero, ObstacleDef::extents);
+                owner.draw_set_transform(mouse, self.rotation as f64, Vector2::new(1.0, 1.0));
+                owner.draw_rect(
+                    Rect2::new((-extents).to_point(), (extents * 2.0).to_size()),
+                    CURSOR_COLOR,
+                    false,
+                    2.0,
+                    false,
+                );
+                owner.draw_set_transform(Vector2::zero(), 0.0, Vector2::new(1.0, 1.0));
+            }
+            Tool::Paths => {
+                let last = self
+                    .active_path
+                    .and_then(|i| level.paths.get(i))
+                    .and_then(|path| {
+                        path.points
+                            .last()
+                            .map(|point| path.position + point.position)
+                    });
+                if let Some(last) = last {
+                    owner.draw_line(last, mouse, ACTIVE_PATH_COLOR, 2.0, true);
+                }
+                owner.draw_circle(mouse, 8.0, CURSOR_COLOR);
+            }
+            Tool::Turrets | Tool::Player => {
+                owner.draw_arc(
+                    mouse,
+                    PICK_RADIUS as f64,
+                    0.0,
+                    2.0 * PI,
+                    32,
+                    CURSOR_COLOR,
+                    2.0,
+                    true,
+                );
+                owner.draw_line(
+                    mouse,
+                    mouse + Vector2::new(PICK_RADIUS, 0.0).rotated(Angle::radians(self.rotation)),
+                    CURSOR_COLOR,
+                    2.0,
+                    true,
+                );
+            }
+        }
+    }
+
+    fn key_pressed(&mut self, owner: TRef<Node2D>, key: TRef<InputEventKey>, position: Vector2) {
+        let scancode = key.scancode();
+        if key.control() {
+            match scancode {
+                GlobalConstants::KEY_Z if key.shift() => self.redo(owner),
+                GlobalConstants::KEY_Z => self.undo(owner),
+                GlobalConstants::KEY_Y => self.redo(owner),
+                GlobalConstants::KEY_S => self.save(owner),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(tool) = Tool::from_scancode(scancode) {
+            self.finish_path();
+            self.tool = tool;
+            self.refresh(owner);
+            return;
+        }
+
+        match scancode {
+            GlobalConstants::KEY_Q => self.select(owner, -1),
+            GlobalConstants::KEY_E => self.select(owner, 1),
+            GlobalConstants::KEY_R => {
+                let step = if key.shift() {
+                    -ROTATION_STEP
+                } else {
+                    ROTATION_STEP
+                };
+                self.rotate(owner, position, step);
+            }
+            GlobalConstants::KEY_ENTER | GlobalConstants::KEY_KP_ENTER => {
+                self.finish_path();
+                self.refresh(owner);
+            }
+            _ => {}
+        }
+    }
+
+    fn button_changed(
+        &mut self,
+        owner: TRef<Node2D>,
+        button: TRef<InputEventMouseButton>,
+        position: Vector2,
+    ) {
+        let index = button.button_index();
+        if index != GlobalConstants::BUTTON_LEFT && index != GlobalConstants::BUTTON_RIGHT {
+            return;
+        }
+        let place = index == GlobalConstants::BUTTON_LEFT;
+
+        if self.tool == Tool::Tiles {
+            if button.is_pressed() {
+                self.stroke = Some(self.level.clone());
+                self.paint(
+                    position,
+                    if place {
+                        self.selected_tile()
+                    } else {
+                        EMPTY_TILE
+                    },
+                );
+            } else if let Some(before) = self.stroke.take() {
+                if before != self.level {
+                    self.history.push(before);
+                    self.refresh(owner);
+                }
+            }
+            return;
+        }
+        if !button.is_pressed() {
+            return;
+        }
+
+        let rotation = self.rotation;
+        let changed = match (self.tool, place) {
+            (Tool::Obstacles, true) => match self.selected_obstacle() {
+                Some(obstacle) => self.edit(owner, |level| {
+                    level.obstacles.push(LevelObstacle::new(
+                        obstacle.name.clone(),
+                        Placement::new(position, rotation),
+                    ));
+                    true
+                }),
+                None => false,
+            },
+            (Tool::Obstacles, false) => self.edit(owner, |level| {
+                match level.obstacle_at(position, PICK_RADIUS) {
+                    Some(i) => level.obstacles.remove(i),
+                    None => return false,
+                };
+                true
+            }),
+            (Tool::Turrets, true) => self.edit(owner, |level| {
+                level.turrets.push(Placement::new(position, rotation));
+                true
+            }),
+            (Tool::Turrets, false) => self.edit(owner, |level| {
+                match level.turret_at(position, PICK_RADIUS) {
+                    Some(i) => level.turrets.remove(i),
+                    None => return false,
+                };
+                true
+            }),
+            (Tool::Paths, true) => self.add_path_point(owner, position),
+            (Tool::Paths, false) => {
+                self.finish_path();
+                self.edit(owner, |level| match level.path_at(position, PICK_RADIUS) {
+                    Some(i) => {
+                        level.remove_path(i);
+                        true
+                    }
+                    None => false,
+                })
+            }
+            (Tool::Player, true) => self.edit(owner, |level| {
+                level.player.position = position;
+                true
+            }),
+            _ => false,
+        };
+        if !changed {
+            self.refresh(owner);
+        }
+    }
+
+    /// Adds a point at `position` to the path being drawn, or starts a new
+    /// path.
+    fn add_path_point(&mut self, owner: TRef<Node2D>, position: Vector2) -> bool {
+        let point = PathPoint::new(position);
+        match self.active_path {
+            Some(index) => self.edit(owner, |level| {
+                let path = &mut level.paths[index];
+                let point = PathPoint::new(point.position - path.position);
+                path.points.push(point);
+                true
+            }),
+            None => {
+                self.active_path = Some(self.level.paths.len());
+                self.edit(owner, |level| {
+                    level.paths.push(LevelPath {
+                        position: Vector2::zero(),
+                        points: vec![point],
+                        patrols: NEW_PATH_PATROLS,
+                    });
+                    true
+                })
+            }
+        }
+    }
+
+    /// Stops drawing the active path. A path needs at least two points, so a
+    /// path with only one is removed.
+    fn finish_path(&mut self) {
+        if let Some(index) = self.active_path.take() {
+            if self
+                .level
+                .paths
+                .get(index)
+                .is_some_and(|path| path.points.len() < 2)
+            {
+                self.level.remove_path(index);
+                self.rebuild();
+            }
+        }
+    }
+
+    /// Rotates the obstacle or turret under the mouse, or the player in the
+    /// player tool. Otherwise the rotation of new obstacles and turrets
+    /// changes.
+    fn rotate(&mut self, owner: TRef<Node2D>, position: Vector2, step: f32) {
+        let changed = match self.tool {
+            Tool::Obstacles => self.edit(owner, |level| {
+                match level.obstacle_at(position, PICK_RADIUS) {
+                    Some(i) => level.obstacles[i].placement.rotation += step,
+                    None => return false,
+                }
+                true
+            }),
+            Tool::Turrets => self.edit(owner, |level| {
+                match level.turret_at(position, PICK_RADIUS) {
+                    Some(i) => level.turrets[i].rotation += step,
+                    None => return false,
+                }
+                true
+            }),
+            Tool::Player => self.edit(owner, |level| {
+                level.player.rotation += step;
+                true
+            }),
+            _ => false,
+        };
+
+        if !changed {
+            self.rotation = (self.rotation + step).rem_euclid(2.0 * PI as f32);
+            self.refresh(owner);
+        }
+    }
+
+    /// Selects the next or previous tile or obstacle type.
+    fn select(&mut self, owner: TRef<Node2D>, step: isize) {
+        let (selected, len) = match self.tool {
+            Tool::Tiles => (&mut self.tile, self.tiles.len()),
+            Tool::Obstacles => (&mut self.obstacle_type, obstacle::atlas().obstacles.len()),
+            _ => return,
+        };
+        if len > 0 {
+            *selected = (*selected as isize + step).rem_euclid(len as isize) as usize;
+        }
+        self.refresh(owner);
+    }
+
+    /// Sets the tile under `position` directly in the ground, without
+    /// rebuilding the level.
+    fn paint(&mut self, position: Vector2, tile: i64) {
+        let cell = (position / self.level.tile_size).floor();
+        let (column, row) = (cell.x as i64, cell.y as i64);
+        if !self.level.set_tile_at(column, row, tile) {
+            return;
+        }
+
+        let ground = self
+            .map
+            .as_ref()
+            .and_then(|map| unsafe { map.assume_safe() }.get_node_or_null(terrain::GROUND_NODE))
+            .and_then(|ground| unsafe { ground.assume_safe() }.cast::<TileMap>());
+        if let Some(ground) = ground {
+            ground.set_cell(column, row, tile, false, false, false, Vector2::zero());
+        }
+    }
+
+    /// Applies `edit` to the level and rebuilds the map when it returns
+    /// true, so the edit can be undone.
+    fn edit<F>(&mut self, owner: TRef<Node2D>, edit: F) -> bool
+    where
+        F: FnOnce(&mut Level) -> bool,
+    {
+        let before = self.level.clone();
+        if !edit(&mut self.level) {
+            return false;
+        }
+
+        self.history.push(before);
+        self.rebuild();
+        self.refresh(owner);
+        true
+    }
+
+    fn undo(&mut self, owner: TRef<Node2D>) {
+        self.active_path = None;
+        self.message = if self.history.undo(&mut self.level) {
+            // a path that was being drawn can be left with a single point
+            self.level.remove_short_paths();
+            self.rebuild();
+            String::from("Undone")
+        } else {
+            String::from("Nothing to undo")
+        };
+        self.refresh(owner);
+    }
+
+    fn redo(&mut self, owner: TRef<Node2D>) {
+        self.active_path = None;
+        self.message = if self.history.redo(&mut self.level) {
+            self.level.remove_short_paths();
+            self.rebuild();
+            String::from("Redone")
+        } else {
+            String::from("Nothing to redo")
+        };
+        self.refresh(owner);
+    }
+
+    fn save(&mut self, owner: TRef<Node2D>) {
+        self.finish_path();
+        self.message = match self
+            .level
+            .validate()
+            .map_err(|err| format!("Cannot save the level, {}", err))
+            .and_then(|_| write_level(self.save_path.as_str(), &self.level))
+        {
+            Ok(()) => format!("Saved to `{}`", self.save_path),
+            Err(err) => {
+                godot_error!("{}", err);
+                err
+            }
+        };
+        self.refresh(owner);
+    }
+
+    fn rebuild(&self) {
+        if let Some(map) = self.map.as_ref() {
+            report_error(build_level(unsafe { map.assume_safe() }, &self.level));
+        }
+    }
+
+    #[inline]
+    fn selected_tile(&self) -> i64 {
+        self.tiles.get(self.tile).copied().unwrap_or(EMPTY_TILE)
+    }
+
+    #[inline]
+    fn selected_obstacle(&self) -> Option<&'static ObstacleDef> {
+        obstacle::atlas().obstacles.get(self.obstacle_type)
+    }
+
+    /// Updates the status text and redraws the overlay.
+    fn refresh(&self, owner: TRef<Node2D>) {
+        owner.update();
+        let status = match self.status_node.try_get_ref() {
+            Ok(status) => status,
+            Err(_) => return,
+        };
+
+        let mut text = format!("Level editor - {}", self.tool.name());
+        let _ = match self.tool {
+            Tool::Tiles => write!(text, ": tile {} (Q/E)", self.selected_tile()),
+            Tool::Obstacles => write!(
+                text,
+                ": {} (Q/E)",
+                self.selected_obstacle()
+                    .map_or("none", |obstacle| obstacle.name.as_str())
+            ),
+            _ => Ok(()),
+        };
+        if matches!(self.tool, Tool::Obstacles | Tool::Turrets) {
+            let _ = write!(text, ", rotation {:.0}° (R)", self.rotation.to_degrees());
+        }
+        let _ = write!(
+            text,
+            "\n1-5 tool, left click place, right click remove, Enter finish path\n\
+             Ctrl+Z undo ({}), Ctrl+Y redo ({}), Ctrl+S save, F3 play\n{}",
+            self.history.undo_len(),
+            self.history.redo_len(),
+            self.message
+        );
+        status.set_text(text);
+    }
+}
+
+impl InstanceFrom<Self, Node2D> for LevelEditor {}
@@ -0,0 +1,468 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::HashMap;
+
+use gdnative::prelude::*;
+
+use crate::pickup::{PickupType, RES_PICKUP_SCENE};
+use crate::scenes::environment::obstacle_tscn;
+use crate::scenes::tanks::{enemy_tank_tscn, gun_turret_tscn, player_tscn};
+use crate::scenes::ui::hud_tscn;
+use crate::terrain;
+use crate::tscn::{parse_sub_resource_id, unquote, SceneFile, SceneNode};
+
+use super::{
+    Level, LevelObstacle, LevelPath, LevelPickup, PathPoint, Placement, Tiles, MAX_TILES_SIDE,
+};
+
+/// Flags of a cell in the `tile_data` of a tile map.
+const TILE_FLIP_H: i64 = 1 << 29;
+const TILE_FLIP_V: i64 = 1 << 30;
+const TILE_TRANSPOSE: i64 = 1 << 31;
+
+/// A level exported from a map scene, and the nodes of the scene that are
+/// not part of the level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Export {
+    pub level: Level,
+    pub warnings: Vec<String>,
+}
+
+/// Converts a map scene, like `Map01.tscn`, into a level. The player, enemy
+/// tanks following a path, turrets, obstacles and pickups are recognized by the
+/// scene they instance, the ground by its name.
+pub fn export_scene(scene: &SceneFile) -> Result<Export, String> {
+    let nodes = scene.nodes();
+    let root = nodes
+        .iter()
+        .find(|node| node.parent.is_none())
+        .ok_or_else(|| String::from("the scene has no root node"))?;
+    let instances = scene
+        .ext_resources()
+        .into_iter()
+        .map(|res| (res.id, res.path))
+        .collect::<HashMap<_, _>>();
+    let transforms = Transforms::new(&nodes);
+
+    let mut export = Export {
+        level: Level {
+            name: root.name.clone(),
+            ..Level::default()
+        },
+        warnings: Vec::new(),
+    };
+    let level = &mut export.level;
+    let mut path_indices = HashMap::new();
+
+    for node in nodes.iter() {
+        let path = node.path();
+        if path == terrain::GROUND_NODE {
+            let (tile_size, tiles) = export_tiles(node)?;
+            level.tile_size = tile_size;
+            level.tiles = tiles;
+            continue;
+        }
+        if node.typ.as_deref() == Some("Path2D") {
+            let curve = get(node, "curve")
+                .and_then(parse_sub_resource_id)
+                .and_then(|id| scene.sub_resource(id))
+                .and_then(|curve| curve.get("_data"))
+                .ok_or_else(|| format!("path `{}` has no curve", path))?;
+            path_indices.insert(path.clone(), level.paths.len());
+            level.paths.push(LevelPath {
+                position: transforms.get(path.as_str()).position,
+                points: parse_curve_points(curve)
+                    .ok_or_else(|| format!("path `{}` has an invalid curve", path))?,
+                patrols: 0,
+            });
+            continue;
+        }
+
+        let instance = match node.instance.and_then(|id| instances.get(&id)) {
+            Some(instance) => instance.as_str(),
+            None => continue,
+        };
+        let placement = transforms.get(path.as_str());
+        match instance {
+            player_tscn::RES_PATH => level.player = placement,
+            gun_turret_tscn::RES_PATH => level.turrets.push(placement),
+            obstacle_tscn::RES_PATH => match get(node, "type_name").and_then(unquote) {
+                Some(type_name) => level
+                    .obstacles
+                    .push(export_obstacle(node, type_name, placement)),
+                None => export
+                    .warnings
+                    .push(format!("obstacle `{}` has no type, skipped", path)),
+            },
+            RES_PICKUP_SCENE => level.pickups.push(LevelPickup {
+                typ: match get(node, "pickup_type").and_then(unquote) {
+                    Some(name) => name.parse()?,
+                    None => PickupType::Repair,
+                },
+                position: placement.position,
+                respawn_time: get(node, "respawn_time")
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(0.0),
+            }),
+            enemy_tank_tscn::RES_PATH => {
+                // enemies follow the path of the `PathFollow2D` they are in
+                let path_follow = node.parent.as_deref().unwrap_or(".");
+                let owner = path_follow.rsplit_once('/').map(|(parent, _)| parent);
+                match owner.and_then(|owner| path_indices.get(owner)) {
+                    Some(index) => level.paths[*index].patrols += 1,
+                    None => export
+                        .warnings
+                        .push(format!("enemy `{}` does not follow a path, skipped", path)),
+                }
+            }
+            hud_tscn::RES_PATH => {}
+            _ => export
+                .warnings
+                .push(format!("`{}` instances `{}`, skipped", path, instance)),
+        }
+    }
+
+    Ok(export)
+}
+
+/// Reads the random pick and variation of an obstacle, the properties that
+/// are left out of the scene have their default.
+fn export_obstacle(node: &SceneNode, type_name: String, placement: Placement) -> LevelObstacle {
+    let mut obstacle = LevelObstacle::new(type_name, placement);
+    let number = |key: &str| get(node, key).and_then(|value| value.parse::<f32>().ok());
+    let color = |key: &str| {
+        get(node, key)
+            .and_then(|value| parse_call(value, "Color"))
+            .filter(|values| values.len() == 4)
+            .map(|values| {
+                Color::rgba(
+                    values[0] as f32,
+                    values[1] as f32,
+                    values[2] as f32,
+                    values[3] as f32,
+                )
+            })
+    };
+
+    if let Some(seed) = get(node, "random_seed").and_then(|value| value.parse().ok()) {
+        obstacle.seed = seed;
+    }
+    if let Some(category) = get(node, "random_category").and_then(unquote) {
+        obstacle.category = category;
+    }
+    let variation = &mut obstacle.variation;
+    if let Some(rotation) = number("random_rotation") {
+        variation.rotation = rotation;
+    }
+    if let Some(scale) = number("random_scale_min") {
+        variation.scale_min = scale;
+    }
+    if let Some(scale) = number("random_scale_max") {
+        variation.scale_max = scale;
+    }
+    variation.flip_h = get(node, "random_flip_h") == Some("true");
+    variation.flip_v = get(node, "random_flip_v") == Some("true");
+    if let Some(tint) = color("random_tint_min") {
+        variation.tint_min = tint;
+    }
+    if let Some(tint) = color("random_tint_max") {
+        variation.tint_max = tint;
+    }
+    obstacle
+}
+
+fn export_tiles(node: &SceneNode) -> Result<(f32, Tiles), String> {
+    let tile_size = match get(node, "cell_size") {
+        Some(value) => {
+            let size = parse_call(value, "Vector2")
+                .filter(|size| size.len() == 2)
+                .ok_or_else(|| format!("invalid cell size `{}`", value))?;
+            if size[0] != size[1] {
+                return Err(format!("the cells of the ground are not square, {}", value));
+            }
+            size[0] as f32
+        }
+        None => 64.0,
+    };
+
+    let data = match get(node, "tile_data") {
+        Some(value) => parse_call(value, "PoolIntArray")
+            .ok_or_else(|| String::from("invalid tile data of the ground"))?,
+        None => Vec::new(),
+    };
+
+    let mut cells = Vec::with_capacity(data.len() / 3);
+    for cell in data.chunks(3) {
+        let (position, tile, autotile) = match cell {
+            [position, tile, autotile] => (*position as i64, *tile as i64, *autotile as i64),
+            _ => return Err(String::from("incomplete tile data of the ground")),
+        };
+        // the column and row are stored in the low and high 16 bits
+        let position = position as u32;
+        let (x, y) = ((position & 0xffff) as i16, (position >> 16) as i16);
+        let tile = tile & 0xffff_ffff;
+        if tile & (TILE_FLIP_H | TILE_FLIP_V | TILE_TRANSPOSE) != 0 || autotile != 0 {
+            return Err(format!(
+                "the tile at {}, {} is flipped, transposed or part of an autotile, which levels do not support",
+                x, y
+            ));
+        }
+        cells.push((x as i64, y as i64, tile));
+    }
+
+    // the bounds of the cells, a ground without cells has no tiles
+    let bounds = cells.iter().fold(None, |bounds, &(x, y, _)| match bounds {
+        None => Some((x, y, x, y)),
+        Some((min_x, min_y, max_x, max_y)) => {
+            Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)))
+        }
+    });
+    let (min_x, min_y, max_x, max_y) = match bounds {
+        Some(bounds) => bounds,
+        None => return Ok((tile_size, Tiles::default())),
+    };
+    let (width, height) = ((max_x - min_x + 1) as u32, (max_y - min_y + 1) as u32);
+    if width > MAX_TILES_SIDE || height > MAX_TILES_SIDE {
+        return Err(format!(
+            "the ground spans {}x{} tiles, levels support at most {2}x{2}",
+            width, height, MAX_TILES_SIDE
+        ));
+    }
+    let mut tiles = Tiles::new(min_x, min_y, width, height);
+    for (x, y, tile) in cells {
+        tiles.set((x - min_x) as u32, (y - min_y) as u32, tile);
+    }
+    Ok((tile_size, tiles))
+}
+
+/// The position and rotation of each node relative to the root of the scene,
+/// from the `position` and `rotation` of the node and its parents.
+struct Transforms<'a> {
+    nodes: HashMap<String, &'a SceneNode>,
+}
+
+impl<'a> Transforms<'a> {
+    fn new(nodes: &'a [SceneNode]) -> Self {
+        Transforms {
+            nodes: nodes.iter().map(|node| (node.path(), node)).collect(),
+        }
+    }
+
+    fn get(&self, path: &str) -> Placement {
+        let node = match self.nodes.get(path) {
+            Some(node) if path != "." => node,
+            _ => return Placement::default(),
+        };
+
+        let local = Placement {
+            position: get(node, "position")
+                .and_then(|value| parse_call(value, "Vector2"))
+                .filter(|position| position.len() == 2)
+                .map(|position| Vector2::new(position[0] as f32, position[1] as f32))
+                .unwrap_or_else(Vector2::zero),
+            rotation: get(node, "rotation")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(0.0),
+        };
+        let parent = self.get(node.parent.as_deref().unwrap_or("."));
+        Placement {
+            position: parent.position + local.position.rotated(Angle::radians(parent.rotation)),
+            rotation: parent.rotation + local.rotation,
+        }
+    }
+}
+
+#[inline]
+fn get<'a>(node: &'a SceneNode, key: &str) -> Option<&'a str> {
+    node.properties
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Reads the numbers of a value like `Vector2( 1, 2 )` or
+/// `PoolIntArray( 1, 2, 3 )`, which may be part of a larger value.
+fn parse_call(value: &str, name: &str) -> Option<Vec<f64>> {
+    let start = value.find(name)? + name.len();
+    let args = value[start..].trim_start().strip_prefix('(')?;
+    let args = &args[..args.find(')')?];
+    args.split(',')
+        .map(str::trim)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| arg.parse().ok())
+        .collect()
+}
+
+/// Reads the points of the `_data` of a `Curve2D`, which are stored as in
+/// handle, out handle and position.
+fn parse_curve_points(data: &str) -> Option<Vec<PathPoint>> {
+    let points = data.find("\"points\"")?;
+    let values = parse_call(&data[points..], "PoolVector2Array")?;
+    if values.len() % 6 != 0 {
+        return None;
+    }
+
+    let vector = |x: f64, y: f64| Vector2::new(x as f32, y as f32);
+    Some(
+        values
+            .chunks(6)
+            .map(|point| PathPoint {
+                position: vector(point[4], point[5]),
+                handle_in: vector(point[0], point[1]),
+                handle_out: vector(point[2], point[3]),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::level::EMPTY_TILE;
+
+    /// A map with a ground of three tiles, one of them at column -1, and one
+    /// node of each kind.
+    fn scene() -> SceneFile {
+        format!(
+            r#"[gd_scene load_steps=7 format=2]
+
+[ext_resource path="{player}" type="PackedScene" id=1]
+[ext_resource path="{turret}" type="PackedScene" id=2]
+[ext_resource path="{obstacle}" type="PackedScene" id=3]
+[ext_resource path="{pickup}" type="PackedScene" id=4]
+[ext_resource path="{enemy}" type="PackedScene" id=5]
+[ext_resource path="res://Other.tscn" type="PackedScene" id=6]
+
+[sub_resource type="Curve2D" id=1]
+_data = {{
+"points": PoolVector2Array( 0, 0, 0, 0, 10, 20, -5, 0, 5, 0, 30, 40 )
+}}
+
+[node name="Map01" type="Node2D"]
+
+[node name="Ground" type="TileMap" parent="."]
+cell_size = Vector2( 64, 64 )
+tile_data = PoolIntArray( 0, 1, 0, 1, 2, 0, 131071, 3, 0 )
+
+[node name="Player" parent="." instance=ExtResource( 1 )]
+position = Vector2( 100, 200 )
+rotation = 1.5
+
+[node name="Turrets" type="Node2D" parent="."]
+position = Vector2( 10, 0 )
+rotation = 3.14159
+
+[node name="GunTurret" parent="Turrets" instance=ExtResource( 2 )]
+position = Vector2( 5, 0 )
+
+[node name="Fence" parent="." instance=ExtResource( 3 )]
+position = Vector2( 1, 2 )
+type_name = "fenceYellow"
+random_seed = 42
+random_category = "fence"
+random_flip_h = true
+random_tint_min = Color( 0.5, 0.5, 0.5, 1 )
+
+[node name="Untyped" parent="." instance=ExtResource( 3 )]
+
+[node name="Pickup" parent="." instance=ExtResource( 4 )]
+position = Vector2( 3, 4 )
+pickup_type = "speed_boost"
+respawn_time = 45.0
+
+[node name="Path2D" type="Path2D" parent="."]
+position = Vector2( 1000, 0 )
+curve = SubResource( 1 )
+
+[node name="PathFollow2D" type="PathFollow2D" parent="Path2D"]
+
+[node name="EnemyTank" parent="Path2D/PathFollow2D" instance=ExtResource( 5 )]
+
+[node name="Other" parent="." instance=ExtResource( 6 )]
+"#,
+            player = player_tscn::RES_PATH,
+            turret = gun_turret_tscn::RES_PATH,
+            obstacle = obstacle_tscn::RES_PATH,
+            pickup = RES_PICKUP_SCENE,
+            enemy = enemy_tank_tscn::RES_PATH,
+        )
+        .parse()
+        .unwrap()
+    }
+
+    fn assert_close(a: Vector2, b: Vector2) {
+        assert!((a - b).length() < 0.001, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn scene_is_exported() {
+        let export = export_scene(&scene()).unwrap();
+        let level = &export.level;
+
+        assert_eq!(level.name, "Map01");
+        assert_eq!(level.tile_size, 64.0);
+        assert_eq!((level.tiles.x, level.tiles.y), (-1, 0));
+        assert_eq!((level.tiles.width, level.tiles.height), (3, 2));
+        assert_eq!(
+            level.tiles.cells,
+            [EMPTY_TILE, 1, 2, 3, EMPTY_TILE, EMPTY_TILE]
+        );
+
+        assert_eq!(level.player.position, Vector2::new(100.0, 200.0));
+        assert_eq!(level.player.rotation, 1.5);
+
+        // the turret is placed by its parent, which is turned around
+        assert_eq!(level.turrets.len(), 1);
+        assert_close(level.turrets[0].position, Vector2::new(5.0, 0.0));
+
+        assert_eq!(level.obstacles.len(), 1);
+        let fence = &level.obstacles[0];
+        assert_eq!(fence.type_name, "fenceYellow");
+        assert_eq!((fence.seed, fence.category.as_str()), (42, "fence"));
+        assert!(fence.variation.flip_h && !fence.variation.flip_v);
+        assert_eq!(fence.variation.tint_min, Color::rgba(0.5, 0.5, 0.5, 1.0));
+
+        assert_eq!(level.pickups.len(), 1);
+        assert_eq!(level.pickups[0].typ, PickupType::SpeedBoost);
+        assert_eq!(level.pickups[0].respawn_time, 45.0);
+
+        assert_eq!(level.paths.len(), 1);
+        let path = &level.paths[0];
+        assert_eq!(path.position, Vector2::new(1000.0, 0.0));
+        assert_eq!(path.patrols, 1);
+        assert_eq!(path.points.len(), 2);
+        assert_eq!(path.points[1].position, Vector2::new(30.0, 40.0));
+        assert_eq!(path.points[1].handle_in, Vector2::new(-5.0, 0.0));
+
+        assert_eq!(export.warnings.len(), 2, "{:?}", export.warnings);
+        assert_eq!(
+            export.level.to_string().parse::<Level>().unwrap(),
+            export.level
+        );
+    }
+
+    #[test]
+    fn unsupported_ground_is_an_error() {
+        let flipped = "[node name=\"Map\" type=\"Node2D\"]\n\
+            [node name=\"Ground\" type=\"TileMap\" parent=\".\"]\n\
+            tile_data = PoolIntArray( 0, 536870913, 0 )\n";
+        assert!(export_scene(&flipped.parse().unwrap()).is_err());
+
+        let wide = format!(
+            "[node name=\"Map\" type=\"Node2D\"]\n\
+            [node name=\"Ground\" type=\"TileMap\" parent=\".\"]\n\
+            tile_data = PoolIntArray( 0, 1, 0, {}, 1, 0 )\n",
+            MAX_TILES_SIDE
+        );
+        assert!(export_scene(&wide.parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn scene_without_ground_has_no_tiles() {
+        let scene = "[node name=\"Map\" type=\"Node2D\"]\n".parse().unwrap();
+        assert_eq!(export_scene(&scene).unwrap().level.tiles, Tiles::default());
+    }
+}
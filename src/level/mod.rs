@@ -0,0 +1,702 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! A compact, versioned text format of levels. Unlike a `.tscn` file a level
+//! file only contains what makes up the level, so it can be diffed in review
+//! and written by tools. Each line is a record of a keyword and its fields:
+//!
+//! ```text
+//! level 2                       format version, always the first record
+//! name Map01
+//! tile_size 128
+//! tiles -1 -1 38 18             first column and row, width and height
+//! row 0*4 1 0*7 1*2 ...         tiles of a row, `id*count` repeats a tile
+//! player 1193 530 1.5708        position and rotation
+//! path 9 3 1                    position and number of patrolling enemies
+//! point 1602 1009 14 -183 -14 183   position and in and out handles
+//! turret 2299 326
+//! obstacle fenceYellow 1476 1980 1.0472
+//! random 12345 fence 15 0.9 1.1 hv 1,1,1,1 0.8,0.8,0.7,1
+//! pickup speed_boost 3300 1400 45   type, position and respawn time
+//! wave 30 0 3                   delay, index of the path and enemies
+//! ```
+//!
+//! Points belong to the path before them. A `random` record, added in version
+//! 2, belongs to the obstacle before it: the seed, the category its type is
+//! picked from or `-`, and the variation of its look as rotation, scale range,
+//! flips (`-`, `h`, `v` or `hv`) and tint range. Rotations, handles and
+//! respawn times may be left out when they are zero, lines starting with `#`
+//! are comments.
+
+use std::fmt;
+use std::str::FromStr;
+
+use gdnative::prelude::*;
+
+use crate::obstacle::{ObstacleAtlas, Variation};
+use crate::pickup::PickupType;
+use crate::utils::{read_records, Record, RecordError};
+
+pub use build::*;
+pub use edit::*;
+pub use editor::*;
+pub use export::*;
+
+mod build;
+mod edit;
+mod editor;
+mod export;
+
+/// Version of the level files that are written, older versions can still be
+/// read.
+pub const LEVEL_VERSION: u32 = 2;
+
+pub const LEVEL_EXTENSION: &str = "level";
+
+/// Tile id of a cell without a tile.
+pub const EMPTY_TILE: i64 = -1;
+/// Most columns or rows of tiles a level can have.
+pub const MAX_TILES_SIDE: u32 = 4096;
+
+/// A rectangle of tiles of the `Ground` tile map.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tiles {
+    /// Column of the first tile.
+    pub x: i64,
+    /// Row of the first tile.
+    pub y: i64,
+    pub width: u32,
+    pub height: u32,
+    /// Tile ids, row by row.
+    pub cells: Vec<i64>,
+}
+
+impl Tiles {
+    /// Empty tiles of `width` by `height`. Panics when the number of cells
+    /// does not fit in memory, sizes read from files are at most
+    /// `MAX_TILES_SIDE`.
+    pub fn new(x: i64, y: i64, width: u32, height: u32) -> Self {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .expect("too many tiles");
+        Tiles {
+            x,
+            y,
+            width,
+            height,
+            cells: vec![EMPTY_TILE; len],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, column: u32, row: u32) -> i64 {
+        self.cells[self.index(column, row)]
+    }
+
+    #[inline]
+    pub fn set(&mut self, column: u32, row: u32, tile: i64) {
+        let index = self.index(column, row);
+        self.cells[index] = tile;
+    }
+
+    #[inline]
+    fn index(&self, column: u32, row: u32) -> usize {
+        row as usize * self.width as usize + column as usize
+    }
+
+    /// The cells that have a tile, as column and row in the tile map and the
+    /// tile id.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, i64, i64)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| **tile != EMPTY_TILE)
+            .map(move |(i, tile)| {
+                let (column, row) = (i as u32 % self.width, i as u32 / self.width);
+                (self.x + column as i64, self.y + row as i64, *tile)
+            })
+    }
+}
+
+/// A position and rotation in the map.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Placement {
+    pub position: Vector2,
+    pub rotation: f32,
+}
+
+impl Placement {
+    #[inline]
+    pub fn new(position: Vector2, rotation: f32) -> Self {
+        Placement { position, rotation }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PathPoint {
+    /// Position relative to the path.
+    pub position: Vector2,
+    pub handle_in: Vector2,
+    pub handle_out: Vector2,
+}
+
+impl PathPoint {
+    #[inline]
+    pub fn new(position: Vector2) -> Self {
+        PathPoint {
+            position,
+            handle_in: Vector2::zero(),
+            handle_out: Vector2::zero(),
+        }
+    }
+}
+
+/// A path that enemies follow.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelPath {
+    pub position: Vector2,
+    pub points: Vec<PathPoint>,
+    /// Enemies that patrol the path from the start of the level.
+    pub patrols: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelObstacle {
+    /// Name of the obstacle in the obstacle atlas.
+    pub type_name: String,
+    pub placement: Placement,
+    /// Seed of the random pick and variation, zero when there is none.
+    pub seed: i64,
+    /// Category of the atlas the type is picked from, empty when the type is
+    /// fixed.
+    pub category: String,
+    pub variation: Variation,
+}
+
+impl LevelObstacle {
+    #[inline]
+    pub fn new(type_name: String, placement: Placement) -> Self {
+        LevelObstacle {
+            type_name,
+            placement,
+            seed: 0,
+            category: String::new(),
+            variation: Variation::default(),
+        }
+    }
+
+    /// Whether the obstacle has a `random` record.
+    #[inline]
+    pub fn is_random(&self) -> bool {
+        self.seed != 0 || !self.category.is_empty() || self.variation != Variation::default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelPickup {
+    pub typ: PickupType,
+    pub position: Vector2,
+    /// Seconds before the pickup appears again, zero when it does not.
+    pub respawn_time: f32,
+}
+
+/// Enemies that enter the level on a path some time after it started.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wave {
+    /// Seconds after the start of the level.
+    pub delay: f32,
+    /// Index of the path in `Level::paths`.
+    pub path: usize,
+    pub enemies: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub tile_size: f32,
+    pub tiles: Tiles,
+    pub player: Placement,
+    pub paths: Vec<LevelPath>,
+    pub turrets: Vec<Placement>,
+    pub obstacles: Vec<LevelObstacle>,
+    /// The pickups, which are the objectives of the level.
+    pub pickups: Vec<LevelPickup>,
+    pub waves: Vec<Wave>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level {
+            name: String::new(),
+            tile_size: 128.0,
+            tiles: Tiles::default(),
+            player: Placement::default(),
+            paths: Vec::new(),
+            turrets: Vec::new(),
+            obstacles: Vec::new(),
+            pickups: Vec::new(),
+            waves: Vec::new(),
+        }
+    }
+}
+
+impl Level {
+    /// Names of the obstacles of the level that `atlas` does not define,
+    /// without duplicates.
+    pub fn unknown_obstacles<'a>(&'a self, atlas: &ObstacleAtlas) -> Vec<&'a str> {
+        let mut names = self
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.type_name.as_str())
+            .filter(|name| !atlas.contains(name))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Checks what a level file can only be read with: every path has at
+    /// least two points, every wave enters on a path, and there is a tile id
+    /// for each cell.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(index) = self.paths.iter().position(|path| path.points.len() < 2) {
+            return Err(format!("path {} has less than 2 points", index));
+        }
+        if let Some(wave) = self.waves.iter().find(|wave| wave.path >= self.paths.len()) {
+            return Err(format!("there is no path {}", wave.path));
+        }
+        let cells = self.tiles.width as usize * self.tiles.height as usize;
+        if self.tiles.cells.len() != cells {
+            return Err(format!(
+                "expected {} tiles, found {}",
+                cells,
+                self.tiles.cells.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Level {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut level = Level::default();
+        let mut rows = 0;
+        let (version, records) = read_records(s, "level", LEVEL_VERSION)?;
+
+        for mut record in records {
+            match record.keyword {
+                "name" => level.name = record.rest().to_string(),
+                "tile_size" => {
+                    level.tile_size = record.number("tile size")?;
+                    if level.tile_size <= 0.0 {
+                        return Err(record.error(String::from("the tile size must be positive")));
+                    }
+                }
+                "tiles" => {
+                    let (x, y) = (record.number("column")?, record.number("row")?);
+                    let (width, height) = (record.number("width")?, record.number("height")?);
+                    if width > MAX_TILES_SIDE || height > MAX_TILES_SIDE {
+                        return Err(record.error(format!(
+                            "at most {0}x{0} tiles are supported, found {1}x{2}",
+                            MAX_TILES_SIDE, width, height
+                        )));
+                    }
+                    level.tiles = Tiles::new(x, y, width, height);
+                    rows = 0;
+                }
+                "row" => {
+                    if rows >= level.tiles.height {
+                        return Err(
+                            record.error(format!("more than {} rows of tiles", level.tiles.height))
+                        );
+                    }
+                    let tiles =
+                        parse_row(record.rest()).map_err(|message| record.error(message))?;
+                    if tiles.len() != level.tiles.width as usize {
+                        return Err(record.error(format!(
+                            "expected {} tiles, found {}",
+                            level.tiles.width,
+                            tiles.len()
+                        )));
+                    }
+                    for (column, tile) in tiles.into_iter().enumerate() {
+                        level.tiles.set(column as u32, rows, tile);
+                    }
+                    rows += 1;
+                }
+                "player" => level.player = placement(&mut record)?,
+                "path" => level.paths.push(LevelPath {
+                    position: record.vector("position")?,
+                    points: Vec::new(),
+                    patrols: record.optional("patrols")?,
+                }),
+                "point" => {
+                    let point = PathPoint {
+                        position: record.vector("position")?,
+                        handle_in: record.optional_vector("in handle")?,
+                        handle_out: record.optional_vector("out handle")?,
+                    };
+                    match level.paths.last_mut() {
+                        Some(path) => path.points.push(point),
+                        None => return Err(record.error(String::from("point outside of a path"))),
+                    }
+                }
+                "turret" => level.turrets.push(placement(&mut record)?),
+                "obstacle" => {
+                    level.obstacles.push(LevelObstacle::new(
+                        record.text("obstacle type")?,
+                        placement(&mut record)?,
+                    ));
+                }
+                "random" if version < 2 => {
+                    return Err(record.error(String::from(
+                        "`random` records are not supported before version 2",
+                    )));
+                }
+                "random" => {
+                    let obstacle = match level.obstacles.last_mut() {
+                        Some(obstacle) => obstacle,
+                        None => {
+                            return Err(record.error(String::from("random outside of an obstacle")))
+                        }
+                    };
+                    obstacle.seed = record.number("seed")?;
+                    obstacle.category = match record.text("category")? {
+                        category if category == "-" => String::new(),
+                        category => category,
+                    };
+                    let variation = &mut obstacle.variation;
+                    variation.rotation = record.number("rotation")?;
+                    variation.scale_min = record.number("minimum scale")?;
+                    variation.scale_max = record.number("maximum scale")?;
+                    let flip = record.text("flip")?;
+                    match flip.as_str() {
+                        "-" | "h" | "v" | "hv" => {
+                            variation.flip_h = flip.contains('h');
+                            variation.flip_v = flip.contains('v');
+                        }
+                        _ => return Err(record.error(format!("invalid flip `{}`", flip))),
+                    }
+                    variation.tint_min = record.color("minimum tint")?;
+                    variation.tint_max = record.color("maximum tint")?;
+                }
+                "pickup" => level.pickups.push(LevelPickup {
+                    typ: record.parse("pickup type")?,
+                    position: record.vector("position")?,
+                    respawn_time: record.optional("respawn time")?,
+                }),
+                "wave" => {
+                    let wave = Wave {
+                        delay: record.number("delay")?,
+                        path: record.number("path")?,
+                        enemies: record.number("enemies")?,
+                    };
+                    if wave.path >= level.paths.len() {
+                        return Err(record.error(format!("there is no path {}", wave.path)));
+                    }
+                    level.waves.push(wave);
+                }
+                _ => return Err(record.error(format!("unknown record `{}`", record.keyword))),
+            }
+
+            record.end()?;
+        }
+
+        if rows != level.tiles.height {
+            return Err(RecordError::at_end(
+                s,
+                format!(
+                    "expected {} rows of tiles, found {}",
+                    level.tiles.height, rows
+                ),
+            ));
+        }
+        level
+            .validate()
+            .map_err(|message| RecordError::at_end(s, message))?;
+
+        Ok(level)
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "level {}", LEVEL_VERSION)?;
+        if !self.name.is_empty() {
+            writeln!(f, "name {}", self.name)?;
+        }
+        writeln!(f, "tile_size {}", self.tile_size)?;
+
+        let tiles = &self.tiles;
+        writeln!(
+            f,
+            "tiles {} {} {} {}",
+            tiles.x, tiles.y, tiles.width, tiles.height
+        )?;
+        for row in 0..tiles.height {
+            write!(f, "row")?;
+            let start = (row * tiles.width) as usize;
+            write_row(f, &tiles.cells[start..start + tiles.width as usize])?;
+            writeln!(f)?;
+        }
+
+        write!(f, "player")?;
+        write_placement(f, &self.player)?;
+        writeln!(f)?;
+
+        for path in self.paths.iter() {
+            write!(f, "path {} {}", path.position.x, path.position.y)?;
+            if path.patrols > 0 {
+                write!(f, " {}", path.patrols)?;
+            }
+            writeln!(f)?;
+
+            for point in path.points.iter() {
+                write!(f, "point {} {}", point.position.x, point.position.y)?;
+                if point.handle_in != Vector2::zero() || point.handle_out != Vector2::zero() {
+                    write!(
+                        f,
+                        " {} {} {} {}",
+                        point.handle_in.x,
+                        point.handle_in.y,
+                        point.handle_out.x,
+                        point.handle_out.y
+                    )?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        for turret in self.turrets.iter() {
+            write!(f, "turret")?;
+            write_placement(f, turret)?;
+            writeln!(f)?;
+        }
+
+        for obstacle in self.obstacles.iter() {
+            write!(f, "obstacle {}", obstacle.type_name)?;
+            write_placement(f, &obstacle.placement)?;
+            writeln!(f)?;
+            if obstacle.is_random() {
+                write_random(f, obstacle)?;
+            }
+        }
+
+        for pickup in self.pickups.iter() {
+            write!(
+                f,
+                "pickup {} {} {}",
+                pickup.typ.name(),
+                pickup.position.x,
+                pickup.position.y
+            )?;
+            if pickup.respawn_time != 0.0 {
+                write!(f, " {}", pickup.respawn_time)?;
+            }
+            writeln!(f)?;
+        }
+
+        for wave in self.waves.iter() {
+            writeln!(f, "wave {} {} {}", wave.delay, wave.path, wave.enemies)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_placement(f: &mut fmt::Formatter<'_>, placement: &Placement) -> fmt::Result {
+    write!(f, " {} {}", placement.position.x, placement.position.y)?;
+    if placement.rotation != 0.0 {
+        write!(f, " {}", placement.rotation)?;
+    }
+    Ok(())
+}
+
+fn write_random(f: &mut fmt::Formatter<'_>, obstacle: &LevelObstacle) -> fmt::Result {
+    let variation = &obstacle.variation;
+    let flip = match (variation.flip_h, variation.flip_v) {
+        (false, false) => "-",
+        (true, false) => "h",
+        (false, true) => "v",
+        (true, true) => "hv",
+    };
+    let category = if obstacle.category.is_empty() {
+        "-"
+    } else {
+        obstacle.category.as_str()
+    };
+    let (min, max) = (variation.tint_min, variation.tint_max);
+    writeln!(
+        f,
+        "random {} {} {} {} {} {} {},{},{},{} {},{},{},{}",
+        obstacle.seed,
+        category,
+        variation.rotation,
+        variation.scale_min,
+        variation.scale_max,
+        flip,
+        min.r,
+        min.g,
+        min.b,
+        min.a,
+        max.r,
+        max.g,
+        max.b,
+        max.a
+    )
+}
+
+/// Writes the tiles of a row, where repeated tiles are written once followed
+/// by `*` and their count.
+fn write_row(f: &mut fmt::Formatter<'_>, tiles: &[i64]) -> fmt::Result {
+    let mut i = 0;
+    while i < tiles.len() {
+        let count = tiles[i..]
+            .iter()
+            .take_while(|tile| **tile == tiles[i])
+            .count();
+        if count > 1 {
+            write!(f, " {}*{}", tiles[i], count)?;
+        } else {
+            write!(f, " {}", tiles[i])?;
+        }
+        i += count;
+    }
+    Ok(())
+}
+
+fn parse_row(text: &str) -> Result<Vec<i64>, String> {
+    let mut tiles = Vec::new();
+    for field in text.split_whitespace() {
+        let (tile, count) = field.split_once('*').unwrap_or((field, "1"));
+        let tile = tile
+            .parse::<i64>()
+            .map_err(|_| format!("invalid tile `{}`", field))?;
+        let count = count
+            .parse::<usize>()
+            .map_err(|_| format!("invalid tile count `{}`", field))?;
+        tiles.extend(std::iter::repeat_n(tile, count));
+    }
+    Ok(tiles)
+}
+
+#[inline]
+fn placement(record: &mut Record) -> Result<Placement, RecordError> {
+    Ok(Placement {
+        position: record.vector("position")?,
+        rotation: record.optional("rotation")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "\
+level 2
+# a comment
+name Map01
+tile_size 128
+tiles -1 0 4 2
+row 0*3 5
+row -1 1*2 -1
+player 10 20 1.5
+path 9 3 1
+point 100 200 14 -183 -14 183
+point 300 400
+turret 50 60
+obstacle fenceYellow 70 80 1.25
+random 12345 fence 15 0.9 1.1 hv 1,1,1,1 0.8,0.8,0.7,1
+obstacle barrelRed 90 100
+pickup speed_boost 3300 1400 45
+wave 30 0 3
+";
+
+    fn error(text: &str) -> RecordError {
+        text.parse::<Level>().unwrap_err()
+    }
+
+    #[test]
+    fn level_round_trips() {
+        let level = LEVEL.parse::<Level>().unwrap();
+        assert_eq!(level.name, "Map01");
+        assert_eq!(level.tiles.cells, [0, 0, 0, 5, -1, 1, 1, -1]);
+        assert_eq!(level.tiles.get(3, 0), 5);
+        assert_eq!(level.paths[0].points.len(), 2);
+        assert!(level.obstacles[0].is_random() && !level.obstacles[1].is_random());
+        assert_eq!(level.obstacles[0].category, "fence");
+        assert!(level.obstacles[0].variation.flip_h && level.obstacles[0].variation.flip_v);
+
+        let text = level.to_string();
+        assert!(text.starts_with("level 2\n"));
+        assert!(text.contains("row 0*3 5\n"));
+        assert_eq!(text.parse::<Level>().unwrap(), level);
+    }
+
+    #[test]
+    fn versions_are_checked() {
+        let version_1 = LEVEL.replacen("level 2", "level 1", 1);
+        assert_eq!(error(&version_1).line, 14);
+
+        let without_random = version_1.replace(
+            "random 12345 fence 15 0.9 1.1 hv 1,1,1,1 0.8,0.8,0.7,1\n",
+            "",
+        );
+        assert!(without_random.parse::<Level>().is_ok());
+
+        assert_eq!(error(&LEVEL.replacen("level 2", "level 3", 1)).line, 1);
+        assert_eq!(error(&LEVEL.replacen("level 2", "level 0", 1)).line, 1);
+        assert_eq!(error("name Map01\nlevel 2\n").line, 1);
+    }
+
+    #[test]
+    fn rows_are_checked() {
+        assert_eq!(parse_row("1*3 -1 2*0").unwrap(), [1, 1, 1, -1]);
+        assert_eq!(parse_row("1 x").unwrap_err(), "invalid tile `x`");
+        assert_eq!(parse_row("1*x").unwrap_err(), "invalid tile count `1*x`");
+        assert_eq!(parse_row("*2").unwrap_err(), "invalid tile `*2`");
+
+        let rows = |first: &str, second: &str| {
+            LEVEL
+                .replacen("row 0*3 5", first, 1)
+                .replacen("row -1 1*2 -1", second, 1)
+        };
+        assert_eq!(error(&rows("row 0*3", "row 1*4")).line, 6);
+        assert_eq!(error(&rows("row 0*5", "row 1*4")).line, 6);
+        assert_eq!(error(&rows("row 0*4", "row 1*x")).line, 7);
+        assert_eq!(error(&rows("row 0*4", "row 1*4\nrow 1*4")).line, 8);
+    }
+
+    #[test]
+    fn tiles_are_limited() {
+        let side = format!("tiles 0 0 {} 1", MAX_TILES_SIDE);
+        assert!(LEVEL
+            .replacen("tiles -1 0 4 2", &side, 1)
+            .replacen("row 0*3 5", &format!("row 0*{}", MAX_TILES_SIDE), 1)
+            .replacen("row -1 1*2 -1\n", "", 1)
+            .parse::<Level>()
+            .is_ok());
+
+        let wide = format!("tiles 0 0 {} 1", MAX_TILES_SIDE + 1);
+        assert_eq!(error(&LEVEL.replacen("tiles -1 0 4 2", &wide, 1)).line, 5);
+        let high = format!("tiles 0 0 1 {}", MAX_TILES_SIDE + 1);
+        assert_eq!(error(&LEVEL.replacen("tiles -1 0 4 2", &high, 1)).line, 5);
+    }
+
+    #[test]
+    fn records_outside_their_parent_are_errors() {
+        assert_eq!(error("level 2\npoint 1 2\n").line, 2);
+        assert_eq!(
+            error("level 2\nrandom 1 - 0 1 1 - 1,1,1,1 1,1,1,1\n").line,
+            2
+        );
+        assert_eq!(error("level 2\nwave 1 0 1\n").line, 2);
+        assert_eq!(
+            error("level 2\nobstacle a 1 2\nrandom 1 - 0 1 1 x 1,1,1,1 1,1,1,1\n").line,
+            3
+        );
+        assert_eq!(error("level 2\nunknown 1\n").line, 2);
+    }
+}
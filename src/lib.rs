@@ -5,23 +5,60 @@
 use gdnative::prelude::*;
 
 mod bullet;
+mod camera;
+mod controls;
+mod damage;
+mod drivetrain;
 mod enemies;
+mod events;
+mod fog;
+pub mod level;
 mod map;
-mod obstacle;
+pub mod mapgen;
+pub mod obstacle;
+mod pickup;
 pub mod player;
+mod ramming;
+mod resources;
+pub mod scenes;
+mod score;
+mod status;
 pub mod tank;
+mod terrain;
+pub mod tscn;
 mod ui;
+mod units;
 mod utils;
+pub mod validate;
 
 fn init(handle: InitHandle) {
+    handle.add_class::<controls::Controls>();
+    handle.add_class::<events::Events>();
+    handle.add_class::<resources::Resources>();
+    handle.add_class::<units::Units>();
+    handle.add_class::<score::Score>();
+    handle.add_class::<score::HighScores>();
     handle.add_class::<map::Map>();
+    handle.add_class::<level::LevelEditor>();
+    handle.add_class::<camera::CameraController>();
     handle.add_tool_class::<obstacle::Obstacle>();
+    handle.add_tool_class::<terrain::TileSetMaker>();
     handle.add_class::<bullet::Bullet>();
+    handle.add_class::<pickup::Pickup>();
     handle.add_class::<player::Player>();
     handle.add_class::<enemies::EnemyTank>();
     handle.add_class::<enemies::GunTurret>();
     handle.add_class::<ui::Hud>();
+    handle.add_class::<ui::Minimap>();
     handle.add_class::<ui::UnitDisplay>();
 }
 
-godot_init!(init);
+/// Releases what the classes keep outside of their instances, while the
+/// engine is still running. The tool classes fill these in the editor too.
+fn terminate(_info: &gdnative::TerminateInfo) {
+    obstacle::clear_shape_cache();
+}
+
+godot_gdnative_init!();
+godot_nativescript_init!(init);
+godot_gdnative_terminate!(terminate);
@@ -2,84 +2,392 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
-use std::borrow::Borrow;
-
-use gdnative::api::{Camera2D, TileMap};
+use gdnative::api::{Camera2D, InputEvent, TileMap, OS};
 use gdnative::prelude::*;
 
 use crate::bullet::Bullet;
+use crate::camera::CameraController;
+use crate::events::{self, LootDropped, MapStarted, TankDestroyed, TankFired};
+use crate::level::{self, Level, LevelEditor, ACTION_LEVEL_EDITOR};
+use crate::mapgen::{self, GeneratorSettings};
+use crate::obstacle;
+use crate::pickup::{Pickup, PickupType, RES_PICKUP_SCENE};
+use crate::player;
+use crate::resources;
+use crate::scenes::maps::level_editor_tscn;
+use crate::score;
+use crate::terrain;
+use crate::units;
 use crate::utils::node::NodeRef;
-use crate::utils::preload::*;
 use crate::utils::*;
 
+pub const RES_CURSOR_TEXTURE: &str = "res://ui/crossair_black.png";
+
+pub const CAMERA_NODE: &str = "Player/Camera2D";
+
+/// Where the level editor saves levels of maps without a level file.
+pub const CUSTOM_LEVEL_FILE: &str = "user://custom.level";
+
 #[derive(NativeClass)]
 #[inherit(Node2D)]
+#[register_with(Self::register)]
 pub struct Map {
+    /// Path of a level file, e.g. `res://maps/Map01.level`, that replaces the
+    /// ground, obstacles, enemies and pickups when the map is ready.
+    #[property]
+    level_file: String,
+    /// Replaces the ground, obstacles, enemies and pickups with a generated
+    /// map when the map is ready.
+    #[property(default = false)]
+    procedural: bool,
+    /// Seed of the generated map, zero picks a random seed.
+    #[property(default = 0)]
+    seed: i64,
+    generator: GeneratorSettings,
+    level: Option<Level>,
+    editor: Option<Ref<Node2D>>,
+
     camera_node: NodeRef<Camera2D>,
     ground_node: NodeRef<TileMap>,
 }
 
 #[methods]
 impl Map {
+    fn register(builder: &ClassBuilder<Self>) {
+        GeneratorSettings::register_group(
+            builder,
+            "generator_",
+            &GeneratorSettings::default(),
+            |m: &Self| &m.generator,
+            |m: &mut Self| &mut m.generator,
+        );
+    }
+
     fn new(_owner: TRef<Node2D>) -> Self {
         Map {
-            camera_node: NodeRef::new("Player/Camera2D"),
-            ground_node: NodeRef::new("Ground"),
+            level_file: String::new(),
+            procedural: false,
+            seed: 0,
+            generator: GeneratorSettings::default(),
+            level: None,
+            editor: None,
+
+            camera_node: NodeRef::new(CAMERA_NODE),
+            ground_node: NodeRef::new(terrain::GROUND_NODE),
         }
     }
 
-    #[inline]
-    pub fn set_camera_limits(&mut self) {
-        let player_camera = self.camera_node.get_ref();
-        let ground = self.ground_node.get_ref();
+    /// The used area of the ground, in global coordinates.
+    pub fn ground_rect(&self) -> NodeResult<Rect2> {
+        let ground = self.ground_node.try_get_ref()?;
 
         let map_limits = ground.get_used_rect();
         let map_cellsize = ground.cell_size();
+        Ok(Rect2::new(
+            Point2::new(
+                map_limits.min_x() * map_cellsize.x,
+                map_limits.min_y() * map_cellsize.y,
+            ),
+            Size2::new(
+                map_limits.width() * map_cellsize.x,
+                map_limits.height() * map_cellsize.y,
+            ),
+        ))
+    }
+
+    /// Limits the player's camera to the used area of the ground. Call it
+    /// again when the map changes, a `CameraController` then moves to the new
+    /// limits smoothly.
+    #[inline]
+    pub fn set_camera_limits(&mut self) -> NodeResult<()> {
+        let player_camera = self.camera_node.try_get_ref()?;
+        let limits = self.ground_rect()?;
+
+        if let Ok(controller) =
+            CameraController::try_instance_from(player_camera.upcast::<Node>().claim())
+        {
+            if controller
+                .map_mut(|controller, camera| controller.set_limits(camera, limits))
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+
+        player_camera.set_limit(0, limits.min_x() as i64);
+        player_camera.set_limit(2, limits.max_x() as i64);
+        player_camera.set_limit(1, limits.min_y() as i64);
+        player_camera.set_limit(3, limits.max_y() as i64);
+        Ok(())
+    }
+
+    /// Generates a map from the seed, or from a random seed, and replaces
+    /// the contents of the map with it.
+    pub fn generate(&mut self, owner: TRef<Node2D>) {
+        let seed = if self.seed != 0 {
+            self.seed as u64
+        } else {
+            OS::godot_singleton().get_ticks_usec() as u64
+                ^ OS::godot_singleton().get_unix_time() as u64
+        };
+
+        godot_print!("Generating map with seed {}", seed);
+        let map = mapgen::generate(seed, &self.generator, obstacle::atlas());
+        self.load_level(owner, &map.to_level());
+    }
 
-        player_camera.set_limit(0, (map_limits.min_x() * map_cellsize.x) as i64);
-        player_camera.set_limit(2, (map_limits.max_x() * map_cellsize.x) as i64);
-        player_camera.set_limit(1, (map_limits.min_y() * map_cellsize.y) as i64);
-        player_camera.set_limit(3, (map_limits.max_y() * map_cellsize.y) as i64);
+    /// Replaces the contents of the map with `level`.
+    pub fn load_level(&mut self, owner: TRef<Node2D>, level: &Level) {
+        self.level = Some(level.clone());
+        report_error(level::build_level(owner, level));
     }
 
+    /// Limits the units and the player's camera to the used area of the
+    /// ground.
+    fn update_bounds(&mut self, owner: TRef<Node2D>) {
+        let node = owner.upcast::<Node>().as_ref();
+        let camera = report_error(self.camera_node.try_get_from(node));
+        let ground = report_error(self.ground_node.try_get_from(node));
+        if ground.is_some() {
+            if let Some(rect) = report_error(self.ground_rect()) {
+                units::set_bounds(node, rect);
+            }
+            if camera.is_some() {
+                report_error(self.set_camera_limits());
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_editor_mode(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    /// Pauses the game and edits the level of the map, or stops editing and
+    /// restarts the map with the edited level.
     #[export]
-    fn _ready(&mut self, owner: &Node2D) {
-        self.camera_node.get_from(owner);
-        self.ground_node.get_from(owner);
-        self.set_camera_limits();
-
-        Input::godot_singleton().set_custom_mouse_cursor(
-            preload::<Texture>("res://ui/crossair_black.png"),
-            Input::CURSOR_ARROW,
-            Vector2::new(16.0, 16.0),
-        )
+    pub fn set_editor_mode(&mut self, owner: TRef<Node2D>, enabled: bool) {
+        if enabled == self.is_editor_mode() {
+            return;
+        }
+
+        let tree = owner.get_tree().map(|tree| unsafe { tree.assume_safe() });
+        let camera = self.camera_node.try_get_ref().ok().and_then(|camera| {
+            CameraController::try_instance_from(camera.upcast::<Node>().claim()).ok()
+        });
+
+        if enabled {
+            let level = match self.level.clone() {
+                Some(level) => level,
+                None => match report_error(level::capture_level(owner)) {
+                    Some(level) => level,
+                    None => return,
+                },
+            };
+            let node = match report_error(
+                resources::try_load::<PackedScene>(owner.as_ref(), level_editor_tscn::RES_PATH)
+                    .and_then(|scene| {
+                        try_instance_scene(scene, PackedScene::GEN_EDIT_STATE_DISABLED)
+                    }),
+            ) {
+                Some(node) => node,
+                None => return,
+            };
+            owner.add_child(node, false);
+
+            let editor = match report_error(LevelEditor::try_instance_from(node)) {
+                Some(editor) => editor,
+                None => {
+                    unsafe { node.assume_safe() }.queue_free();
+                    return;
+                }
+            };
+            let save_path = if self.level_file.is_empty() {
+                CUSTOM_LEVEL_FILE
+            } else {
+                self.level_file.as_str()
+            };
+            let _ = editor.map_mut(|editor, node| editor.start(node, owner, level, save_path));
+            self.editor = Some(editor.base().claim());
+
+            if let Some(tree) = tree {
+                tree.set_pause(true);
+            }
+            if let Some(camera) = camera {
+                let _ = camera.map_mut(|camera, node| {
+                    node.set_pause_mode(Node::PAUSE_MODE_PROCESS);
+                    camera.set_spectator(node, true);
+                });
+            }
+        } else {
+            let node = match self.editor.take() {
+                Some(node) => unsafe { node.assume_safe() },
+                None => return,
+            };
+            let level = LevelEditor::try_instance_from(node.upcast::<Node>().claim())
+                .ok()
+                .and_then(|editor| editor.map_mut(|editor, _| editor.finish()).ok());
+            owner.remove_child(node);
+            node.queue_free();
+
+            if let Some(tree) = tree {
+                tree.set_pause(false);
+            }
+            if let Some(camera) = camera {
+                let _ = camera.map_mut(|camera, node| {
+                    node.set_pause_mode(Node::PAUSE_MODE_INHERIT);
+                    camera.set_spectator(node, false);
+                });
+            }
+            if let Some(level) = level {
+                self.load_level(owner, &level);
+                self.update_bounds(owner);
+            }
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: TRef<Node2D>) {
+        if self.procedural {
+            self.generate(owner);
+        } else if !self.level_file.is_empty() {
+            match level::read_level(self.level_file.as_str()) {
+                Ok(level) => self.load_level(owner, &level),
+                Err(err) => godot_error!("{}", err),
+            }
+        }
+
+        self.update_bounds(owner);
+
+        let node = owner.upcast::<Node>().as_ref();
+        events::subscribe::<TankFired, _>(node, owner, "_on_Events_tank_fired", 0);
+        events::subscribe::<LootDropped, _>(
+            node,
+            owner,
+            "_on_Events_loot_dropped",
+            Object::CONNECT_DEFERRED,
+        );
+        events::subscribe::<TankDestroyed, _>(node, owner, "_on_Events_tank_destroyed", 0);
+        events::publish(
+            node,
+            MapStarted {
+                map: score::map_name(owner.filename().to_string().as_str())
+                    .unwrap_or_else(|| owner.name().to_string()),
+                player: owner.get_node_or_null(player::NAME).map_or(0, |player| {
+                    unsafe { player.assume_safe() }.get_instance_id()
+                }),
+            },
+        );
+
+        if let Some(cursor) = report_error(resources::try_load::<Texture>(
+            owner.as_ref(),
+            RES_CURSOR_TEXTURE,
+        )) {
+            Input::godot_singleton().set_custom_mouse_cursor(
+                cursor,
+                Input::CURSOR_ARROW,
+                Vector2::new(16.0, 16.0),
+            )
+        }
+    }
+
+    #[export]
+    fn _unhandled_input(&mut self, owner: TRef<Node2D>, event: Ref<InputEvent>) {
+        // while editing the map is paused, the editor switches back
+        let event = unsafe { event.assume_safe() };
+        if event.is_action_pressed(ACTION_LEVEL_EDITOR, false) {
+            self.set_editor_mode(owner, true);
+            if let Some(tree) = owner.get_tree() {
+                unsafe { tree.assume_safe() }.set_input_as_handled();
+            }
+        }
+    }
+
+    #[export]
+    fn _on_wave_timeout(&self, owner: TRef<Node2D>, index: i64) {
+        let wave = self
+            .level
+            .as_ref()
+            .and_then(|level| level.waves.get(index as usize));
+        if let Some(wave) = wave {
+            report_error(level::spawn_wave(owner, wave));
+        }
     }
 
     #[allow(non_snake_case)]
     #[export]
-    fn _on_Tank_shoot(
-        &self,
-        owner: TRef<Node2D>,
-        bullet_scene: Ref<PackedScene, Shared>,
-        position: Vector2,
-        direction: Vector2,
-    ) {
-        let bullet_node = instance_scene(bullet_scene, PackedScene::GEN_EDIT_STATE_DISABLED);
+    fn _on_Events_tank_fired(&self, owner: TRef<Node2D>, event: TankFired) {
+        let bullet_node = match report_error(try_instance_scene(
+            event.bullet.clone(),
+            PackedScene::GEN_EDIT_STATE_DISABLED,
+        )) {
+            Some(bullet_node) => bullet_node,
+            None => return,
+        };
         owner.add_child(bullet_node, false);
 
-        Bullet::instance_from(bullet_node)
-            .borrow()
-            .map_mut(|bullet, node| bullet.start(node, position, direction))
-            .expect("Failed to start bullet");
+        let bullet = match report_error(Bullet::try_instance_from(bullet_node)) {
+            Some(bullet) => bullet,
+            None => {
+                unsafe { bullet_node.assume_safe() }.queue_free();
+                return;
+            }
+        };
+        if let Err(err) = bullet
+            .map_mut(|bullet, node| bullet.start(node, event.position, event.direction, event.tank))
+        {
+            godot_error!("Failed to start bullet: {:?}", err);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_loot_dropped(&self, owner: TRef<Node2D>, event: LootDropped) {
+        let typ = match event.pickup_type.parse::<PickupType>() {
+            Ok(typ) => typ,
+            Err(err) => {
+                godot_warn!("{}", err);
+                return;
+            }
+        };
+
+        let pickup_node = match report_error(
+            resources::try_load::<PackedScene>(owner.as_ref(), RES_PICKUP_SCENE)
+                .and_then(|scene| try_instance_scene(scene, PackedScene::GEN_EDIT_STATE_DISABLED)),
+        ) {
+            Some(pickup_node) => pickup_node,
+            None => return,
+        };
+        owner.add_child(pickup_node, false);
+
+        let pickup = match report_error(Pickup::try_instance_from(pickup_node)) {
+            Some(pickup) => pickup,
+            None => {
+                unsafe { pickup_node.assume_safe() }.queue_free();
+                return;
+            }
+        };
+        if let Err(err) = pickup.map_mut(|pickup, node| {
+            pickup.set_type(node, typ);
+            node.set_global_position(event.position);
+        }) {
+            godot_error!("Failed to place pickup: {:?}", err);
+        }
     }
 
     #[allow(non_snake_case)]
     #[export]
-    fn _on_Player_dead(&self, owner: TRef<Node2D>) {
-        owner
-            .get_tree()
-            .map(|tree| unsafe { tree.assume_safe() })
-            .and_then(|tree| tree.reload_current_scene().ok())
-            .expect("Failed to reload");
+    fn _on_Events_tank_destroyed(&self, owner: TRef<Node2D>, event: TankDestroyed) {
+        if !event.kind.is_player() {
+            return;
+        }
+
+        let tree = match owner.get_tree() {
+            Some(tree) => unsafe { tree.assume_safe() },
+            None => return,
+        };
+        if let Err(err) = tree.reload_current_scene() {
+            godot_error!("Failed to reload the map: {:?}", err);
+        }
     }
 }
@@ -0,0 +1,791 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Seeded generation of maps. The generator does not use the engine, so the
+//! same seed gives the same map in the game and in headless checks, see the
+//! `generate_map` binary.
+
+use std::collections::VecDeque;
+
+use gdnative::prelude::*;
+
+use crate::level::{Level, LevelObstacle, LevelPath, LevelPickup, PathPoint, Placement, Tiles};
+use crate::obstacle::ObstacleAtlas;
+use crate::pickup::PickupType;
+use crate::utils::{ExportGroup, Rng};
+
+/// Ids of the tiles in `terrain_tiles.tres` that the generator uses.
+pub const TILE_GRASS: i64 = 0;
+pub const TILE_ROAD_VERTICAL: i64 = 1;
+pub const TILE_ROAD_HORIZONTAL: i64 = 2;
+pub const TILE_ROAD_CROSSING: i64 = 12;
+pub const TILE_SAND: i64 = 20;
+
+/// Obstacles that are placed on the open field.
+const FIELD_OBSTACLES: &[&str] = &[
+    "treeBrown_large",
+    "treeBrown_small",
+    "treeGreen_large",
+    "treeGreen_small",
+];
+
+/// Obstacles that are placed along the roads.
+const ROADSIDE_OBSTACLES: &[&str] = &[
+    "barrelBlack_top",
+    "barrelGreen_top",
+    "barrelRed_top",
+    "barrelRust_top",
+    "barricadeMetal",
+    "barricadeWood",
+    "sandbagBeige",
+    "sandbagBrown",
+];
+
+/// How many times the generator tries to place each obstacle.
+const PLACEMENT_ATTEMPTS: u32 = 20;
+
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct GeneratorSettings {
+    /// Width of the map in tiles.
+    #[export(range(8.0, 128.0, 1.0))]
+    pub width: u32,
+    /// Height of the map in tiles.
+    #[export(range(8.0, 128.0, 1.0))]
+    pub height: u32,
+    #[export]
+    pub tile_size: f32,
+    /// Roads that cross the map from left to right, at least two.
+    #[export(range(2.0, 8.0, 1.0))]
+    pub horizontal_roads: u32,
+    /// Roads that cross the map from top to bottom, at least two.
+    #[export(range(2.0, 8.0, 1.0))]
+    pub vertical_roads: u32,
+    #[export]
+    pub sand_patches: u32,
+    /// Trees placed on the open field.
+    #[export]
+    pub field_obstacles: u32,
+    /// Barrels, barricades and sandbags placed along the roads.
+    #[export]
+    pub roadside_obstacles: u32,
+    /// Minimum distance between the centers of two obstacles.
+    #[export]
+    pub obstacle_spacing: f32,
+    /// Enemy tanks that patrol the roads.
+    #[export]
+    pub patrols: u32,
+    #[export]
+    pub turrets: u32,
+    /// Minimum distance between a turret and the player spawn.
+    #[export]
+    pub turret_distance: f32,
+    /// Places the player has to reach, there always is a path to them.
+    #[export]
+    pub objectives: u32,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            width: 40,
+            height: 24,
+            tile_size: 128.0,
+            horizontal_roads: 3,
+            vertical_roads: 4,
+            sand_patches: 4,
+            field_obstacles: 24,
+            roadside_obstacles: 16,
+            obstacle_spacing: 160.0,
+            patrols: 2,
+            turrets: 2,
+            turret_distance: 1200.0,
+            objectives: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlacedObstacle {
+    pub type_name: String,
+    pub position: Vector2,
+    pub extents: Vector2,
+}
+
+/// The result of the generator, in the coordinates of the map scene.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedMap {
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: f32,
+    /// Tile ids, row by row.
+    pub tiles: Vec<i64>,
+    pub player_spawn: Vector2,
+    pub objectives: Vec<Vector2>,
+    pub obstacles: Vec<PlacedObstacle>,
+    /// Closed loops along the roads, one per patrolling enemy.
+    pub patrols: Vec<Vec<Vector2>>,
+    pub turrets: Vec<Vector2>,
+
+    /// Tiles that cannot be driven through.
+    blocked: Vec<bool>,
+    /// Tiles that must stay free of obstacles.
+    reserved: Vec<bool>,
+}
+
+impl GeneratedMap {
+    fn new(seed: u64, settings: &GeneratorSettings) -> Self {
+        let width = settings.width.max(8);
+        let height = settings.height.max(8);
+        let tiles = width as usize * height as usize;
+
+        GeneratedMap {
+            seed,
+            width,
+            height,
+            tile_size: settings.tile_size.max(1.0),
+            tiles: vec![TILE_GRASS; tiles],
+            player_spawn: Vector2::zero(),
+            objectives: Vec::new(),
+            obstacles: Vec::new(),
+            patrols: Vec::new(),
+            turrets: Vec::new(),
+
+            blocked: vec![false; tiles],
+            reserved: vec![false; tiles],
+        }
+    }
+
+    #[inline]
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    #[inline]
+    pub fn tile(&self, x: u32, y: u32) -> i64 {
+        self.tiles[self.index(x, y)]
+    }
+
+    #[inline]
+    fn set_tile(&mut self, x: u32, y: u32, tile: i64) {
+        let index = self.index(x, y);
+        self.tiles[index] = tile;
+    }
+
+    #[inline]
+    pub fn is_road(&self, x: u32, y: u32) -> bool {
+        matches!(
+            self.tile(x, y),
+            TILE_ROAD_VERTICAL | TILE_ROAD_HORIZONTAL | TILE_ROAD_CROSSING
+        )
+    }
+
+    #[inline]
+    pub fn is_blocked(&self, x: u32, y: u32) -> bool {
+        self.blocked[self.index(x, y)]
+    }
+
+    #[inline]
+    pub fn bounds(&self) -> Rect2 {
+        Rect2::new(
+            Point2::zero(),
+            Size2::new(
+                self.width as f32 * self.tile_size,
+                self.height as f32 * self.tile_size,
+            ),
+        )
+    }
+
+    /// The tile at `position`.
+    #[inline]
+    pub fn cell_at(&self, position: Vector2) -> Option<(u32, u32)> {
+        if position.x < 0.0 || position.y < 0.0 {
+            return None;
+        }
+
+        let (x, y) = (
+            (position.x / self.tile_size) as u32,
+            (position.y / self.tile_size) as u32,
+        );
+        if x < self.width && y < self.height {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn cell_center(&self, x: u32, y: u32) -> Vector2 {
+        Vector2::new(x as f32 + 0.5, y as f32 + 0.5) * self.tile_size
+    }
+
+    /// The tiles covered by `rect`.
+    fn cells_in(&self, rect: Rect2) -> impl Iterator<Item = (u32, u32)> {
+        let to_cell =
+            |value: f32, max: u32| ((value / self.tile_size).max(0.0) as u32).min(max - 1);
+        let min_x = to_cell(rect.min_x(), self.width);
+        let min_y = to_cell(rect.min_y(), self.height);
+        let max_x = to_cell(rect.max_x() - 0.01, self.width);
+        let max_y = to_cell(rect.max_y() - 0.01, self.height);
+
+        (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+    }
+
+    /// The shortest path of tiles from `from` to `to` that does not go
+    /// through an obstacle or turret.
+    pub fn find_path(&self, from: Vector2, to: Vector2) -> Option<Vec<(u32, u32)>> {
+        let start = self.cell_at(from)?;
+        let end = self.cell_at(to)?;
+        if self.is_blocked(start.0, start.1) || self.is_blocked(end.0, end.1) {
+            return None;
+        }
+
+        let mut previous: Vec<Option<(u32, u32)>> = vec![None; self.tiles.len()];
+        let mut queue = VecDeque::new();
+        previous[self.index(start.0, start.1)] = Some(start);
+        queue.push_back(start);
+
+        while let Some((x, y)) = queue.pop_front() {
+            if (x, y) == end {
+                let mut path = vec![end];
+                let mut cell = end;
+                while cell != start {
+                    cell = previous[self.index(cell.0, cell.1)]?;
+                    path.push(cell);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbours.iter().copied() {
+                if nx >= self.width || ny >= self.height || self.is_blocked(nx, ny) {
+                    continue;
+                }
+                let index = self.index(nx, ny);
+                if previous[index].is_none() {
+                    previous[index] = Some((x, y));
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The objectives that cannot be reached from the player spawn, which is
+    /// always empty for a generated map.
+    pub fn unreachable_objectives(&self) -> Vec<Vector2> {
+        self.objectives
+            .iter()
+            .filter(|objective| self.find_path(self.player_spawn, **objective).is_none())
+            .copied()
+            .collect()
+    }
+
+    /// The map as a level, with a pickup at each objective and an enemy
+    /// patrolling each patrol loop.
+    pub fn to_level(&self) -> Level {
+        let types = PickupType::all();
+        Level {
+            name: format!("Generated {}", self.seed),
+            tile_size: self.tile_size,
+            tiles: Tiles {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+                cells: self.tiles.clone(),
+            },
+            player: Placement::new(self.player_spawn, 0.0),
+            paths: self
+                .patrols
+                .iter()
+                .map(|points| LevelPath {
+                    position: Vector2::zero(),
+                    points: points.iter().copied().map(PathPoint::new).collect(),
+                    patrols: 1,
+                })
+                .collect(),
+            turrets: self
+                .turrets
+                .iter()
+                .map(|position| Placement::new(*position, 0.0))
+                .collect(),
+            obstacles: self
+                .obstacles
+                .iter()
+                .map(|obstacle| {
+                    LevelObstacle::new(
+                        obstacle.type_name.clone(),
+                        Placement::new(obstacle.position, 0.0),
+                    )
+                })
+                .collect(),
+            pickups: self
+                .objectives
+                .iter()
+                .enumerate()
+                .map(|(i, position)| LevelPickup {
+                    typ: types[i % types.len()],
+                    position: *position,
+                    respawn_time: 0.0,
+                })
+                .collect(),
+            waves: Vec::new(),
+        }
+    }
+
+    /// A hash of the whole map, equal for maps generated with the same seed
+    /// and settings.
+    pub fn digest(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write_u64(self.seed);
+        hash.write_u64(self.width as u64);
+        hash.write_u64(self.height as u64);
+        hash.write_f32(self.tile_size);
+        self.tiles
+            .iter()
+            .for_each(|tile| hash.write_u64(*tile as u64));
+        hash.write_vector(self.player_spawn);
+        self.objectives
+            .iter()
+            .for_each(|pos| hash.write_vector(*pos));
+        for obstacle in self.obstacles.iter() {
+            hash.write_bytes(obstacle.type_name.as_bytes());
+            hash.write_vector(obstacle.position);
+        }
+        for patrol in self.patrols.iter() {
+            hash.write_u64(patrol.len() as u64);
+            patrol.iter().for_each(|pos| hash.write_vector(*pos));
+        }
+        self.turrets.iter().for_each(|pos| hash.write_vector(*pos));
+        hash.finish()
+    }
+}
+
+/// Generates a map from `seed`, with the obstacles of `atlas`. The same seed,
+/// settings and atlas always give the same map.
+pub fn generate(seed: u64, settings: &GeneratorSettings, atlas: &ObstacleAtlas) -> GeneratedMap {
+    let mut generator = Generator {
+        settings,
+        atlas,
+        rng: Rng::new(seed),
+        map: GeneratedMap::new(seed, settings),
+        rows: Vec::new(),
+        columns: Vec::new(),
+    };
+
+    generator.add_sand();
+    generator.add_roads();
+    generator.add_spawn_and_objectives();
+    generator.add_turrets();
+    generator.add_patrols();
+    generator.add_field_obstacles();
+    generator.add_roadside_obstacles();
+    generator.map
+}
+
+struct Generator<'a> {
+    settings: &'a GeneratorSettings,
+    atlas: &'a ObstacleAtlas,
+    rng: Rng,
+    map: GeneratedMap,
+    /// Rows of the horizontal roads.
+    rows: Vec<u32>,
+    /// Columns of the vertical roads.
+    columns: Vec<u32>,
+}
+
+impl<'a> Generator<'a> {
+    fn add_sand(&mut self) {
+        let (width, height) = (self.map.width, self.map.height);
+        for _ in 0..self.settings.sand_patches {
+            let cx = self.rng.range_f32(0.0, width as f32);
+            let cy = self.rng.range_f32(0.0, height as f32);
+            let radius = self.rng.range_f32(1.5, 4.0);
+
+            for y in 0..height {
+                for x in 0..width {
+                    let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                    if dx * dx + dy * dy <= radius * radius {
+                        self.map.set_tile(x, y, TILE_SAND);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lays roads across the whole map. All roads cross each other, so every
+    /// road tile can be reached from every other road tile.
+    fn add_roads(&mut self) {
+        let (width, height) = (self.map.width, self.map.height);
+        self.rows = spread(&mut self.rng, self.settings.horizontal_roads, height);
+        self.columns = spread(&mut self.rng, self.settings.vertical_roads, width);
+
+        for &y in self.rows.iter() {
+            for x in 0..width {
+                self.map.set_tile(x, y, TILE_ROAD_HORIZONTAL);
+            }
+        }
+        for &x in self.columns.iter() {
+            for y in 0..height {
+                let tile = if self.rows.contains(&y) {
+                    TILE_ROAD_CROSSING
+                } else {
+                    TILE_ROAD_VERTICAL
+                };
+                self.map.set_tile(x, y, tile);
+            }
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                if self.map.is_road(x, y) {
+                    let index = self.map.index(x, y);
+                    self.map.reserved[index] = true;
+                }
+            }
+        }
+    }
+
+    fn crossings(&self) -> Vec<(u32, u32)> {
+        self.rows
+            .iter()
+            .flat_map(|&y| self.columns.iter().map(move |&x| (x, y)))
+            .collect()
+    }
+
+    /// Spawns the player at the crossing nearest to a random corner, the
+    /// objectives are at crossings far away from it.
+    fn add_spawn_and_objectives(&mut self) {
+        let bounds = self.map.bounds();
+        let corners = [
+            Vector2::new(bounds.min_x(), bounds.min_y()),
+            Vector2::new(bounds.max_x(), bounds.min_y()),
+            Vector2::new(bounds.min_x(), bounds.max_y()),
+            Vector2::new(bounds.max_x(), bounds.max_y()),
+        ];
+        let corner = *self.rng.pick(&corners).unwrap();
+
+        let mut crossings = self
+            .crossings()
+            .into_iter()
+            .map(|(x, y)| self.map.cell_center(x, y))
+            .collect::<Vec<_>>();
+        crossings.sort_by(|a, b| {
+            a.distance_squared_to(corner)
+                .partial_cmp(&b.distance_squared_to(corner))
+                .unwrap()
+        });
+
+        let spawn = crossings.remove(0);
+        self.map.player_spawn = spawn;
+        self.reserve_around(spawn, 1);
+
+        // the farthest half of the crossings are candidates
+        crossings.reverse();
+        crossings.truncate(crossings.len().div_ceil(2));
+        for _ in 0..self.settings.objectives {
+            match self.rng.take(&mut crossings) {
+                Some(objective) => self.map.objectives.push(objective),
+                None => break,
+            }
+        }
+    }
+
+    /// Places turrets next to crossings, away from the player spawn.
+    fn add_turrets(&mut self) {
+        let mut candidates = Vec::new();
+        for (x, y) in self.crossings() {
+            for (dx, dy) in [(-1i64, -1i64), (1, -1), (-1, 1), (1, 1)] {
+                let (tx, ty) = (x as i64 + dx, y as i64 + dy);
+                if tx < 0 || ty < 0 || tx >= self.map.width as i64 || ty >= self.map.height as i64 {
+                    continue;
+                }
+
+                let (tx, ty) = (tx as u32, ty as u32);
+                let position = self.map.cell_center(tx, ty);
+                if !self.map.reserved[self.map.index(tx, ty)]
+                    && position.distance_to(self.map.player_spawn) >= self.settings.turret_distance
+                {
+                    candidates.push((tx, ty));
+                }
+            }
+        }
+
+        for _ in 0..self.settings.turrets {
+            let (x, y) = match self.rng.take(&mut candidates) {
+                Some(cell) => cell,
+                None => break,
+            };
+            if self.map.reserved[self.map.index(x, y)] {
+                continue;
+            }
+
+            let index = self.map.index(x, y);
+            self.map.reserved[index] = true;
+            self.map.blocked[index] = true;
+            self.map.turrets.push(self.map.cell_center(x, y));
+        }
+    }
+
+    /// Lays out a loop for each patrol around the block between two
+    /// horizontal and two vertical roads.
+    fn add_patrols(&mut self) {
+        for _ in 0..self.settings.patrols {
+            let mut rows = self.rows.clone();
+            let mut columns = self.columns.clone();
+            let (top, bottom) = pick_pair(&mut self.rng, &mut rows);
+            let (left, right) = pick_pair(&mut self.rng, &mut columns);
+
+            let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+            let start = self.rng.range_u32(0, corners.len() as u32) as usize;
+            let mut points = (0..=corners.len())
+                .map(|i| {
+                    let (x, y) = corners[(start + i) % corners.len()];
+                    self.map.cell_center(x, y)
+                })
+                .collect::<Vec<_>>();
+            points.dedup();
+            self.map.patrols.push(points);
+        }
+    }
+
+    fn add_field_obstacles(&mut self) {
+        let bounds = self.map.bounds();
+        for _ in 0..self.settings.field_obstacles {
+            let typ = *self.rng.pick(FIELD_OBSTACLES).unwrap();
+            for _ in 0..PLACEMENT_ATTEMPTS {
+                let position = Vector2::new(
+                    self.rng.range_f32(bounds.min_x(), bounds.max_x()),
+                    self.rng.range_f32(bounds.min_y(), bounds.max_y()),
+                );
+                if self.try_place(typ, position) {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn add_roadside_obstacles(&mut self) {
+        let tile_size = self.map.tile_size;
+        for _ in 0..self.settings.roadside_obstacles {
+            let typ = *self.rng.pick(ROADSIDE_OBSTACLES).unwrap();
+            for _ in 0..PLACEMENT_ATTEMPTS {
+                // a tile next to a random road, close to the side of the road
+                let side = if self.rng.next_f32() < 0.5 { -1.0 } else { 1.0 };
+                let along = self.rng.range_f32(-0.15, 0.15) * tile_size;
+                let position = if self.rng.next_f32() < 0.5 {
+                    let y = *self.rng.pick(&self.rows).unwrap();
+                    let x = self.rng.range_u32(0, self.map.width);
+                    self.map.cell_center(x, y) + Vector2::new(along, side * tile_size * 0.8)
+                } else {
+                    let x = *self.rng.pick(&self.columns).unwrap();
+                    let y = self.rng.range_u32(0, self.map.height);
+                    self.map.cell_center(x, y) + Vector2::new(side * tile_size * 0.8, along)
+                };
+                if self.try_place(typ, position) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Places an obstacle of `typ` at `position` when the atlas defines it, it
+    /// lies within the map, does not cover a reserved tile and is far enough
+    /// from the other obstacles.
+    fn try_place(&mut self, typ: &str, position: Vector2) -> bool {
+        let extents = match self.atlas.get(typ) {
+            Some(obstacle) => obstacle.extents(),
+            None => return false,
+        };
+        let rect = Rect2::new((position - extents).to_point(), (extents * 2.0).to_size());
+        if !self.map.bounds().contains_rect(&rect) {
+            return false;
+        }
+
+        let spacing = self.settings.obstacle_spacing;
+        if self
+            .map
+            .obstacles
+            .iter()
+            .any(|obstacle| obstacle.position.distance_to(position) < spacing)
+        {
+            return false;
+        }
+
+        let cells = self.map.cells_in(rect).collect::<Vec<_>>();
+        if cells
+            .iter()
+            .any(|&(x, y)| self.map.reserved[self.map.index(x, y)])
+        {
+            return false;
+        }
+
+        for (x, y) in cells {
+            let index = self.map.index(x, y);
+            self.map.blocked[index] = true;
+        }
+        self.map.obstacles.push(PlacedObstacle {
+            type_name: typ.to_string(),
+            position,
+            extents,
+        });
+        true
+    }
+
+    /// Keeps the tiles within `radius` tiles of `position` free.
+    fn reserve_around(&mut self, position: Vector2, radius: i64) {
+        let (cx, cy) = match self.map.cell_at(position) {
+            Some(cell) => (cell.0 as i64, cell.1 as i64),
+            None => return,
+        };
+        for y in (cy - radius).max(0)..=(cy + radius).min(self.map.height as i64 - 1) {
+            for x in (cx - radius).max(0)..=(cx + radius).min(self.map.width as i64 - 1) {
+                let index = self.map.index(x as u32, y as u32);
+                self.map.reserved[index] = true;
+            }
+        }
+    }
+}
+
+/// Picks `count` distinct lines between 1 and `size - 1`, spread evenly over
+/// the map, sorted.
+fn spread(rng: &mut Rng, count: u32, size: u32) -> Vec<u32> {
+    let inner = size.saturating_sub(2).max(2);
+    let count = count.max(2).min(inner / 2);
+    let band = inner / count;
+
+    (0..count)
+        .map(|i| {
+            let start = 1 + i * band;
+            rng.range_u32(start, start + band)
+        })
+        .collect()
+}
+
+/// Takes two different lines from `lines`, sorted.
+fn pick_pair(rng: &mut Rng, lines: &mut Vec<u32>) -> (u32, u32) {
+    let a = rng.take(lines).unwrap();
+    let b = rng.take(lines).unwrap_or(a);
+    (a.min(b), a.max(b))
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable between Rust
+/// versions.
+struct Fnv(u64);
+
+impl Fnv {
+    #[inline]
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_f32(&mut self, value: f32) {
+        self.write_bytes(&value.to_bits().to_le_bytes());
+    }
+
+    #[inline]
+    fn write_vector(&mut self, value: Vector2) {
+        self.write_f32(value.x);
+        self.write_f32(value.y);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The obstacles the generator places, with the regions of the game's
+    /// atlas, so the tests do not depend on the Godot project.
+    const ATLAS: &str = "\
+atlas 2
+texture res://obstacles.png
+obstacle barrelBlack_top 220 89 48 48 circle 20
+obstacle barrelGreen_top 220 137 48 48 circle 20
+obstacle barrelRed_top 172 89 48 48 circle 20 explosive
+obstacle barrelRust_top 172 137 48 48 circle 20
+obstacle barricadeMetal 532 130 56 56 concave 0
+obstacle barricadeWood 72 130 56 56 convex 40
+obstacle sandbagBeige 164 282 44 64 convex 0 see_through
+obstacle sandbagBrown 622 278 44 64 convex 0 see_through
+obstacle treeBrown_large 0 654 128 128 circle 0
+obstacle treeBrown_small 694 118 72 72 circle 0
+obstacle treeGreen_large 128 654 128 128 circle 0
+obstacle treeGreen_small 694 190 72 72 circle 0
+";
+
+    const SEEDS: std::ops::Range<u64> = 1..51;
+
+    fn atlas() -> ObstacleAtlas {
+        ATLAS.parse().unwrap()
+    }
+
+    #[test]
+    fn same_seed_gives_same_map() {
+        let atlas = atlas();
+        let settings = GeneratorSettings::default();
+        for seed in SEEDS {
+            let map = generate(seed, &settings, &atlas);
+            let again = generate(seed, &settings, &atlas);
+            assert_eq!(map, again, "seed {}", seed);
+            assert_eq!(map.digest(), again.digest(), "seed {}", seed);
+            assert!(!map.obstacles.is_empty(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_maps() {
+        let atlas = atlas();
+        let settings = GeneratorSettings::default();
+        assert_ne!(
+            generate(1, &settings, &atlas).digest(),
+            generate(2, &settings, &atlas).digest()
+        );
+    }
+
+    #[test]
+    fn objectives_are_reachable() {
+        let atlas = atlas();
+        let settings = GeneratorSettings::default();
+        for seed in SEEDS {
+            let map = generate(seed, &settings, &atlas);
+            assert!(!map.objectives.is_empty(), "seed {}", seed);
+            assert!(map.unreachable_objectives().is_empty(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn map_survives_the_level_format() {
+        let atlas = atlas();
+        let level = generate(7, &GeneratorSettings::default(), &atlas).to_level();
+        assert_eq!(level.to_string().parse::<Level>(), Ok(level.clone()));
+        assert!(level.unknown_obstacles(&atlas).is_empty());
+    }
+}
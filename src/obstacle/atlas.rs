@@ -0,0 +1,399 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! The obstacles that can be placed on a map, cut from a single texture. The
+//! atlas is a versioned text file, like a level file, so obstacles are added
+//! without changing code:
+//!
+//! ```text
+//! atlas 2                        format version, always the first record
+//! texture res://assets/onlyObjects_retina_rotated.png
+//! obstacle barrelRed_top 172 89 48 48 circle 20 explosive
+//! obstacle sandbagBeige 164 282 44 64 rect 0 see_through
+//! category sandbag sandbagBeige  obstacles one of which is picked at random
+//! ```
+//!
+//! An obstacle has a name, the position and size of its region of the
+//! texture, the kind of its collision shape (`rect`, `circle`, `convex` or
+//! `concave`) and its health, zero when it cannot be destroyed. It may be
+//! followed by the flags `explosive`, `shoot_through` and `see_through`. A
+//! category has a name and the obstacles it consists of, which are defined
+//! before it. Categories were added in version 2. Lines starting with `#` are
+//! comments.
+
+use std::str::FromStr;
+
+use gdnative::prelude::*;
+
+use crate::utils::{read_records, RecordError};
+
+/// Version of the atlas files that can be read, older versions can be read
+/// too.
+pub const ATLAS_VERSION: u32 = 2;
+
+/// The kind of collision shape of an obstacle. A rectangle covers the whole
+/// region, the other shapes are traced from the solid pixels of the region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeKind {
+    Rect,
+    Circle,
+    /// The convex hull of the solid pixels.
+    Convex,
+    /// The outline of the solid pixels, for obstacles with openings.
+    Concave,
+}
+
+impl ShapeKind {
+    #[inline]
+    pub fn all() -> Vec<ShapeKind> {
+        vec![Self::Rect, Self::Circle, Self::Convex, Self::Concave]
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rect => "rect",
+            Self::Circle => "circle",
+            Self::Convex => "convex",
+            Self::Concave => "concave",
+        }
+    }
+}
+
+impl FromStr for ShapeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|kind| kind.name().eq(s))
+            .ok_or_else(|| format!("unknown shape `{}`", s))
+    }
+}
+
+/// A kind of obstacle of the atlas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObstacleDef {
+    pub name: String,
+    /// Region of the atlas texture.
+    pub region: Rect2,
+    pub shape: ShapeKind,
+    /// Damage the obstacle takes before it is destroyed, zero when it cannot
+    /// be destroyed.
+    pub health: u32,
+    /// Damages whatever is close when the obstacle is destroyed.
+    pub explosive: bool,
+    pub blocks_bullets: bool,
+    pub blocks_vision: bool,
+}
+
+impl ObstacleDef {
+    #[inline]
+    pub fn extents(&self) -> Vector2 {
+        self.region.size.to_vector() * 0.5
+    }
+
+    #[inline]
+    pub fn is_destructible(&self) -> bool {
+        self.health > 0
+    }
+}
+
+/// A group of obstacles that look alike, e.g. all barrels, of which one can
+/// be picked at random.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObstacleCategory {
+    pub name: String,
+    /// Names of the obstacles of the category.
+    pub obstacles: Vec<String>,
+}
+
+/// The obstacles of an atlas file, in the order of the file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObstacleAtlas {
+    /// Path of the texture the regions of the obstacles are cut from.
+    pub texture: String,
+    pub obstacles: Vec<ObstacleDef>,
+    pub categories: Vec<ObstacleCategory>,
+}
+
+impl ObstacleAtlas {
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&ObstacleDef> {
+        self.obstacles.iter().find(|obstacle| obstacle.name == name)
+    }
+
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    #[inline]
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.obstacles.iter().map(|obstacle| obstacle.name.as_str())
+    }
+
+    #[inline]
+    pub fn category(&self, name: &str) -> Option<&ObstacleCategory> {
+        self.categories
+            .iter()
+            .find(|category| category.name == name)
+    }
+
+    #[inline]
+    pub fn category_names(&self) -> impl Iterator<Item = &str> {
+        self.categories
+            .iter()
+            .map(|category| category.name.as_str())
+    }
+
+    /// Describes each obstacle whose region does not lie inside a texture of
+    /// `size`.
+    pub fn check_regions(&self, size: Vector2) -> Vec<String> {
+        let texture = Rect2::new(Point2::zero(), size.to_size());
+        self.obstacles
+            .iter()
+            .filter(|obstacle| !texture.contains_rect(&obstacle.region))
+            .map(|obstacle| {
+                format!(
+                    "the region of obstacle `{}` lies outside of `{}`, which is {}x{}",
+                    obstacle.name, self.texture, size.x, size.y
+                )
+            })
+            .collect()
+    }
+}
+
+impl FromStr for ObstacleAtlas {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut atlas = ObstacleAtlas::default();
+        let (version, records) = read_records(s, "atlas", ATLAS_VERSION)?;
+
+        for mut record in records {
+            match record.keyword {
+                "texture" => atlas.texture = record.text("texture")?,
+                "obstacle" => {
+                    let name = record.text("name")?;
+                    if atlas.contains(name.as_str()) {
+                        return Err(record.error(format!("obstacle `{}` is defined twice", name)));
+                    }
+                    let region = Rect2::new(
+                        Point2::new(record.number("x")?, record.number("y")?),
+                        Size2::new(record.number("width")?, record.number("height")?),
+                    );
+                    if region.size.width <= 0.0 || region.size.height <= 0.0 {
+                        return Err(
+                            record.error(format!("obstacle `{}` has an empty region", name))
+                        );
+                    }
+
+                    let mut obstacle = ObstacleDef {
+                        name,
+                        region,
+                        shape: record.parse("shape")?,
+                        health: record.number("health")?,
+                        explosive: false,
+                        blocks_bullets: true,
+                        blocks_vision: true,
+                    };
+                    for flag in record.remaining() {
+                        match flag {
+                            "explosive" => obstacle.explosive = true,
+                            "shoot_through" => obstacle.blocks_bullets = false,
+                            "see_through" => obstacle.blocks_vision = false,
+                            _ => return Err(record.error(format!("unknown flag `{}`", flag))),
+                        }
+                    }
+                    atlas.obstacles.push(obstacle);
+                }
+                "category" if version < 2 => {
+                    return Err(record.error(String::from(
+                        "categories are not supported before version 2",
+                    )));
+                }
+                "category" => {
+                    let name = record.text("name")?;
+                    if atlas.category(name.as_str()).is_some() {
+                        return Err(record.error(format!("category `{}` is defined twice", name)));
+                    }
+                    let obstacles = record
+                        .remaining()
+                        .into_iter()
+                        .map(String::from)
+                        .collect::<Vec<_>>();
+                    if obstacles.is_empty() {
+                        return Err(record.error(format!("category `{}` is empty", name)));
+                    }
+                    if let Some(obstacle) = obstacles.iter().find(|o| !atlas.contains(o.as_str())) {
+                        return Err(record.error(format!("unknown obstacle `{}`", obstacle)));
+                    }
+                    atlas.categories.push(ObstacleCategory { name, obstacles });
+                }
+                _ => return Err(record.error(format!("unknown record `{}`", record.keyword))),
+            }
+            record.end()?;
+        }
+
+        if atlas.texture.is_empty() {
+            return Err(RecordError::at_end(
+                s,
+                String::from("the atlas has no texture"),
+            ));
+        }
+        Ok(atlas)
+    }
+}
+
+/// The width and height of a PNG image, from its header.
+pub fn png_size(data: &[u8]) -> Option<Vector2> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if data.len() < 24 || !data.starts_with(SIGNATURE) || &data[12..16] != b"IHDR" {
+        return None;
+    }
+
+    let read = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    Some(Vector2::new(read(16) as f32, read(20) as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ATLAS: &str = "\
+atlas 2
+texture res://obstacles.png
+# barrels
+obstacle barrelRed 172 89 48 48 circle 20 explosive
+obstacle barrelGreen 0 0 48 48 circle 20
+obstacle sandbag 164 282 44 64 rect 0 see_through shoot_through
+category barrel barrelRed barrelGreen
+";
+
+    fn error(text: &str) -> String {
+        text.parse::<ObstacleAtlas>().unwrap_err().message
+    }
+
+    #[test]
+    fn atlas_is_read() {
+        let atlas = ATLAS.parse::<ObstacleAtlas>().unwrap();
+        assert_eq!(atlas.texture, "res://obstacles.png");
+        assert_eq!(
+            atlas.names().collect::<Vec<_>>(),
+            ["barrelRed", "barrelGreen", "sandbag"]
+        );
+
+        let barrel = atlas.get("barrelRed").unwrap();
+        assert_eq!(
+            barrel.region,
+            Rect2::new(Point2::new(172.0, 89.0), Size2::new(48.0, 48.0))
+        );
+        assert_eq!(barrel.shape, ShapeKind::Circle);
+        assert!(barrel.explosive && barrel.blocks_bullets && barrel.blocks_vision);
+        assert!(barrel.is_destructible());
+
+        let sandbag = atlas.get("sandbag").unwrap();
+        assert!(!sandbag.explosive && !sandbag.blocks_bullets && !sandbag.blocks_vision);
+        assert!(!sandbag.is_destructible());
+
+        let category = atlas.category("barrel").unwrap();
+        assert_eq!(category.obstacles, ["barrelRed", "barrelGreen"]);
+    }
+
+    #[test]
+    fn duplicates_are_errors() {
+        let obstacle = format!("{}obstacle sandbag 0 0 1 1 rect 0\n", ATLAS);
+        assert_eq!(error(&obstacle), "obstacle `sandbag` is defined twice");
+        let category = format!("{}category barrel sandbag\n", ATLAS);
+        assert_eq!(error(&category), "category `barrel` is defined twice");
+    }
+
+    #[test]
+    fn obstacles_are_checked() {
+        let obstacle = |line: &str| format!("atlas 2\ntexture t.png\n{}\n", line);
+        assert_eq!(
+            error(&obstacle("obstacle a 0 0 0 10 rect 0")),
+            "obstacle `a` has an empty region"
+        );
+        assert_eq!(
+            error(&obstacle("obstacle a 0 0 10 -1 rect 0")),
+            "obstacle `a` has an empty region"
+        );
+        assert_eq!(
+            error(&obstacle("obstacle a 0 0 10 10 rect 0 bouncy")),
+            "unknown flag `bouncy`"
+        );
+        assert_eq!(
+            error(&obstacle("obstacle a 0 0 10 10 square 0")),
+            "unknown shape `square`"
+        );
+        assert_eq!(error(&obstacle("obstacle a 0 0 10 10")), "missing shape");
+    }
+
+    #[test]
+    fn categories_are_checked() {
+        let category = |line: &str| format!("{}{}\n", ATLAS, line);
+        assert_eq!(
+            error(&category("category empty")),
+            "category `empty` is empty"
+        );
+        assert_eq!(
+            error(&category("category tree treeGreen")),
+            "unknown obstacle `treeGreen`"
+        );
+        // obstacles are defined before the categories they are in
+        let before = ATLAS.replacen(
+            "obstacle sandbag",
+            "category bag sandbag\nobstacle sandbag",
+            1,
+        );
+        assert_eq!(error(&before), "unknown obstacle `sandbag`");
+    }
+
+    #[test]
+    fn categories_need_version_2() {
+        let version_1 = ATLAS.replacen("atlas 2", "atlas 1", 1);
+        let err = version_1.parse::<ObstacleAtlas>().unwrap_err();
+        assert_eq!(err.line, 7);
+        assert_eq!(err.message, "categories are not supported before version 2");
+
+        let without_categories =
+            version_1.replacen("category barrel barrelRed barrelGreen\n", "", 1);
+        let atlas = without_categories.parse::<ObstacleAtlas>().unwrap();
+        assert_eq!(atlas.obstacles.len(), 3);
+        assert!(atlas.categories.is_empty());
+
+        assert!(ATLAS
+            .replacen("atlas 2", "atlas 3", 1)
+            .parse::<ObstacleAtlas>()
+            .is_err());
+    }
+
+    #[test]
+    fn texture_is_required() {
+        let err = "atlas 2\nobstacle a 0 0 1 1 rect 0\n"
+            .parse::<ObstacleAtlas>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.message, "the atlas has no texture");
+    }
+
+    #[test]
+    fn regions_outside_the_texture_are_reported() {
+        let atlas = ATLAS.parse::<ObstacleAtlas>().unwrap();
+        assert!(atlas.check_regions(Vector2::new(256.0, 512.0)).is_empty());
+        assert_eq!(atlas.check_regions(Vector2::new(200.0, 512.0)).len(), 2);
+    }
+
+    #[test]
+    fn png_size_is_read_from_the_header() {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&640u32.to_be_bytes());
+        data.extend_from_slice(&480u32.to_be_bytes());
+        assert_eq!(png_size(&data), Some(Vector2::new(640.0, 480.0)));
+        assert_eq!(png_size(&data[..20]), None);
+        assert_eq!(png_size(b"GIF89a and some more bytes to be long"), None);
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::sync::OnceLock;
+
+use gdnative::api::{Engine, File, RandomNumberGenerator, ResourceLoader, StaticBody2D};
+use gdnative::nativescript::property::{EnumHint, FloatHint, RangeHint, StringHint};
+use gdnative::prelude::*;
+
+pub use atlas::*;
+pub use shape::*;
+pub use variation::*;
+
+use crate::damage::{Damage, DamageType};
+use crate::scenes::environment::obstacle_tscn;
+use crate::tank::{self, GROUP_DAMAGE_TAKER};
+use crate::units::{self, UnitKind};
+use crate::utils::node::try_get_child_as;
+use crate::utils::*;
+
+mod atlas;
+mod shape;
+mod variation;
+
+/// The atlas the obstacles of the game are defined in.
+pub const RES_OBSTACLE_ATLAS: &str = "res://environment/Obstacles.atlas";
+
+/// Obstacles that bullets fly through.
+pub const GROUP_SHOOT_THROUGH: &str = "shoot_through";
+
+/// Distance within which an explosive obstacle damages tanks and other
+/// obstacles when it is destroyed.
+const EXPLOSION_RADIUS: f32 = 160.0;
+const EXPLOSION_DAMAGE: u8 = 40;
+
+static ATLAS: OnceLock<ObstacleAtlas> = OnceLock::new();
+
+/// The obstacle atlas of the game, read from `RES_OBSTACLE_ATLAS` when it is
+/// first used. It is empty when the file cannot be read.
+pub fn atlas() -> &'static ObstacleAtlas {
+    ATLAS.get_or_init(|| {
+        read_atlas(RES_OBSTACLE_ATLAS).unwrap_or_else(|err| {
+            godot_error!("{}", err);
+            ObstacleAtlas::default()
+        })
+    })
+}
+
+/// Reads the atlas file at `path`. Obstacles with a region outside of the
+/// texture of the atlas are left out, also from their categories.
+pub fn read_atlas(path: &str) -> Result<ObstacleAtlas, String> {
+    let file = File::new();
+    file.open(path, File::READ)
+        .map_err(|err| format!("Failed to open `{}`: {:?}", path, err))?;
+    let text = file.get_as_text().to_string();
+    file.close();
+
+    let mut atlas = text
+        .parse::<ObstacleAtlas>()
+        .map_err(|err| format!("Failed to read `{}`, {}", path, err))?;
+
+    let size = load_texture(atlas.texture.as_str())
+        .map(|texture| unsafe { texture.assume_safe() }.get_size())
+        .ok_or_else(|| format!("Failed to load the texture of `{}`", path))?;
+    for err in atlas.check_regions(size) {
+        godot_error!("{}: {}", path, err);
+    }
+    let texture = Rect2::new(Point2::zero(), size.to_size());
+    atlas
+        .obstacles
+        .retain(|obstacle| texture.contains_rect(&obstacle.region));
+    let names = atlas.names().map(String::from).collect::<Vec<_>>();
+    for category in atlas.categories.iter_mut() {
+        category.obstacles.retain(|name| names.contains(name));
+    }
+    atlas
+        .categories
+        .retain(|category| !category.obstacles.is_empty());
+    Ok(atlas)
+}
+
+#[inline]
+fn load_texture(path: &str) -> Option<Ref<Texture>> {
+    ResourceLoader::godot_singleton()
+        .load(path, "Texture", false)
+        .and_then(|resource| resource.cast::<Texture>())
+}
+
+#[derive(NativeClass)]
+#[inherit(StaticBody2D)]
+#[register_with(Self::register)]
+pub struct Obstacle {
+    type_name: String,
+    /// When set, the type of the obstacle is picked at random from this
+    /// category of the atlas instead.
+    category: String,
+    /// How far the traced collision shape may be off from the outline of
+    /// the sprite, in pixels.
+    shape_tolerance: f32,
+    /// Seed of the random category pick and variation. A seed is picked when
+    /// it is zero and either is used, so an obstacle placed in the editor
+    /// keeps its look.
+    seed: i64,
+    variation: Variation,
+    look: Look,
+    def: Option<ObstacleDef>,
+    health: u32,
+    destroyed: bool,
+}
+
+#[methods]
+impl Obstacle {
+    fn register(builder: &ClassBuilder<Self>) {
+        let mut names = vec![String::new()];
+        names.extend(atlas().names().map(String::from));
+
+        builder
+            .add_property::<String>("type_name")
+            .with_hint(StringHint::Enum(EnumHint::new(names)))
+            .with_setter(Self::set_type_name)
+            .with_getter(|t: &Obstacle, _| -> String { t.type_name.clone() })
+            .done();
+
+        let mut categories = vec![String::new()];
+        categories.extend(atlas().category_names().map(String::from));
+
+        builder
+            .add_property::<String>("random_category")
+            .with_hint(StringHint::Enum(EnumHint::new(categories)))
+            .with_setter(Self::set_category)
+            .with_getter(|t: &Obstacle, _| -> String { t.category.clone() })
+            .done();
+
+        builder
+            .add_property::<i64>("random_seed")
+            .with_default(0)
+            .with_setter(|t: &mut Obstacle, owner, seed: i64| {
+                t.seed = seed;
+                t.update(owner);
+            })
+            .with_getter(|t: &Obstacle, _| t.seed)
+            .done();
+
+        builder
+            .add_property::<f32>("shape_tolerance")
+            .with_default(DEFAULT_SHAPE_TOLERANCE)
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 16.0).with_step(0.5)))
+            .with_setter(|t: &mut Obstacle, owner, tolerance: f32| {
+                t.shape_tolerance = tolerance;
+                t.update(owner);
+            })
+            .with_getter(|t: &Obstacle, _| t.shape_tolerance)
+            .done();
+
+        Variation::register_group_with(
+            builder,
+            "random_",
+            &Variation::default(),
+            |t: &Obstacle| &t.variation,
+            |t: &mut Obstacle| &mut t.variation,
+            |t: &mut Obstacle, owner| t.update(owner),
+        );
+    }
+
+    fn new(_owner: TRef<StaticBody2D>) -> Self {
+        Obstacle {
+            type_name: String::new(),
+            category: String::new(),
+            shape_tolerance: DEFAULT_SHAPE_TOLERANCE,
+            seed: 0,
+            variation: Variation::default(),
+            look: Look::default(),
+            def: None,
+            health: 0,
+            destroyed: false,
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: TRef<StaticBody2D>) {
+        if Engine::godot_singleton().is_editor_hint() {
+            return;
+        }
+        let def = match self.def.as_ref() {
+            Some(def) => def,
+            None => return,
+        };
+
+        let kind = if def.blocks_vision {
+            UnitKind::Obstacle
+        } else {
+            UnitKind::Cover
+        };
+        let extents = self.look.extents(def.extents());
+        units::register(owner.upcast::<Node2D>(), kind, extents, 0.0);
+
+        if def.is_destructible() {
+            self.health = def.health;
+            owner.add_to_group(GROUP_DAMAGE_TAKER, false);
+        }
+    }
+
+    /// Applies `damage` when `node` is an obstacle, returns false otherwise.
+    /// An explosive obstacle that is destroyed damages what is close to it.
+    pub fn try_take_damage(node: Ref<Node>, damage: Damage) -> bool {
+        let obstacle = match Self::try_instance_from(node) {
+            Ok(obstacle) => obstacle,
+            Err(_) => return false,
+        };
+
+        // the explosion may damage other obstacles, so it happens after the
+        // obstacle is released
+        let explosion = obstacle
+            .map_mut(|obstacle, owner| obstacle.take_damage(owner, damage))
+            .ok()
+            .flatten();
+        if let Some(position) = explosion {
+            explode(unsafe { node.assume_safe() }, position);
+        }
+        true
+    }
+
+    /// Returns the position of the explosion when the obstacle is destroyed
+    /// and explosive.
+    fn take_damage(&mut self, owner: TRef<StaticBody2D>, damage: Damage) -> Option<Vector2> {
+        if self.destroyed || self.health == 0 {
+            return None;
+        }
+
+        self.health = self.health.saturating_sub(damage.amount as u32);
+        if self.health > 0 {
+            return None;
+        }
+
+        self.destroyed = true;
+        owner.queue_free();
+        self.def
+            .as_ref()
+            .filter(|def| def.explosive)
+            .map(|_| owner.global_position())
+    }
+
+    fn set_type_name(&mut self, owner: TRef<StaticBody2D>, type_name: String) {
+        if !type_name.is_empty() && !atlas().contains(type_name.as_str()) {
+            godot_warn!("Unknown obstacle type `{}`", type_name);
+        }
+        self.type_name = type_name;
+        self.update(owner);
+    }
+
+    fn set_category(&mut self, owner: TRef<StaticBody2D>, category: String) {
+        if !category.is_empty() && atlas().category(category.as_str()).is_none() {
+            godot_warn!("Unknown obstacle category `{}`", category);
+        }
+        self.category = category;
+        self.update(owner);
+    }
+
+    /// Rolls the look of the obstacle and picks its type when it has a
+    /// category. The picked type becomes the type name, so it is what the
+    /// editor saves and levels capture.
+    fn roll(&mut self) {
+        let category = atlas().category(self.category.as_str());
+        if self.seed == 0 && (category.is_some() || self.variation.is_random()) {
+            let rng = RandomNumberGenerator::new();
+            rng.randomize();
+            self.seed = 1 + rng.randi();
+        }
+
+        // a variation that is not random, like a fixed scale, applies
+        // without a seed as well
+        self.look = self.variation.roll(self.seed);
+        if let Some(name) = category.and_then(|category| self.look.pick(category)) {
+            self.type_name = String::from(name);
+        }
+        self.def = atlas().get(self.type_name.as_str()).cloned();
+    }
+
+    /// Updates the sprite and the collision shape to the type and look of
+    /// the obstacle. This also happens in the editor, as it is a tool class.
+    fn update(&mut self, owner: TRef<StaticBody2D>) {
+        self.roll();
+        let def = match self.def.as_ref() {
+            Some(def) => def,
+            None => return,
+        };
+        let look = self.look;
+
+        let owner = owner.as_ref();
+        let sprite = match report_error(try_get_child_as::<obstacle_tscn::SpriteNode>(owner)) {
+            Some(sprite) => sprite,
+            None => return,
+        };
+        let texture_path = sprite
+            .texture()
+            .map(|texture| unsafe { texture.assume_safe() }.path().to_string());
+        if texture_path.as_deref() != Some(atlas().texture.as_str()) {
+            if let Some(texture) = load_texture(atlas().texture.as_str()) {
+                sprite.set_texture(texture);
+            }
+        }
+        sprite.set_region_rect(def.region);
+        sprite.set_rotation(look.rotation as f64);
+        sprite.set_scale(Vector2::new(look.scale.x.abs(), look.scale.y.abs()));
+        sprite.set_flip_h(look.scale.x < 0.0);
+        sprite.set_flip_v(look.scale.y < 0.0);
+        sprite.set_modulate(look.tint);
+
+        let shape = collision_shape(atlas().texture.as_str(), def, self.shape_tolerance);
+        let collision = match report_error(try_get_child_as::<obstacle_tscn::CollisionShape2DNode>(
+            owner,
+        )) {
+            Some(collision) => collision,
+            None => return,
+        };
+        collision.set_rotation(look.rotation as f64);
+        collision.set_shape(scaled_shape(&shape, look.scale));
+
+        if def.blocks_bullets {
+            owner.remove_from_group(GROUP_SHOOT_THROUGH);
+        } else {
+            owner.add_to_group(GROUP_SHOOT_THROUGH, false);
+        }
+    }
+}
+
+impl InstanceFrom<Self, StaticBody2D> for Obstacle {}
+
+/// Damages the tanks and obstacles within `EXPLOSION_RADIUS` of `position`.
+fn explode(node: TRef<Node>, position: Vector2) {
+    let id = node.get_instance_id();
+    let targets = units::with_registry(node.as_ref(), |registry| {
+        registry
+            .iter()
+            .filter(|unit| unit.id != id && unit.kind != UnitKind::Pickup)
+            .filter(|unit| unit.position().distance_to(position) <= EXPLOSION_RADIUS)
+            .map(|unit| unit.node)
+            .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+
+    for target in targets {
+        tank::take_damage(
+            unsafe { target.assume_safe() }.upcast::<Node>().claim(),
+            Damage::new(EXPLOSION_DAMAGE, DamageType::Fire),
+        );
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Collision shapes of obstacles, traced from the alpha channel of their
+//! region of the atlas texture.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use gdnative::api::{
+    BitMap, CircleShape2D, ConcavePolygonShape2D, ConvexPolygonShape2D, Geometry, RectangleShape2D,
+    Shape2D,
+};
+use gdnative::prelude::*;
+
+use super::{load_texture, ObstacleDef, ShapeKind};
+
+/// Tolerance of the traced outlines, in pixels, when none is set.
+pub const DEFAULT_SHAPE_TOLERANCE: f32 = 2.0;
+
+/// Alpha above which a pixel of the texture is solid.
+const ALPHA_THRESHOLD: f64 = 0.1;
+
+thread_local! {
+    /// Shapes by obstacle type and tolerance, shared by all obstacles of the
+    /// same type.
+    static SHAPES: RefCell<HashMap<(String, u32), Ref<Shape2D>>> = RefCell::new(HashMap::new());
+}
+
+/// The collision shape of obstacles of type `def`, cut from `texture`. Round
+/// types get a circle that covers their solid pixels, polygon types an
+/// outline of those pixels within `tolerance` pixels. A rectangle that covers
+/// the region is used when the texture cannot be traced.
+pub fn collision_shape(texture: &str, def: &ObstacleDef, tolerance: f32) -> Ref<Shape2D> {
+    let key = (def.name.clone(), tolerance.to_bits());
+    if let Some(shape) = SHAPES.with(|shapes| shapes.borrow().get(&key).cloned()) {
+        return shape;
+    }
+
+    let shape = new_shape(texture, def, tolerance);
+    SHAPES.with(|shapes| shapes.borrow_mut().insert(key, shape.clone()));
+    shape
+}
+
+/// Releases the shared collision shapes, which has to happen while the
+/// engine is still running. Called when the library is unloaded.
+pub fn clear_shape_cache() {
+    SHAPES.with(|shapes| shapes.borrow_mut().clear());
+}
+
+fn new_shape(texture: &str, def: &ObstacleDef, tolerance: f32) -> Ref<Shape2D> {
+    let extents = def.extents();
+    let outlines = match def.shape {
+        ShapeKind::Rect => Vec::new(),
+        _ => trace_outlines(texture, def, tolerance).unwrap_or_else(|| {
+            godot_warn!("Failed to trace the outline of obstacle `{}`", def.name);
+            Vec::new()
+        }),
+    };
+
+    match def.shape {
+        ShapeKind::Circle if !outlines.is_empty() => {
+            let radius = outlines
+                .iter()
+                .flatten()
+                .map(|point| point.length())
+                .fold(0.0, f32::max);
+
+            let shape = CircleShape2D::new();
+            shape.set_radius(radius as f64);
+            shape.upcast::<Shape2D>().into_shared()
+        }
+        ShapeKind::Convex if !outlines.is_empty() => {
+            let points = outlines.iter().flatten().copied().collect::<Vec<_>>();
+            let hull = Geometry::godot_singleton().convex_hull_2d(Vector2Array::from_vec(points));
+
+            let shape = ConvexPolygonShape2D::new();
+            shape.set_points(hull);
+            shape.upcast::<Shape2D>().into_shared()
+        }
+        ShapeKind::Concave if !outlines.is_empty() => {
+            // the largest outline, smaller ones are usually loose pixels
+            let outline = outlines
+                .iter()
+                .max_by(|a, b| area(a).partial_cmp(&area(b)).unwrap())
+                .unwrap();
+            let mut segments = Vector2Array::new();
+            for (i, point) in outline.iter().enumerate() {
+                segments.push(*point);
+                segments.push(outline[(i + 1) % outline.len()]);
+            }
+
+            let shape = ConcavePolygonShape2D::new();
+            shape.set_segments(segments);
+            shape.upcast::<Shape2D>().into_shared()
+        }
+        _ => {
+            let shape = RectangleShape2D::new();
+            shape.set_extents(extents);
+            shape.upcast::<Shape2D>().into_shared()
+        }
+    }
+}
+
+/// A copy of `shape` scaled by `scale`, which is negative along the axes it
+/// is flipped on. Collision shapes do not support negative scales, so the
+/// scale is applied to the points of the shape. `shape` itself is returned
+/// when it is not scaled.
+pub fn scaled_shape(shape: &Ref<Shape2D>, scale: Vector2) -> Ref<Shape2D> {
+    if scale == Vector2::new(1.0, 1.0) {
+        return shape.clone();
+    }
+
+    let shape = unsafe { shape.assume_safe() };
+    let abs = Vector2::new(scale.x.abs(), scale.y.abs());
+    let scale_points = |points: Vector2Array| {
+        Vector2Array::from_vec(
+            points
+                .read()
+                .iter()
+                .map(|point| Vector2::new(point.x * scale.x, point.y * scale.y))
+                .collect(),
+        )
+    };
+
+    if let Some(circle) = shape.cast::<CircleShape2D>() {
+        let scaled = CircleShape2D::new();
+        scaled.set_radius(circle.radius() * abs.x.max(abs.y) as f64);
+        scaled.upcast::<Shape2D>().into_shared()
+    } else if let Some(polygon) = shape.cast::<ConvexPolygonShape2D>() {
+        let mut points = scale_points(polygon.points()).read().to_vec();
+        if scale.x * scale.y < 0.0 {
+            // a flip turns the winding of the points around
+            points.reverse();
+        }
+        let scaled = ConvexPolygonShape2D::new();
+        scaled.set_points(Vector2Array::from_vec(points));
+        scaled.upcast::<Shape2D>().into_shared()
+    } else if let Some(polygon) = shape.cast::<ConcavePolygonShape2D>() {
+        let scaled = ConcavePolygonShape2D::new();
+        scaled.set_segments(scale_points(polygon.segments()));
+        scaled.upcast::<Shape2D>().into_shared()
+    } else if let Some(rect) = shape.cast::<RectangleShape2D>() {
+        let extents = rect.extents();
+        let scaled = RectangleShape2D::new();
+        scaled.set_extents(Vector2::new(extents.x * abs.x, extents.y * abs.y));
+        scaled.upcast::<Shape2D>().into_shared()
+    } else {
+        shape.claim()
+    }
+}
+
+/// Outlines of the solid pixels of the region of `def`, relative to the
+/// center of the region. Outlines with less than three points are left out.
+fn trace_outlines(texture: &str, def: &ObstacleDef, tolerance: f32) -> Option<Vec<Vec<Vector2>>> {
+    let image =
+        load_texture(texture).and_then(|texture| unsafe { texture.assume_safe() }.get_data())?;
+    let image = unsafe { image.assume_safe() };
+    if image.is_compressed() && image.decompress().is_err() {
+        return None;
+    }
+    let region = image.get_rect(def.region)?;
+
+    let bitmap = BitMap::new();
+    bitmap.create_from_image_alpha(region, ALPHA_THRESHOLD);
+    let polygons = bitmap.opaque_to_polygons(
+        Rect2::new(Point2::zero(), def.region.size),
+        tolerance.max(0.0) as f64,
+    );
+
+    let extents = def.extents();
+    let outlines = polygons
+        .iter()
+        .filter_map(|polygon| polygon.try_to_vector2_array())
+        .map(|polygon| {
+            polygon
+                .read()
+                .iter()
+                .map(|point| *point - extents)
+                .collect::<Vec<_>>()
+        })
+        .filter(|outline| outline.len() >= 3)
+        .collect::<Vec<_>>();
+    if outlines.is_empty() {
+        None
+    } else {
+        Some(outlines)
+    }
+}
+
+/// The area of `outline`, regardless of its winding.
+fn area(outline: &[Vector2]) -> f32 {
+    let sum = outline
+        .iter()
+        .zip(outline.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f32>();
+    sum.abs() * 0.5
+}
@@ -0,0 +1,134 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Random variations of obstacles, so a map dressed with the same few
+//! obstacles does not look repetitive. The variation of an obstacle is rolled
+//! from its seed, so it looks the same every time the map is loaded.
+
+use gdnative::prelude::*;
+
+use super::ObstacleCategory;
+use crate::utils::{ExportGroup, Rng};
+
+const WHITE: Color = Color {
+    r: 1.0,
+    g: 1.0,
+    b: 1.0,
+    a: 1.0,
+};
+
+/// The ranges within which an obstacle is varied.
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct Variation {
+    /// Largest rotation to either side, in degrees.
+    #[export(range(0.0, 180.0, 1.0))]
+    pub rotation: f32,
+    #[export(range(0.25, 4.0, 0.05))]
+    pub scale_min: f32,
+    #[export(range(0.25, 4.0, 0.05))]
+    pub scale_max: f32,
+    /// Flips horizontally half of the time.
+    #[export]
+    pub flip_h: bool,
+    /// Flips vertically half of the time.
+    #[export]
+    pub flip_v: bool,
+    /// The tint is a random blend of `tint_min` and `tint_max`.
+    #[export]
+    pub tint_min: Color,
+    #[export]
+    pub tint_max: Color,
+}
+
+impl Default for Variation {
+    fn default() -> Self {
+        Variation {
+            rotation: 0.0,
+            scale_min: 1.0,
+            scale_max: 1.0,
+            flip_h: false,
+            flip_v: false,
+            tint_min: WHITE,
+            tint_max: WHITE,
+        }
+    }
+}
+
+impl Variation {
+    /// Whether obstacles with this variation look different from each other.
+    pub fn is_random(&self) -> bool {
+        self.rotation != 0.0
+            || self.scale_min != self.scale_max
+            || self.flip_h
+            || self.flip_v
+            || self.tint_min != self.tint_max
+    }
+
+    /// Rolls the look of an obstacle from `seed`. Every value is rolled, even
+    /// when its range is empty, so changing one range leaves the others as
+    /// they were.
+    pub fn roll(&self, seed: i64) -> Look {
+        let mut rng = Rng::new(seed as u64);
+        let pick = rng.next_u64();
+        let rotation = rng.range_f32(-self.rotation, self.rotation);
+        let scale = rng.range_f32(self.scale_min, self.scale_max);
+        let flip_h = rng.next_f32() < 0.5 && self.flip_h;
+        let flip_v = rng.next_f32() < 0.5 && self.flip_v;
+        let tint = self.tint_min.lerp(self.tint_max, rng.next_f32());
+
+        Look {
+            pick,
+            rotation: rotation.to_radians(),
+            scale: Vector2::new(
+                if flip_h { -scale } else { scale },
+                if flip_v { -scale } else { scale },
+            ),
+            tint,
+        }
+    }
+}
+
+/// The rolled variation of an obstacle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Look {
+    /// Number to pick the type of the obstacle from its category with.
+    pub pick: u64,
+    /// Rotation in radians.
+    pub rotation: f32,
+    /// Scale, negative along the axes the obstacle is flipped on.
+    pub scale: Vector2,
+    pub tint: Color,
+}
+
+impl Default for Look {
+    fn default() -> Self {
+        Look {
+            pick: 0,
+            rotation: 0.0,
+            scale: Vector2::new(1.0, 1.0),
+            tint: WHITE,
+        }
+    }
+}
+
+impl Look {
+    /// The name of the obstacle of `category` this look picks.
+    #[inline]
+    pub fn pick<'a>(&self, category: &'a ObstacleCategory) -> Option<&'a str> {
+        let len = category.obstacles.len() as u64;
+        if len == 0 {
+            return None;
+        }
+        Some(category.obstacles[(self.pick % len) as usize].as_str())
+    }
+
+    /// The extents of the box around an obstacle with `extents` once it is
+    /// scaled and rotated.
+    pub fn extents(&self, extents: Vector2) -> Vector2 {
+        let x = extents.x * self.scale.x.abs();
+        let y = extents.y * self.scale.y.abs();
+        let (sin, cos) = self.rotation.sin_cos();
+        Vector2::new(x * cos.abs() + y * sin.abs(), x * sin.abs() + y * cos.abs())
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::fmt;
+use std::str::FromStr;
+
+use gdnative::api::Area2D;
+use gdnative::nativescript::property::{EnumHint, StringHint};
+use gdnative::prelude::*;
+
+use crate::scenes::pickups::pickup_tscn;
+use crate::tank;
+use crate::units::{self, UnitKind};
+use crate::utils::node::{try_get_child_as, NodeRef};
+use crate::utils::preload::*;
+use crate::utils::*;
+
+/// Scene of the pickups that are dropped by destroyed tanks.
+pub const RES_PICKUP_SCENE: &str = pickup_tscn::RES_PATH;
+/// Bullets given by weapon pickups that do not have a `weapon_scene` set.
+pub const RES_DEFAULT_WEAPON_SCENE: &str = "res://bullets/PlayerHeavyBullet.tscn";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PickupType {
+    /// Restores `amount` health.
+    Repair,
+    /// Adds `amount` ammo.
+    Ammo,
+    /// Recharges the shield with `amount` points.
+    Shield,
+    /// Applies the `speed_boost` status effect for `duration` seconds.
+    SpeedBoost,
+    /// Applies the `rapid_fire` status effect for `duration` seconds.
+    RapidFire,
+    /// Replaces the tank's bullets with the pickup's weapon.
+    Weapon,
+}
+
+impl PickupType {
+    #[inline]
+    pub fn all() -> Vec<PickupType> {
+        vec![
+            Self::Repair,
+            Self::Ammo,
+            Self::Shield,
+            Self::SpeedBoost,
+            Self::RapidFire,
+            Self::Weapon,
+        ]
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Repair => "repair",
+            Self::Ammo => "ammo",
+            Self::Shield => "shield",
+            Self::SpeedBoost => "speed_boost",
+            Self::RapidFire => "rapid_fire",
+            Self::Weapon => "weapon",
+        }
+    }
+
+    /// Amount used when a pickup does not define one.
+    #[inline]
+    pub fn default_amount(&self) -> f32 {
+        match self {
+            Self::Repair => 25.0,
+            Self::Ammo => 20.0,
+            Self::Shield => 25.0,
+            Self::SpeedBoost => 0.5,
+            Self::RapidFire => 0.5,
+            Self::Weapon => 0.0,
+        }
+    }
+
+    /// Duration used when a pickup does not define one.
+    #[inline]
+    pub fn default_duration(&self) -> f32 {
+        match self {
+            Self::SpeedBoost | Self::RapidFire => 8.0,
+            _ => 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn rect(&self) -> Rect2 {
+        match self {
+            Self::Repair => Rect2::new(Point2::new(220.0, 137.0), Size2::new(48.0, 48.0)),
+            Self::Ammo => Rect2::new(Point2::new(172.0, 137.0), Size2::new(48.0, 48.0)),
+            Self::Shield | Self::Weapon => {
+                Rect2::new(Point2::new(220.0, 89.0), Size2::new(48.0, 48.0))
+            }
+            Self::SpeedBoost | Self::RapidFire => {
+                Rect2::new(Point2::new(172.0, 89.0), Size2::new(48.0, 48.0))
+            }
+        }
+    }
+
+    #[inline]
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Repair | Self::Ammo | Self::SpeedBoost => Color::rgb(1.0, 1.0, 1.0),
+            Self::Shield => Color::rgb(0.6, 0.85, 1.0),
+            Self::RapidFire => Color::rgb(1.0, 0.9, 0.4),
+            Self::Weapon => Color::rgb(1.0, 0.6, 0.3),
+        }
+    }
+}
+
+impl FromStr for PickupType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Self::all()
+            .into_iter()
+            .find(|typ| typ.name().eq(s))
+            .ok_or_else(|| format!("Unknown pickup type `{}`", s))
+    }
+}
+
+/// Weighted chances of a destroyed tank dropping a pickup. Loot tables are
+/// written as `type:weight` pairs, where `none` is the chance of not dropping
+/// anything, e.g. `repair:3,shield:1,none:4`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LootTable {
+    entries: Vec<(Option<PickupType>, u32)>,
+}
+
+impl LootTable {
+    pub fn new() -> Self {
+        LootTable {
+            entries: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn total_weight(&self) -> u32 {
+        self.entries.iter().map(|(_, weight)| weight).sum()
+    }
+
+    /// Picks an entry, where `roll` is a random number in the range
+    /// `0.0..1.0`.
+    pub fn pick(&self, roll: f32) -> Option<PickupType> {
+        let total = self.total_weight();
+        if total == 0 {
+            return None;
+        }
+
+        let mut target = (roll.clamp(0.0, 1.0) * total as f32) as u32;
+        for (typ, weight) in self.entries.iter() {
+            if target < *weight {
+                return *typ;
+            }
+            target -= weight;
+        }
+
+        self.entries.last().and_then(|(typ, _)| *typ)
+    }
+}
+
+impl fmt::Display for LootTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(typ, weight)| format!("{}:{}", typ.map_or("none", |typ| typ.name()), weight))
+            .collect::<Vec<String>>();
+
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+impl FromStr for LootTable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut table = LootTable::new();
+        for part in s.split(',').filter(|part| !part.trim().is_empty()) {
+            let (name, weight) = part
+                .trim()
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid loot table entry `{}`", part))?;
+
+            let typ = match name.trim() {
+                "none" => None,
+                name => Some(name.parse::<PickupType>()?),
+            };
+            let weight = weight
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid weight in loot table entry `{}`", part))?;
+
+            table.entries.push((typ, weight));
+        }
+
+        Ok(table)
+    }
+}
+
+impl ExportValue for LootTable {
+    type Export = String;
+
+    fn to_export(&self) -> String {
+        self.to_string()
+    }
+
+    fn set_export(&mut self, value: String) {
+        *self = value.parse().unwrap_or_else(|err| {
+            godot_warn!("{}", err);
+            LootTable::new()
+        })
+    }
+}
+
+/// What a tank receives when it collects a pickup.
+pub struct PickupEffect {
+    pub typ: PickupType,
+    pub amount: f32,
+    pub duration: f32,
+    pub weapon_scene: Option<Ref<PackedScene>>,
+}
+
+#[derive(NativeClass)]
+#[inherit(Area2D)]
+#[register_with(Self::register)]
+pub struct Pickup {
+    typ: PickupType,
+    /// Amount of the pickup, zero uses the default of its type.
+    #[property(default = 0.0)]
+    amount: f32,
+    /// Duration of timed pickups, zero uses the default of its type.
+    #[property(default = 0.0)]
+    duration: f32,
+    /// Seconds before the pickup appears again after it was collected. When
+    /// zero or less the pickup is removed once collected.
+    #[property(default = 0.0)]
+    respawn_time: f64,
+    #[property]
+    weapon_scene: Option<Ref<PackedScene>>,
+
+    collected: bool,
+
+    // child node(s)
+    respawn_timer_node: NodeRef<Timer>,
+}
+
+#[methods]
+impl Pickup {
+    fn register(builder: &ClassBuilder<Self>) {
+        let names = PickupType::all()
+            .iter()
+            .map(|typ| typ.name().to_string())
+            .collect();
+
+        builder
+            .add_property::<String>("pickup_type")
+            .with_default(PickupType::Repair.name().to_string())
+            .with_hint(StringHint::Enum(EnumHint::new(names)))
+            .with_setter(|t: &mut Pickup, owner, v: String| match v.parse() {
+                Ok(typ) => t.set_type(owner, typ),
+                Err(err) => godot_warn!("{}", err),
+            })
+            .with_getter(|t: &Pickup, _| -> String { t.typ.name().to_string() })
+            .done();
+    }
+
+    fn new(_owner: TRef<Area2D>) -> Self {
+        Pickup {
+            typ: PickupType::Repair,
+            amount: 0.0,
+            duration: 0.0,
+            respawn_time: 0.0,
+            weapon_scene: None,
+
+            collected: false,
+
+            respawn_timer_node: NodeRef::of::<pickup_tscn::RespawnTimerNode>(),
+        }
+    }
+
+    pub fn set_type(&mut self, owner: TRef<Area2D>, typ: PickupType) {
+        self.typ = typ;
+
+        if let Ok(sprite) = try_get_child_as::<pickup_tscn::SpriteNode>(owner.as_ref()) {
+            sprite.set_region_rect(typ.rect());
+            sprite.set_modulate(typ.color());
+        }
+    }
+
+    /// The effect a tank receives when collecting this pickup.
+    pub fn effect(&self) -> PickupEffect {
+        let weapon_scene = match self.typ {
+            PickupType::Weapon => self
+                .weapon_scene
+                .clone()
+                .or_else(|| try_preload::<PackedScene>(RES_DEFAULT_WEAPON_SCENE, "").ok()),
+            _ => None,
+        };
+
+        PickupEffect {
+            typ: self.typ,
+            amount: if self.amount > 0.0 {
+                self.amount
+            } else {
+                self.typ.default_amount()
+            },
+            duration: if self.duration > 0.0 {
+                self.duration
+            } else {
+                self.typ.default_duration()
+            },
+            weapon_scene,
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: TRef<Area2D>) {
+        self.set_type(owner, self.typ);
+        report_error(self.respawn_timer_node.try_get_from(owner.as_ref()));
+        units::register(
+            owner.upcast::<Node2D>(),
+            UnitKind::Pickup,
+            Vector2::zero(),
+            0.0,
+        );
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Pickup_body_entered(&mut self, owner: TRef<Area2D>, body: Ref<Node>) {
+        if self.collected || !tank::collect_pickup(body, &self.effect()) {
+            return;
+        }
+
+        if self.respawn_time <= 0.0 {
+            self.collected = true;
+            owner.queue_free();
+            return;
+        }
+
+        self.set_collected(owner, true);
+        match report_error(self.respawn_timer_node.try_get_ref()) {
+            Some(timer) => timer.start(self.respawn_time),
+            // without a timer the pickup cannot respawn
+            None => owner.queue_free(),
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_RespawnTimer_timeout(&mut self, owner: TRef<Area2D>) {
+        self.set_collected(owner, false);
+    }
+
+    fn set_collected(&mut self, owner: TRef<Area2D>, collected: bool) {
+        self.collected = collected;
+        owner.set_visible(!collected);
+        if let Some(shape) = report_error(try_get_child_as::<pickup_tscn::CollisionShape2DNode>(
+            owner.as_ref(),
+        )) {
+            unsafe { shape.call_deferred("set_disabled", &[Variant::from_bool(collected)]) };
+        }
+    }
+}
+
+impl InstanceFrom<Self, Area2D> for Pickup {}
@@ -6,7 +6,9 @@ use std::borrow::{Borrow, BorrowMut};
 
 use gdnative::prelude::*;
 
-use crate::tank::{BasicTank, DamageTaker, TankProperties};
+use crate::controls;
+use crate::drivetrain::DriveInput;
+use crate::tank::{BasicTank, DamageTaker, TankKind, TankProperties};
 use crate::utils::*;
 
 pub const NAME: &str = "Player";
@@ -60,6 +62,8 @@ impl Player {
 }
 
 impl BasicTank<Self> for Player {
+    const KIND: TankKind = TankKind::Player;
+
     #[inline]
     fn props(&self) -> &TankProperties {
         self.properties.borrow()
@@ -72,36 +76,36 @@ impl BasicTank<Self> for Player {
 
     #[inline]
     fn control(&mut self, owner: TRef<KinematicBody2D>, delta: f32) {
-        self.properties
-            .turret_node
-            .get_ref()
-            .look_at(owner.get_global_mouse_position());
+        if !self.properties.status_effects.turret_locked() {
+            self.properties
+                .turret_node
+                .get_ref()
+                .look_at(owner.get_global_mouse_position());
+        }
+
+        if controls::is_capturing(owner.as_ref()) {
+            self.drive(owner, DriveInput::default(), delta);
+            return;
+        }
 
-        let mut rot_dir = 0.0;
         let input = Input::godot_singleton();
-        if input.is_action_pressed("turn_right") {
-            rot_dir += 1.0;
+        let mut drive = DriveInput::default();
+        if input.is_action_pressed(controls::ACTION_TURN_RIGHT) {
+            drive.steer += 1.0;
         }
-        if input.is_action_pressed("turn_left") {
-            rot_dir -= 1.0;
+        if input.is_action_pressed(controls::ACTION_TURN_LEFT) {
+            drive.steer -= 1.0;
         }
-
-        let rotation = owner.rotation() + (self.properties.rotation_speed * rot_dir * delta) as f64;
-        owner.set_rotation(rotation);
-
-        let mut velocity = Vector2::zero();
-        if input.is_action_pressed("forward") {
-            velocity.x = self.properties.max_speed;
-            velocity = velocity.rotated(Angle::radians(rotation as f32));
+        if input.is_action_pressed(controls::ACTION_FORWARD) {
+            drive.throttle += 1.0;
         }
-        if input.is_action_pressed("back") {
-            velocity.x = -self.properties.max_speed / 2.0;
-            velocity = velocity.rotated(Angle::radians(rotation as f32));
+        if input.is_action_pressed(controls::ACTION_BACK) {
+            drive.throttle -= 1.0;
         }
 
-        self.properties.velocity = velocity;
+        self.drive(owner, drive, delta);
 
-        if input.is_action_just_pressed("click") {
+        if input.is_action_just_pressed(controls::ACTION_SHOOT) {
             self.shoot(owner);
         }
     }
@@ -0,0 +1,84 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::prelude::*;
+
+/// Impacts slower than this, in units per second, do not cause any damage.
+pub const RAM_MIN_SPEED: f32 = 60.0;
+/// Damage per unit of impact speed above `RAM_MIN_SPEED`, for two bodies of
+/// equal mass.
+pub const RAM_DAMAGE_FACTOR: f32 = 0.2;
+/// Time in seconds after a ram during which a tank does not ram again.
+pub const RAM_COOLDOWN: f32 = 0.5;
+/// Rate at which a knockback velocity fades out, per second.
+pub const KNOCKBACK_DAMPING: f32 = 6.0;
+
+/// A body hitting another body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ram {
+    /// Mass of the body that hits.
+    pub mass: f32,
+    /// Speed at which both bodies approach each other.
+    pub impact_speed: f32,
+    /// Unit vector pointing from the body that hits to the body being hit.
+    pub direction: Vector2,
+    /// Instance id of the body that hits.
+    pub source: Option<i64>,
+}
+
+/// The effect of a `Ram` on the body being hit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RamResponse {
+    pub damage: u8,
+    pub push: Vector2,
+}
+
+impl Ram {
+    #[inline]
+    pub fn new(mass: f32, impact_speed: f32, direction: Vector2, source: Option<i64>) -> Self {
+        Ram {
+            mass,
+            impact_speed,
+            direction,
+            source,
+        }
+    }
+
+    /// The same collision, as seen from the body being hit with `mass` and
+    /// instance id `source`.
+    #[inline]
+    pub fn reversed(&self, mass: f32, source: Option<i64>) -> Self {
+        Ram {
+            mass,
+            impact_speed: self.impact_speed,
+            direction: -self.direction,
+            source,
+        }
+    }
+
+    /// Calculates the damage and push received by a body of `mass`. The
+    /// lighter a body is compared to the one hitting it, the larger its share
+    /// of the impact.
+    pub fn response(&self, mass: f32) -> RamResponse {
+        let total = self.mass + mass;
+        let share = if total > 0.0 { self.mass / total } else { 0.5 };
+
+        let damage = (self.impact_speed - RAM_MIN_SPEED).max(0.0) * RAM_DAMAGE_FACTOR * share;
+        RamResponse {
+            damage: damage.round().min(u8::MAX as f32) as u8,
+            push: self.direction * (self.impact_speed * share),
+        }
+    }
+}
+
+/// Fades out a knockback velocity in a frame rate independent way.
+#[inline]
+pub fn damp_knockback(knockback: Vector2, delta: f32) -> Vector2 {
+    let knockback = knockback * (-KNOCKBACK_DAMPING * delta).exp();
+    if knockback.square_length() < 1.0 {
+        Vector2::zero()
+    } else {
+        knockback
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::time::Duration;
+
+use gdnative::api::Resource;
+use gdnative::prelude::*;
+
+use crate::events::{self, ResourcesPreloaded};
+use crate::map::RES_CURSOR_TEXTURE;
+use crate::pickup::{RES_DEFAULT_WEAPON_SCENE, RES_PICKUP_SCENE};
+use crate::ui::{
+    RES_HEALTHBAR_GREEN_TEXTURE, RES_HEALTHBAR_RED_TEXTURE, RES_HEALTHBAR_YELLOW_TEXTURE,
+};
+use crate::utils::preload::*;
+use crate::utils::*;
+
+/// The resources that are loaded in the background when the game starts.
+pub const MANIFEST: &[Preload] = &[
+    Preload::new(RES_CURSOR_TEXTURE, "Texture"),
+    Preload::new(RES_HEALTHBAR_RED_TEXTURE, "Texture"),
+    Preload::new(RES_HEALTHBAR_YELLOW_TEXTURE, "Texture"),
+    Preload::new(RES_HEALTHBAR_GREEN_TEXTURE, "Texture"),
+    Preload::new(RES_PICKUP_SCENE, "PackedScene"),
+    Preload::new(RES_DEFAULT_WEAPON_SCENE, "PackedScene"),
+];
+
+/// How long preloading may take each frame.
+const PRELOAD_BUDGET: Duration = Duration::from_millis(4);
+
+/// Autoloaded node that owns the resource cache and preloads the `MANIFEST`.
+#[derive(NativeClass)]
+#[inherit(Node)]
+pub struct Resources {
+    cache: ResourceCache,
+    queue: PreloadQueue,
+}
+
+#[methods]
+impl Resources {
+    fn new(_owner: &Node) -> Self {
+        Resources {
+            cache: ResourceCache::new(),
+            queue: PreloadQueue::new(),
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: &Node) {
+        self.queue.extend(MANIFEST);
+        owner.set_process(true);
+    }
+
+    #[export]
+    fn _process(&mut self, owner: &Node, _delta: f64) {
+        if !self.queue.poll(&mut self.cache, PRELOAD_BUDGET) {
+            return;
+        }
+
+        owner.set_process(false);
+        events::publish(
+            owner,
+            ResourcesPreloaded {
+                loaded: self.cache.len() as u32,
+                missing: self.cache.missing().len() as u32,
+            },
+        );
+    }
+
+    #[export]
+    fn _exit_tree(&mut self, _owner: &Node) {
+        // release the resources while the engine is still running
+        self.cache.clear();
+    }
+
+    #[export]
+    fn is_preloaded(&self, _owner: &Node) -> bool {
+        self.queue.is_done()
+    }
+
+    /// Paths of the resources that could not be loaded.
+    #[export]
+    fn missing_resources(&self, _owner: &Node) -> StringArray {
+        StringArray::from_vec(
+            self.cache
+                .missing()
+                .iter()
+                .map(|err| GodotString::from_str(err.path.as_str()))
+                .collect(),
+        )
+    }
+}
+
+impl SingletonInstance<Self, Node> for Resources {
+    #[inline]
+    fn node_path<'a>() -> &'a str {
+        "/root/Resources"
+    }
+}
+
+/// Returns the resource at `path` from the cache of the `Resources` autoload.
+/// Loads the resource directly when the autoload is not available, e.g. when
+/// a scene is run in the editor on its own.
+pub fn try_load<T>(node: &Node, path: &str) -> NodeResult<Ref<T, Shared>>
+where
+    T: GodotObject<RefKind = <Resource as GodotObject>::RefKind>
+        + gdnative::prelude::SubClass<Resource>,
+{
+    match Resources::try_singleton(node) {
+        Some(resources) => resources
+            .map_mut(|resources, _| resources.cache.try_load::<T>(path))
+            .unwrap_or_else(|_| try_preload::<T>(path, "")),
+        None => try_preload::<T>(path, ""),
+    }
+}
+
+/// A typed handle to a resource, which is taken from the `Resources` cache
+/// once it is first needed.
+pub struct ResourceRef<T>
+where
+    T: GodotObject<RefKind = <Resource as GodotObject>::RefKind>
+        + gdnative::prelude::SubClass<Resource>,
+{
+    path: String,
+    resource: Option<Ref<T, Shared>>,
+}
+
+impl<T> ResourceRef<T>
+where
+    T: GodotObject<RefKind = <Resource as GodotObject>::RefKind>
+        + gdnative::prelude::SubClass<Resource>,
+{
+    pub fn new(path: impl ToString) -> Self {
+        ResourceRef {
+            path: path.to_string(),
+            resource: None,
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Takes the resource from the cache of the `Resources` autoload, which
+    /// `node` is used to find, and keeps a reference to it.
+    #[inline]
+    pub fn try_get_from(&mut self, node: &Node) -> NodeResult<Ref<T, Shared>> {
+        let resource = try_load::<T>(node, self.path())?;
+        self.resource = Some(resource.clone());
+        Ok(resource)
+    }
+
+    #[inline]
+    pub fn try_get_ref(&self) -> NodeResult<Ref<T, Shared>> {
+        self.resource
+            .clone()
+            .ok_or_else(|| NodeError::new(NodeErrorKind::Unresolved, self.path(), T::class_name()))
+    }
+}
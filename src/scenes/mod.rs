@@ -0,0 +1,39 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Typed references to the nodes of the scenes in the Godot project. The
+//! modules are generated from the `.tscn` files by `build.rs`, so renaming,
+//! moving or changing the type of a node that is used by a native class
+//! results in a compile error.
+//!
+//! A generated module, e.g. `tanks::tank_tscn`, contains:
+//! - `RES_PATH`, the resource path of the scene;
+//! - an `ANIM_*` constant per animation of its `AnimationPlayer` and
+//!   `AnimatedSprite` nodes;
+//! - a type per node that implements `ChildNodeRef`, e.g. `TurretMuzzleNode`
+//!   for the node at `Turret/Muzzle`;
+//! - `CONNECTIONS`, the signal connections that are made in the scene.
+
+use gdnative::prelude::*;
+
+include!(concat!(env!("OUT_DIR"), "/scenes.rs"));
+
+/// A node in a scene, at a path relative to the scene's root.
+pub trait ChildNodeRef {
+    type Base: GodotObject;
+
+    fn path() -> &'static str;
+}
+
+/// A signal connection that is made in a scene.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Connection {
+    pub signal: &'static str,
+    /// Path of the node that emits the signal.
+    pub from: &'static str,
+    /// Path of the node that receives the signal.
+    pub to: &'static str,
+    pub method: &'static str,
+    pub flags: i64,
+}
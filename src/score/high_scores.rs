@@ -0,0 +1,244 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::api::ConfigFile;
+use gdnative::prelude::*;
+
+use crate::score::leaderboard::*;
+use crate::score::Score;
+use crate::utils::*;
+
+pub const SIGNAL_LEADERBOARD_CHANGED: &str = "leaderboard_changed";
+
+const CONFIG_SECTION: &str = "leaderboard";
+const CONFIG_KEY_ENTRIES: &str = "entries";
+
+/// Path of the file the leaderboard of `map` is stored in.
+#[inline]
+pub fn config_path(map: &str) -> String {
+    format!("user://leaderboard_{}.cfg", map.to_lowercase())
+}
+
+/// Name of the map that is currently played, see `map_name`.
+pub fn current_map_name(node: &Node) -> Option<String> {
+    let tree = unsafe { node.get_tree()?.assume_safe() };
+    let scene = unsafe { tree.current_scene()?.assume_safe() };
+    map_name(scene.filename().to_string().as_str())
+}
+
+/// Name of the map of the scene file `filename`, which is the file name
+/// without its extension, e.g. `Map01`.
+pub fn map_name(filename: &str) -> Option<String> {
+    let name = filename.rsplit('/').next().unwrap_or_default();
+    let name = name.split('.').next().unwrap_or_default();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Loads the leaderboard of `map`. Entries that were tampered with are
+/// dropped.
+pub fn load_leaderboard(map: &str, size: usize) -> Leaderboard {
+    let path = config_path(map);
+    let config = ConfigFile::new();
+    if config.load(path.as_str()).is_err() {
+        // no results saved yet
+        return Leaderboard::new(map, size);
+    }
+
+    let lines = config
+        .get_value(CONFIG_SECTION, CONFIG_KEY_ENTRIES, Variant::new())
+        .try_to_string_array()
+        .map(|lines| {
+            lines
+                .read()
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+
+    let (leaderboard, errors) = Leaderboard::from_lines(map, size, lines.as_slice());
+    for err in errors {
+        godot_warn!("{} in `{}`", err, path);
+    }
+    leaderboard
+}
+
+pub fn save_leaderboard(leaderboard: &Leaderboard) -> bool {
+    let path = config_path(leaderboard.map());
+    let lines = leaderboard
+        .to_lines()
+        .iter()
+        .map(GodotString::from)
+        .collect::<Vec<GodotString>>();
+
+    let config = ConfigFile::new();
+    config.set_value(
+        CONFIG_SECTION,
+        CONFIG_KEY_ENTRIES,
+        StringArray::from_vec(lines),
+    );
+
+    if let Err(err) = config.save(path.as_str()) {
+        godot_error!("Failed to save `{}`: {:?}", path, err);
+        return false;
+    }
+    true
+}
+
+/// Gives a results screen access to the leaderboard of a map. Add it to the
+/// screen, read the entries with `get_entries` and let the player enter a
+/// name when `qualifies` returns true.
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct HighScores {
+    /// Map of which the leaderboard is shown, defaults to the current scene.
+    #[property]
+    map_name: String,
+    /// Number of entries that are kept.
+    #[property(default = 10)]
+    size: u32,
+
+    leaderboard: Leaderboard,
+}
+
+#[methods]
+impl HighScores {
+    fn register(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: SIGNAL_LEADERBOARD_CHANGED,
+            args: &[SignalArgument {
+                name: "rank",
+                default: Variant::from_i64(-1),
+                export_info: ExportInfo::new(VariantType::I64),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+    }
+
+    fn new(_owner: &Node) -> Self {
+        HighScores {
+            map_name: String::new(),
+            size: LEADERBOARD_SIZE as u32,
+
+            leaderboard: Leaderboard::new("", LEADERBOARD_SIZE),
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: &Node) {
+        if self.map_name.is_empty() {
+            self.map_name = current_map_name(owner).unwrap_or_default();
+        }
+        self.reload(owner);
+    }
+
+    /// Reads the leaderboard of `map_name` from disk.
+    #[export]
+    pub fn reload(&mut self, _owner: &Node) {
+        self.leaderboard = load_leaderboard(self.map_name.as_str(), self.size as usize);
+    }
+
+    /// The entries from best to worst, as dictionaries with the keys `rank`,
+    /// `name`, `score`, `time`, `time_text` and `accuracy`.
+    #[export]
+    fn get_entries(&self, _owner: &Node) -> VariantArray {
+        let entries = VariantArray::new();
+        for (rank, entry) in self.leaderboard.entries().iter().enumerate() {
+            let dict = Dictionary::new();
+            dict.insert("rank", rank as i64 + 1);
+            dict.insert("name", entry.name.as_str());
+            dict.insert("score", entry.score as i64);
+            dict.insert("time", entry.time as f64);
+            dict.insert("time_text", format_time(entry.time));
+            dict.insert("accuracy", entry.accuracy as f64);
+            entries.push(dict.into_shared());
+        }
+        entries.into_shared()
+    }
+
+    /// Returns true when a result would make it onto the leaderboard.
+    #[export]
+    fn qualifies(&self, _owner: &Node, score: u32, time: f32, accuracy: f32) -> bool {
+        self.leaderboard
+            .rank(&LeaderboardEntry::new("", score, time, accuracy))
+            .is_some()
+    }
+
+    /// Returns true when the current result of the `Score` autoload would
+    /// make it onto the leaderboard.
+    #[export]
+    fn current_qualifies(&self, owner: &Node) -> bool {
+        self.current_entry(owner, "")
+            .is_some_and(|entry| self.leaderboard.rank(&entry).is_some())
+    }
+
+    /// Adds a result and returns its rank, starting at one, or zero when it
+    /// did not make it onto the leaderboard.
+    #[export]
+    pub fn insert(
+        &mut self,
+        owner: TRef<Node>,
+        name: String,
+        score: u32,
+        time: f32,
+        accuracy: f32,
+    ) -> u32 {
+        self.insert_entry(
+            owner,
+            LeaderboardEntry::new(name.as_str(), score, time, accuracy),
+        )
+    }
+
+    /// Adds the current result of the `Score` autoload under `name`, see
+    /// `insert`.
+    #[export]
+    fn submit(&mut self, owner: TRef<Node>, name: String) -> u32 {
+        match self.current_entry(owner.as_ref(), name.as_str()) {
+            Some(entry) => self.insert_entry(owner, entry),
+            None => 0,
+        }
+    }
+
+    /// Removes all entries of the leaderboard.
+    #[export]
+    fn clear(&mut self, owner: TRef<Node>) {
+        self.leaderboard.clear();
+        save_leaderboard(&self.leaderboard);
+        owner.emit_signal(SIGNAL_LEADERBOARD_CHANGED, &[Variant::from_i64(0)]);
+    }
+
+    fn current_entry(&self, owner: &Node, name: &str) -> Option<LeaderboardEntry> {
+        let score = Score::try_singleton(owner)?;
+        score
+            .map(|score, _| {
+                let board = score.board();
+                LeaderboardEntry::new(
+                    name,
+                    board.score(),
+                    board.elapsed(),
+                    board.accuracy() * 100.0,
+                )
+            })
+            .ok()
+    }
+
+    fn insert_entry(&mut self, owner: TRef<Node>, entry: LeaderboardEntry) -> u32 {
+        let rank = match self.leaderboard.insert(entry) {
+            Some(rank) => rank as u32 + 1,
+            None => return 0,
+        };
+
+        save_leaderboard(&self.leaderboard);
+        owner.emit_signal(
+            SIGNAL_LEADERBOARD_CHANGED,
+            &[Variant::from_i64(rank as i64)],
+        );
+        rank
+    }
+}
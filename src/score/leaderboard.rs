@@ -0,0 +1,359 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of entries kept when a leaderboard does not define its own size.
+pub const LEADERBOARD_SIZE: usize = 10;
+/// Names longer than this are cut off.
+pub const MAX_NAME_LENGTH: usize = 16;
+
+/// Mixed into every checksum, so entries cannot be recreated by hashing the
+/// visible values only.
+const CHECKSUM_SALT: &str = "tanks/leaderboard/v1";
+
+/// A single result on a leaderboard. Entries are written as
+/// `score:time:accuracy:name`, where the time is in seconds and the accuracy
+/// is a percentage, e.g. `1250:84.5:62:Roel`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: u32,
+    pub time: f32,
+    pub accuracy: f32,
+}
+
+impl LeaderboardEntry {
+    pub fn new(name: &str, score: u32, time: f32, accuracy: f32) -> Self {
+        LeaderboardEntry {
+            name: sanitize_name(name),
+            score,
+            time: time.max(0.0),
+            accuracy: accuracy.clamp(0.0, 100.0),
+        }
+    }
+
+    /// Higher scores rank first, equal scores are ranked by the shortest time
+    /// and then by the best accuracy.
+    pub fn rank_cmp(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .cmp(&self.score)
+            .then(
+                self.time
+                    .partial_cmp(&other.time)
+                    .unwrap_or(Ordering::Equal),
+            )
+            .then(
+                other
+                    .accuracy
+                    .partial_cmp(&self.accuracy)
+                    .unwrap_or(Ordering::Equal),
+            )
+    }
+}
+
+impl fmt::Display for LeaderboardEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.score, self.time, self.accuracy, self.name
+        )
+    }
+}
+
+impl FromStr for LeaderboardEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(4, ':');
+        let mut next = || {
+            parts
+                .next()
+                .ok_or_else(|| format!("Missing values in leaderboard entry `{}`", s))
+        };
+
+        let score = next()?;
+        let time = next()?;
+        let accuracy = next()?;
+        let name = next()?;
+
+        let invalid = || format!("Invalid value in leaderboard entry `{}`", s);
+        Ok(LeaderboardEntry::new(
+            name,
+            score.trim().parse::<u32>().map_err(|_| invalid())?,
+            time.trim().parse::<f32>().map_err(|_| invalid())?,
+            accuracy.trim().parse::<f32>().map_err(|_| invalid())?,
+        ))
+    }
+}
+
+/// Trims `name`, replaces control characters and cuts it off at
+/// `MAX_NAME_LENGTH` characters. An empty name becomes `???`.
+pub fn sanitize_name(name: &str) -> String {
+    let name = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+
+    let name = name.trim_end();
+    if name.is_empty() {
+        String::from("???")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Checksum of `entry` on the leaderboard of `map`. Including the map name
+/// means entries cannot be copied from one map's leaderboard to another.
+pub fn checksum(map: &str, entry: &LeaderboardEntry) -> String {
+    // 64-bit FNV-1a, which, unlike the std hashers, is stable between builds
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in [CHECKSUM_SALT, map, entry.to_string().as_str()].iter() {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    format!("{:016x}", hash)
+}
+
+/// The best results of a single map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Leaderboard {
+    map: String,
+    size: usize,
+    entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    pub fn new(map: &str, size: usize) -> Self {
+        Leaderboard {
+            map: map.to_string(),
+            size: size.max(1),
+            entries: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn map(&self) -> &str {
+        self.map.as_str()
+    }
+
+    /// Entries ordered from best to worst.
+    #[inline]
+    pub fn entries(&self) -> &[LeaderboardEntry] {
+        self.entries.as_slice()
+    }
+
+    /// Zero based rank `entry` would get, or `None` when it does not make it
+    /// onto the leaderboard.
+    pub fn rank(&self, entry: &LeaderboardEntry) -> Option<usize> {
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| entry.rank_cmp(other) == Ordering::Less)
+            .unwrap_or(self.entries.len());
+
+        if rank < self.size {
+            Some(rank)
+        } else {
+            None
+        }
+    }
+
+    /// Inserts `entry` and returns its zero based rank, or `None` when it did
+    /// not make it onto the leaderboard.
+    pub fn insert(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        let rank = self.rank(&entry)?;
+        self.entries.insert(rank, entry);
+        self.entries.truncate(self.size);
+        Some(rank)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The entries as `checksum:entry` lines, as they are stored on disk.
+    pub fn to_lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| format!("{}:{}", checksum(self.map.as_str(), entry), entry))
+            .collect()
+    }
+
+    /// Reads a leaderboard from lines written by `to_lines`. Lines that
+    /// cannot be parsed, or of which the checksum does not match, are
+    /// skipped and returned as errors.
+    pub fn from_lines<S: AsRef<str>>(map: &str, size: usize, lines: &[S]) -> (Self, Vec<String>) {
+        let mut leaderboard = Leaderboard::new(map, size);
+        let mut errors = Vec::new();
+
+        for line in lines.iter().map(|line| line.as_ref()) {
+            let (sum, entry) = match line.split_once(':') {
+                Some(parts) => parts,
+                None => {
+                    errors.push(format!("Invalid leaderboard line `{}`", line));
+                    continue;
+                }
+            };
+
+            let entry = match entry.parse::<LeaderboardEntry>() {
+                Ok(entry) => entry,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+            if checksum(map, &entry) != sum {
+                errors.push(format!(
+                    "Checksum mismatch of leaderboard entry `{}`",
+                    entry
+                ));
+                continue;
+            }
+
+            leaderboard.insert(entry);
+        }
+
+        (leaderboard, errors)
+    }
+}
+
+/// Formats a time in seconds as `m:ss.t`, e.g. `1:24.5`.
+pub fn format_time(seconds: f32) -> String {
+    let tenths = (seconds.max(0.0) * 10.0).round() as u32;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "Map01";
+
+    fn entry(name: &str, score: u32, time: f32) -> LeaderboardEntry {
+        LeaderboardEntry::new(name, score, time, 50.0)
+    }
+
+    #[test]
+    fn entry_survives_its_text() {
+        let entry = LeaderboardEntry::new("Roel", 1250, 84.5, 62.0);
+        assert_eq!(entry.to_string(), "1250:84.5:62:Roel");
+        assert_eq!(entry.to_string().parse::<LeaderboardEntry>(), Ok(entry));
+
+        // the name is the last value, so it may contain colons
+        let parsed = "10:1:2:a:b".parse::<LeaderboardEntry>().unwrap();
+        assert_eq!(parsed.name, "a:b");
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        assert!("1250:84.5:62".parse::<LeaderboardEntry>().is_err());
+        assert!("-5:84.5:62:Roel".parse::<LeaderboardEntry>().is_err());
+        assert!("1250:fast:62:Roel".parse::<LeaderboardEntry>().is_err());
+    }
+
+    #[test]
+    fn entries_are_sanitized() {
+        let entry = LeaderboardEntry::new("  a\tvery long name of a player ", 1, -3.0, 120.0);
+        assert_eq!(entry.name, "a very long name");
+        assert_eq!((entry.time, entry.accuracy), (0.0, 100.0));
+        assert_eq!(LeaderboardEntry::new(" \n ", 1, 1.0, 1.0).name, "???");
+    }
+
+    #[test]
+    fn checksum_depends_on_map_and_entry() {
+        let entry = entry("Roel", 1250, 84.5);
+        assert_eq!(checksum(MAP, &entry), checksum(MAP, &entry.clone()));
+        assert_eq!(checksum(MAP, &entry).len(), 16);
+        assert_ne!(checksum(MAP, &entry), checksum("Map02", &entry));
+
+        let better = LeaderboardEntry {
+            score: 9999,
+            ..entry.clone()
+        };
+        assert_ne!(checksum(MAP, &entry), checksum(MAP, &better));
+    }
+
+    #[test]
+    fn rank_by_score_time_and_accuracy() {
+        let best = entry("a", 200, 60.0);
+        let faster = entry("b", 100, 30.0);
+        let slower = entry("c", 100, 90.0);
+        let accurate = LeaderboardEntry::new("d", 100, 90.0, 80.0);
+
+        assert_eq!(best.rank_cmp(&faster), Ordering::Less);
+        assert_eq!(faster.rank_cmp(&slower), Ordering::Less);
+        assert_eq!(accurate.rank_cmp(&slower), Ordering::Less);
+        assert_eq!(slower.rank_cmp(&slower.clone()), Ordering::Equal);
+    }
+
+    #[test]
+    fn insert_keeps_the_best_entries() {
+        let mut leaderboard = Leaderboard::new(MAP, 2);
+        assert_eq!(leaderboard.insert(entry("a", 100, 10.0)), Some(0));
+        assert_eq!(leaderboard.insert(entry("b", 300, 10.0)), Some(0));
+        assert_eq!(leaderboard.insert(entry("c", 200, 10.0)), Some(1));
+        assert_eq!(leaderboard.insert(entry("d", 50, 10.0)), None);
+
+        let names = leaderboard
+            .entries()
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b", "c"]);
+
+        // an equal result ranks below the one that was there first
+        assert_eq!(leaderboard.insert(entry("e", 300, 10.0)), Some(1));
+    }
+
+    #[test]
+    fn lines_survive_a_round_trip() {
+        let mut leaderboard = Leaderboard::new(MAP, LEADERBOARD_SIZE);
+        leaderboard.insert(entry("a", 100, 10.0));
+        leaderboard.insert(entry("b", 300, 12.5));
+
+        let (read, errors) =
+            Leaderboard::from_lines(MAP, LEADERBOARD_SIZE, &leaderboard.to_lines());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(read, leaderboard);
+    }
+
+    #[test]
+    fn tampered_lines_are_rejected() {
+        let mut leaderboard = Leaderboard::new(MAP, LEADERBOARD_SIZE);
+        leaderboard.insert(entry("a", 100, 10.0));
+        leaderboard.insert(entry("b", 300, 12.5));
+
+        let mut lines = leaderboard.to_lines();
+        lines[0] = lines[0].replace(":300:", ":3000:");
+        lines.push(String::from("no checksum"));
+        lines.push(String::from("0123456789abcdef:not an entry"));
+
+        let (read, errors) = Leaderboard::from_lines(MAP, LEADERBOARD_SIZE, &lines);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert_eq!(read.entries(), &leaderboard.entries()[1..]);
+
+        // entries of another map do not match either
+        let (read, errors) =
+            Leaderboard::from_lines("Map02", LEADERBOARD_SIZE, &leaderboard.to_lines());
+        assert_eq!(errors.len(), 2);
+        assert!(read.entries().is_empty());
+    }
+
+    #[test]
+    fn time_is_formatted_as_minutes() {
+        assert_eq!(format_time(84.5), "1:24.5");
+        assert_eq!(format_time(5.04), "0:05.0");
+        assert_eq!(format_time(-1.0), "0:00.0");
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::prelude::*;
+
+pub use high_scores::{map_name, HighScores};
+pub use rules::*;
+
+use crate::events::{self, BulletHit, MapStarted, ScoreChanged, TankDestroyed, TankFired};
+use crate::utils::*;
+
+mod high_scores;
+pub mod leaderboard;
+mod rules;
+
+/// Autoloaded node that keeps the player's score, based on the events
+/// published by tanks and bullets.
+#[derive(NativeClass)]
+#[inherit(Node)]
+pub struct Score {
+    board: ScoreBoard,
+}
+
+#[methods]
+impl Score {
+    fn new(_owner: &Node) -> Self {
+        Score {
+            board: ScoreBoard::new(ScoreRules::new()),
+        }
+    }
+
+    #[inline]
+    pub fn board(&self) -> &ScoreBoard {
+        &self.board
+    }
+
+    fn publish_score_changed(&self, owner: TRef<Node>) {
+        events::publish(
+            owner.as_ref(),
+            ScoreChanged {
+                score: self.board.score(),
+                multiplier: self.board.multiplier() as f64,
+                accuracy: self.board.accuracy() as f64 * 100.0,
+            },
+        );
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: TRef<Node>) {
+        let node = owner.as_ref();
+        events::subscribe::<MapStarted, _>(node, owner, "_on_Events_map_started", 0);
+        events::subscribe::<TankFired, _>(node, owner, "_on_Events_tank_fired", 0);
+        events::subscribe::<BulletHit, _>(node, owner, "_on_Events_bullet_hit", 0);
+        events::subscribe::<TankDestroyed, _>(node, owner, "_on_Events_tank_destroyed", 0);
+    }
+
+    #[export]
+    fn _process(&mut self, owner: TRef<Node>, delta: f32) {
+        if self.board.update(delta) {
+            self.publish_score_changed(owner);
+        }
+    }
+
+    #[export]
+    fn get_score(&self, _owner: &Node) -> u32 {
+        self.board.score()
+    }
+
+    #[export]
+    fn get_kills(&self, _owner: &Node) -> u32 {
+        self.board.kills()
+    }
+
+    #[export]
+    fn get_shots(&self, _owner: &Node) -> u32 {
+        self.board.shots()
+    }
+
+    #[export]
+    fn get_hits(&self, _owner: &Node) -> u32 {
+        self.board.hits()
+    }
+
+    #[export]
+    fn get_combo(&self, _owner: &Node) -> u32 {
+        self.board.combo()
+    }
+
+    /// Seconds played on the current map.
+    #[export]
+    fn get_elapsed(&self, _owner: &Node) -> f32 {
+        self.board.elapsed()
+    }
+
+    /// Accuracy as a percentage.
+    #[export]
+    fn get_accuracy(&self, _owner: &Node) -> f32 {
+        self.board.accuracy() * 100.0
+    }
+
+    #[export]
+    fn get_multiplier(&self, _owner: &Node) -> f32 {
+        self.board.multiplier()
+    }
+
+    /// Starts a new score for the player of the map that started, or stops
+    /// scoring when the map has no player.
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_map_started(&mut self, owner: TRef<Node>, event: MapStarted) {
+        self.board.start(if event.player != 0 {
+            Some(event.player)
+        } else {
+            None
+        });
+        self.publish_score_changed(owner);
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_tank_fired(&mut self, owner: TRef<Node>, event: TankFired) {
+        if self.board.record_shot(event.tank) {
+            self.publish_score_changed(owner);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_bullet_hit(&mut self, owner: TRef<Node>, event: BulletHit) {
+        if self.board.record_hit(event.shooter) {
+            self.publish_score_changed(owner);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_tank_destroyed(&mut self, owner: TRef<Node>, event: TankDestroyed) {
+        let kind = match TargetKind::of(event.kind) {
+            Some(kind) => kind,
+            None => return,
+        };
+        if self.board.record_kill(event.killer, kind).is_some() {
+            self.publish_score_changed(owner);
+        }
+    }
+}
+
+impl SingletonInstance<Self, Node> for Score {
+    #[inline]
+    fn node_path<'a>() -> &'a str {
+        "/root/Score"
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::str::FromStr;
+
+use crate::tank::TankKind;
+
+/// The kinds of enemies the player is awarded points for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TargetKind {
+    EnemyTank,
+    GunTurret,
+}
+
+impl TargetKind {
+    #[inline]
+    pub fn all() -> Vec<TargetKind> {
+        vec![Self::EnemyTank, Self::GunTurret]
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::EnemyTank => "enemy_tank",
+            Self::GunTurret => "gun_turret",
+        }
+    }
+
+    /// The kind of target a tank of `kind` is, `None` for the player.
+    #[inline]
+    pub fn of(kind: TankKind) -> Option<TargetKind> {
+        match kind {
+            TankKind::Player => None,
+            TankKind::EnemyTank => Some(Self::EnemyTank),
+            TankKind::GunTurret => Some(Self::GunTurret),
+        }
+    }
+}
+
+impl FromStr for TargetKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Self::all()
+            .into_iter()
+            .find(|kind| kind.name().eq(s))
+            .ok_or_else(|| format!("Unknown target kind `{}`", s))
+    }
+}
+
+/// How kills are turned into points. Kills made within `combo_window`
+/// seconds of each other form a combo, every kill in a combo after the first
+/// raises the multiplier by `combo_step`, up to `max_multiplier`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreRules {
+    pub enemy_tank_points: u32,
+    pub gun_turret_points: u32,
+    pub combo_window: f32,
+    pub combo_step: f32,
+    pub max_multiplier: f32,
+}
+
+impl ScoreRules {
+    pub fn new() -> Self {
+        ScoreRules {
+            enemy_tank_points: 150,
+            gun_turret_points: 100,
+            combo_window: 4.0,
+            combo_step: 0.5,
+            max_multiplier: 4.0,
+        }
+    }
+
+    /// Base points for destroying a target of `kind`.
+    #[inline]
+    pub fn points(&self, kind: TargetKind) -> u32 {
+        match kind {
+            TargetKind::EnemyTank => self.enemy_tank_points,
+            TargetKind::GunTurret => self.gun_turret_points,
+        }
+    }
+
+    /// Multiplier for the `combo`th kill of a combo, where the first kill is
+    /// one.
+    #[inline]
+    pub fn multiplier(&self, combo: u32) -> f32 {
+        let multiplier = 1.0 + combo.saturating_sub(1) as f32 * self.combo_step;
+        multiplier.clamp(1.0, self.max_multiplier.max(1.0))
+    }
+}
+
+impl Default for ScoreRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The score of a single player during a single map. Shots, hits and kills
+/// count only when they are made by the player, the instance id of whose
+/// tank is recorded with `start`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScoreBoard {
+    rules: ScoreRules,
+    player: Option<i64>,
+
+    score: u32,
+    kills: u32,
+    shots: u32,
+    hits: u32,
+    combo: u32,
+    since_kill: f32,
+    elapsed: f32,
+}
+
+impl ScoreBoard {
+    pub fn new(rules: ScoreRules) -> Self {
+        ScoreBoard {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    #[inline]
+    pub fn kills(&self) -> u32 {
+        self.kills
+    }
+
+    #[inline]
+    pub fn shots(&self) -> u32 {
+        self.shots
+    }
+
+    #[inline]
+    pub fn hits(&self) -> u32 {
+        self.hits
+    }
+
+    /// Number of kills in the current combo.
+    #[inline]
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    /// Seconds played since the last reset.
+    #[inline]
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Multiplier applied to the next kill when it is made within the combo
+    /// window.
+    #[inline]
+    pub fn multiplier(&self) -> f32 {
+        self.rules.multiplier(self.combo.max(1))
+    }
+
+    /// Part of the shots fired that hit something, in the range `0.0..=1.0`.
+    #[inline]
+    pub fn accuracy(&self) -> f32 {
+        if self.shots == 0 {
+            0.0
+        } else {
+            (self.hits as f32 / self.shots as f32).min(1.0)
+        }
+    }
+
+    /// Clears the score, keeping the rules.
+    pub fn reset(&mut self) {
+        *self = ScoreBoard::new(self.rules.clone());
+    }
+
+    /// Clears the score and starts keeping it for the tank with instance id
+    /// `player`, or for no one when it is `None`.
+    pub fn start(&mut self, player: Option<i64>) {
+        self.reset();
+        self.player = player;
+    }
+
+    #[inline]
+    pub fn is_player(&self, id: i64) -> bool {
+        self.player == Some(id)
+    }
+
+    /// Advances time by `delta` seconds and returns true when the current
+    /// combo has ended.
+    pub fn update(&mut self, delta: f32) -> bool {
+        self.elapsed += delta;
+        if self.combo == 0 {
+            return false;
+        }
+
+        self.since_kill += delta;
+        if self.since_kill <= self.rules.combo_window {
+            return false;
+        }
+
+        self.combo = 0;
+        true
+    }
+
+    /// Counts a shot fired by `tank`, returns whether it was the player's.
+    #[inline]
+    pub fn record_shot(&mut self, tank: i64) -> bool {
+        if !self.is_player(tank) {
+            return false;
+        }
+        self.shots += 1;
+        true
+    }
+
+    /// Counts a hit by a bullet of `shooter`, returns whether it was the
+    /// player's.
+    #[inline]
+    pub fn record_hit(&mut self, shooter: i64) -> bool {
+        if !self.is_player(shooter) {
+            return false;
+        }
+        self.hits += 1;
+        true
+    }
+
+    /// Awards the points for destroying a target of `kind` when `killer` is
+    /// the player, and returns them.
+    pub fn record_kill(&mut self, killer: i64, kind: TargetKind) -> Option<u32> {
+        if !self.is_player(killer) {
+            return None;
+        }
+
+        self.combo += 1;
+        self.since_kill = 0.0;
+        self.kills += 1;
+
+        let points = (self.rules.points(kind) as f32 * self.rules.multiplier(self.combo)).round();
+        let points = points as u32;
+        self.score = self.score.saturating_add(points);
+        Some(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: i64 = 1;
+    const ENEMY: i64 = 2;
+
+    fn board() -> ScoreBoard {
+        let mut board = ScoreBoard::new(ScoreRules::new());
+        board.start(Some(PLAYER));
+        board
+    }
+
+    #[test]
+    fn points_per_target_kind() {
+        let rules = ScoreRules::new();
+        for kind in TargetKind::all() {
+            let mut board = board();
+            assert_eq!(board.record_kill(PLAYER, kind), Some(rules.points(kind)));
+            assert_eq!(board.score(), rules.points(kind));
+        }
+        assert_ne!(
+            rules.points(TargetKind::EnemyTank),
+            rules.points(TargetKind::GunTurret)
+        );
+    }
+
+    #[test]
+    fn combo_multiplier_grows_and_resets() {
+        let rules = ScoreRules::new();
+        let mut board = board();
+        let points = rules.points(TargetKind::GunTurret) as f32;
+
+        assert_eq!(board.multiplier(), 1.0);
+        board.record_kill(PLAYER, TargetKind::GunTurret);
+        board.update(rules.combo_window * 0.5);
+        let second = board.record_kill(PLAYER, TargetKind::GunTurret);
+        assert_eq!(board.combo(), 2);
+        assert_eq!(second, Some((points * (1.0 + rules.combo_step)) as u32));
+
+        for _ in 0..20 {
+            board.record_kill(PLAYER, TargetKind::GunTurret);
+        }
+        assert_eq!(board.multiplier(), rules.max_multiplier);
+
+        assert!(!board.update(rules.combo_window * 0.5));
+        assert!(board.update(rules.combo_window));
+        assert_eq!(board.combo(), 0);
+        assert_eq!(board.multiplier(), 1.0);
+        assert_eq!(
+            board.record_kill(PLAYER, TargetKind::GunTurret),
+            Some(points as u32)
+        );
+    }
+
+    #[test]
+    fn accuracy_without_shots_is_zero() {
+        let mut board = board();
+        assert_eq!(board.accuracy(), 0.0);
+
+        board.record_shot(PLAYER);
+        board.record_shot(PLAYER);
+        board.record_hit(PLAYER);
+        assert_eq!(board.accuracy(), 0.5);
+    }
+
+    #[test]
+    fn only_the_player_is_credited() {
+        let mut board = board();
+        assert!(!board.record_shot(ENEMY));
+        assert!(!board.record_hit(ENEMY));
+        assert_eq!(board.record_kill(ENEMY, TargetKind::EnemyTank), None);
+        assert_eq!((board.shots(), board.hits(), board.kills()), (0, 0, 0));
+        assert_eq!(board.score(), 0);
+
+        assert_eq!(
+            board.record_kill(PLAYER, TargetKind::EnemyTank),
+            Some(ScoreRules::new().points(TargetKind::EnemyTank))
+        );
+        assert_eq!(board.kills(), 1);
+
+        board.start(None);
+        assert_eq!(board.record_kill(PLAYER, TargetKind::EnemyTank), None);
+        assert_eq!(board.score(), 0);
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::drivetrain::DriveModifiers;
+use crate::scenes::tanks::tank_tscn;
+
+/// The kinds of effects that can last on a tank for some time, both harmful
+/// ones and the power-ups given by pickups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusEffectType {
+    /// Damage over time, `strength` is the damage per second per stack.
+    Burning,
+    /// Slower movement, `strength` is the part of the speed that is lost.
+    Slowed,
+    /// The turret cannot turn.
+    Stunned,
+    /// The weapons cannot fire.
+    Emp,
+    /// Faster movement, `strength` is the part of the speed that is gained.
+    SpeedBoost,
+    /// Shorter gun cooldown, `strength` is the part of the cooldown that is
+    /// skipped.
+    RapidFire,
+}
+
+/// What happens when an effect is applied to a tank that already has an
+/// effect of the same type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stacking {
+    /// The strongest strength and longest duration of both are kept.
+    Refresh,
+    /// The durations are added, up to `max_duration` seconds.
+    Extend { max_duration: f32 },
+    /// Adds a stack, up to `max_stacks`, and restarts the duration.
+    Intensify { max_stacks: u8 },
+}
+
+impl StatusEffectType {
+    #[inline]
+    pub fn all() -> Vec<StatusEffectType> {
+        vec![
+            Self::Burning,
+            Self::Slowed,
+            Self::Stunned,
+            Self::Emp,
+            Self::SpeedBoost,
+            Self::RapidFire,
+        ]
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Burning => "burning",
+            Self::Slowed => "slowed",
+            Self::Stunned => "stunned",
+            Self::Emp => "emp",
+            Self::SpeedBoost => "speed_boost",
+            Self::RapidFire => "rapid_fire",
+        }
+    }
+
+    /// Name of the animation of the tank's `AnimationPlayer` that is played
+    /// while the effect is active.
+    #[inline]
+    pub fn animation(&self) -> &'static str {
+        match self {
+            Self::Burning => tank_tscn::ANIM_STATUS_BURNING,
+            Self::Slowed => tank_tscn::ANIM_STATUS_SLOWED,
+            Self::Stunned => tank_tscn::ANIM_STATUS_STUNNED,
+            Self::Emp => tank_tscn::ANIM_STATUS_EMP,
+            Self::SpeedBoost => tank_tscn::ANIM_STATUS_SPEED_BOOST,
+            Self::RapidFire => tank_tscn::ANIM_STATUS_RAPID_FIRE,
+        }
+    }
+
+    #[inline]
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            Self::Burning => Stacking::Intensify { max_stacks: 3 },
+            Self::Slowed => Stacking::Refresh,
+            Self::Stunned => Stacking::Refresh,
+            Self::Emp => Stacking::Extend { max_duration: 6.0 },
+            Self::SpeedBoost | Self::RapidFire => Stacking::Refresh,
+        }
+    }
+
+    /// Strength used when an effect is defined without one.
+    #[inline]
+    pub fn default_strength(&self) -> f32 {
+        match self {
+            Self::Burning => 5.0,
+            Self::Slowed | Self::SpeedBoost | Self::RapidFire => 0.5,
+            Self::Stunned | Self::Emp => 0.0,
+        }
+    }
+}
+
+impl FromStr for StatusEffectType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Self::all()
+            .into_iter()
+            .find(|typ| typ.name().eq(s))
+            .ok_or_else(|| format!("Unknown status effect `{}`", s))
+    }
+}
+
+/// An effect as it is defined on bullets and explosions. Effects are written
+/// as `type:duration` or `type:duration:strength`, e.g. `burning:3:4`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatusEffect {
+    pub typ: StatusEffectType,
+    pub duration: f32,
+    pub strength: f32,
+}
+
+impl StatusEffect {
+    #[inline]
+    pub fn new(typ: StatusEffectType, duration: f32, strength: f32) -> Self {
+        StatusEffect {
+            typ,
+            duration,
+            strength,
+        }
+    }
+}
+
+impl fmt::Display for StatusEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.typ.name(), self.duration, self.strength)
+    }
+}
+
+impl FromStr for StatusEffect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let typ = parts
+            .next()
+            .unwrap_or_default()
+            .parse::<StatusEffectType>()?;
+
+        let parse = |v: Option<&str>| -> Result<Option<f32>, String> {
+            v.map(|v| {
+                v.trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid value in status effect `{}`", s))
+            })
+            .transpose()
+        };
+
+        let duration = parse(parts.next())?
+            .ok_or_else(|| format!("Missing duration in status effect `{}`", s))?;
+        let strength = parse(parts.next())?.unwrap_or_else(|| typ.default_strength());
+
+        if parts.next().is_some() {
+            return Err(format!("Too many values in status effect `{}`", s));
+        }
+        Ok(StatusEffect::new(typ, duration, strength))
+    }
+}
+
+/// Parses an optional effect, as used by exported properties where an empty
+/// string means no effect.
+#[inline]
+pub fn parse_status_effect(value: &str) -> Result<Option<StatusEffect>, String> {
+    if value.trim().is_empty() {
+        Ok(None)
+    } else {
+        value.parse().map(Some)
+    }
+}
+
+/// An effect that is currently active on a tank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActiveEffect {
+    pub typ: StatusEffectType,
+    pub remaining: f32,
+    pub strength: f32,
+    pub stacks: u8,
+}
+
+/// The result of applying an effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Applied {
+    New,
+    Stacked,
+    Immune,
+}
+
+/// What happened during a single update of the status effects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatusTick {
+    pub damage: u8,
+    pub expired: Vec<StatusEffectType>,
+}
+
+/// Keeps track of the status effects of a single tank.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatusEffects {
+    active: Vec<ActiveEffect>,
+    immunities: Vec<StatusEffectType>,
+    pending_damage: f32,
+}
+
+impl StatusEffects {
+    pub fn new() -> Self {
+        StatusEffects {
+            active: Vec::new(),
+            immunities: Vec::new(),
+            pending_damage: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn immunities(&self) -> &[StatusEffectType] {
+        self.immunities.as_slice()
+    }
+
+    /// Sets the effects the tank is immune to. Returns the active effects
+    /// that are removed because the tank became immune to them.
+    pub fn set_immunities(&mut self, immunities: Vec<StatusEffectType>) -> Vec<StatusEffectType> {
+        let removed = self
+            .active
+            .iter()
+            .map(|effect| effect.typ)
+            .filter(|typ| immunities.contains(typ))
+            .collect();
+        self.active
+            .retain(|effect| !immunities.contains(&effect.typ));
+        self.immunities = immunities;
+        removed
+    }
+
+    #[inline]
+    pub fn is_immune(&self, typ: StatusEffectType) -> bool {
+        self.immunities.contains(&typ)
+    }
+
+    #[inline]
+    pub fn get(&self, typ: StatusEffectType) -> Option<&ActiveEffect> {
+        self.active.iter().find(|effect| effect.typ == typ)
+    }
+
+    #[inline]
+    pub fn is_active(&self, typ: StatusEffectType) -> bool {
+        self.get(typ).is_some()
+    }
+
+    /// The effect that was applied most recently, which is the one that is
+    /// shown on the tank.
+    #[inline]
+    pub fn latest(&self) -> Option<StatusEffectType> {
+        self.active.last().map(|effect| effect.typ)
+    }
+
+    pub fn apply(&mut self, effect: StatusEffect) -> Applied {
+        if self.is_immune(effect.typ) || effect.duration <= 0.0 {
+            return Applied::Immune;
+        }
+
+        let index = match self.active.iter().position(|a| a.typ == effect.typ) {
+            Some(index) => index,
+            None => {
+                self.active.push(ActiveEffect {
+                    typ: effect.typ,
+                    remaining: effect.duration,
+                    strength: effect.strength,
+                    stacks: 1,
+                });
+                return Applied::New;
+            }
+        };
+
+        // the most recently applied effect is kept last
+        let mut active = self.active.remove(index);
+        match effect.typ.stacking() {
+            Stacking::Refresh => {
+                active.remaining = active.remaining.max(effect.duration);
+                active.strength = active.strength.max(effect.strength);
+            }
+            Stacking::Extend { max_duration } => {
+                active.remaining = (active.remaining + effect.duration).min(max_duration);
+                active.strength = active.strength.max(effect.strength);
+            }
+            Stacking::Intensify { max_stacks } => {
+                active.remaining = effect.duration;
+                active.strength = active.strength.max(effect.strength);
+                active.stacks = (active.stacks + 1).min(max_stacks);
+            }
+        }
+
+        self.active.push(active);
+        Applied::Stacked
+    }
+
+    /// Advances all effects by `delta` seconds and returns the damage taken
+    /// and the effects that have expired.
+    pub fn update(&mut self, delta: f32) -> StatusTick {
+        let mut tick = StatusTick::default();
+
+        for effect in self.active.iter_mut() {
+            let time = delta.min(effect.remaining);
+            if effect.typ == StatusEffectType::Burning {
+                self.pending_damage += effect.strength * effect.stacks as f32 * time;
+            }
+            effect.remaining -= time;
+        }
+
+        self.active.retain(|effect| {
+            if effect.remaining > 0.0 {
+                return true;
+            }
+            tick.expired.push(effect.typ);
+            false
+        });
+
+        let damage = self.pending_damage.floor().min(u8::MAX as f32);
+        self.pending_damage -= damage;
+        if !self.is_active(StatusEffectType::Burning) {
+            self.pending_damage = 0.0;
+        }
+
+        tick.damage = damage as u8;
+        tick
+    }
+
+    pub fn clear(&mut self) -> Vec<StatusEffectType> {
+        self.pending_damage = 0.0;
+        self.active.drain(..).map(|effect| effect.typ).collect()
+    }
+
+    /// Movement modifiers caused by the active effects.
+    pub fn drive_modifiers(&self) -> DriveModifiers {
+        let mut modifiers = DriveModifiers::default();
+        if let Some(slowed) = self.get(StatusEffectType::Slowed) {
+            let factor = (1.0 - slowed.strength).clamp(0.0, 1.0);
+            modifiers.max_speed *= factor;
+            modifiers.rotation_speed *= factor;
+        }
+        if let Some(boost) = self.get(StatusEffectType::SpeedBoost) {
+            modifiers.max_speed *= 1.0 + boost.strength.max(0.0);
+        }
+
+        modifiers
+    }
+
+    /// Factor applied to the cooldown of the tank's gun.
+    #[inline]
+    pub fn gun_cooldown_factor(&self) -> f64 {
+        self.get(StatusEffectType::RapidFire)
+            .map_or(1.0, |rapid_fire| {
+                (1.0 - rapid_fire.strength).clamp(0.1, 1.0) as f64
+            })
+    }
+
+    #[inline]
+    pub fn turret_locked(&self) -> bool {
+        self.is_active(StatusEffectType::Stunned)
+    }
+
+    #[inline]
+    pub fn weapons_disabled(&self) -> bool {
+        self.is_active(StatusEffectType::Emp)
+    }
+}
@@ -4,32 +4,89 @@
 
 use std::borrow::Borrow;
 
-use gdnative::api::{AnimatedSprite, AnimationPlayer, CollisionShape2D, Position2D, Sprite};
+use gdnative::api::{AnimationPlayer, Position2D, RandomNumberGenerator, Sprite};
 use gdnative::nativescript::Map;
 use gdnative::prelude::user_data::MapMut;
 use gdnative::prelude::*;
 
+use crate::damage::{Damage, DamageType, Shield};
+use crate::drivetrain::{DriveInput, DriveModifiers, DriveState, DriveStep, Drivetrain};
 use crate::enemies::*;
+use crate::events::{self, LootDropped, TankDamaged, TankDestroyed, TankFired};
+use crate::events::{TankHealthChanged, TankShieldChanged};
+use crate::obstacle::Obstacle;
+use crate::pickup::{LootTable, PickupEffect, PickupType};
 use crate::player::Player;
-use crate::utils::node::{get_node_as, NodeRef};
-use crate::utils::InstanceFrom;
+use crate::ramming::{self, Ram, RAM_COOLDOWN, RAM_MIN_SPEED};
+use crate::scenes::tanks::tank_tscn::{self, ANIM_INIT, ANIM_MUZZLE_FLASH};
+use crate::status::{parse_status_effect, Applied, StatusEffect, StatusEffectType, StatusEffects};
+use crate::terrain::TerrainLookup;
+use crate::units::{self, UnitKind};
+use crate::utils::node::{try_get_child_as, NodeRef};
+use crate::utils::{report_error, ExportGroup, ExportValue, InstanceFrom};
 
-// pub const ANIM_INIT: &str = "init";
-pub const ANIM_MUZZLE_FLASH: &str = "muzzle_flash";
-
-pub const SIGNAL_SHOOT: &str = "shoot";
 pub const SIGNAL_HEALTH_CHANGED: &str = "health_changed";
-pub const SIGNAL_DEAD: &str = "dead";
+pub const SIGNAL_SHIELD_CHANGED: &str = "shield_changed";
+pub const SIGNAL_AMMO_CHANGED: &str = "ammo_changed";
+pub const SIGNAL_STATUS_EFFECT_APPLIED: &str = "status_effect_applied";
+pub const SIGNAL_STATUS_EFFECT_EXPIRED: &str = "status_effect_expired";
+
+/// Group of all nodes that can take damage, status effects and pickups.
+pub const GROUP_DAMAGE_TAKER: &str = "damage_taker";
 
+/// The kind of a tank, which its events carry so handlers do not have to
+/// look at its node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ToVariant, FromVariant)]
+pub enum TankKind {
+    Player,
+    EnemyTank,
+    GunTurret,
+}
+
+impl TankKind {
+    #[inline]
+    pub fn is_player(&self) -> bool {
+        *self == Self::Player
+    }
+}
+
+#[derive(ExportGroup)]
 pub struct TankProperties {
+    #[export(no_default)]
     pub bullet_scene: Ref<PackedScene>,
-    pub max_speed: f32,
-    pub rotation_speed: f32,
+    #[export(group)]
+    pub drivetrain: Drivetrain,
+    #[export(range(0.0, 10.0, 0.05))]
     pub gun_cooldown: f64,
+    #[export]
     pub max_health: u8,
+    #[export]
+    pub max_ammo: u16,
+    #[export]
+    pub mass: f32,
+    #[export]
+    pub explosion_effect: Option<StatusEffect>,
+    #[export]
+    pub explosion_radius: f32,
+    #[export]
+    pub loot_table: LootTable,
+    /// How far the tank can see past obstacles.
+    #[export]
+    pub vision_radius: f32,
 
+    pub drive_state: DriveState,
+    pub drive_modifiers: DriveModifiers,
     pub velocity: Vector2,
+    pub knockback: Vector2,
+    pub ram_cooldown: f32,
     pub health: u8,
+    pub last_attacker: Option<i64>,
+    #[export(group)]
+    pub shield: Shield,
+    pub ammo: u16,
+    /// Registered by `BasicTank`, which reports the effects that expire when
+    /// the immunities change.
+    pub status_effects: StatusEffects,
     pub can_shoot: bool,
     pub alive: bool,
 
@@ -39,6 +96,8 @@ pub struct TankProperties {
     pub turret_muzzle_node: NodeRef<Position2D>,
     pub turret_flash_node: NodeRef<Sprite>,
     pub anim_player_node: NodeRef<AnimationPlayer>,
+
+    pub terrain: TerrainLookup,
 }
 
 impl TankProperties {
@@ -46,25 +105,91 @@ impl TankProperties {
         TankProperties {
             // exported
             bullet_scene: PackedScene::new().into_shared(),
-            max_speed: 200.0,
-            rotation_speed: 1.0,
+            drivetrain: Drivetrain::default(),
             gun_cooldown: 0.5,
             max_health: 100,
+            max_ammo: 0,
+            mass: 10.0,
+            explosion_effect: None,
+            explosion_radius: 0.0,
+            loot_table: LootTable::new(),
+            vision_radius: 600.0,
 
+            drive_state: DriveState::default(),
+            drive_modifiers: DriveModifiers::default(),
             velocity: Vector2::zero(),
+            knockback: Vector2::zero(),
+            ram_cooldown: 0.0,
             health: 100,
+            last_attacker: None,
+            shield: Shield::new(),
+            ammo: 0,
+            status_effects: StatusEffects::new(),
             can_shoot: true,
             alive: true,
 
             // child node(s)
-            body_node: NodeRef::new("Body"),
-            gun_timer_node: NodeRef::new("GunTimer"),
-            turret_node: NodeRef::new("Turret"),
-            turret_muzzle_node: NodeRef::new("Turret/Muzzle"),
-            turret_flash_node: NodeRef::new("Turret/Flash"),
-            anim_player_node: NodeRef::new("AnimationPlayer"),
+            body_node: NodeRef::of::<tank_tscn::BodyNode>(),
+            gun_timer_node: NodeRef::of::<tank_tscn::GunTimerNode>(),
+            turret_node: NodeRef::of::<tank_tscn::TurretNode>(),
+            turret_muzzle_node: NodeRef::of::<tank_tscn::TurretMuzzleNode>(),
+            turret_flash_node: NodeRef::of::<tank_tscn::TurretFlashNode>(),
+            anim_player_node: NodeRef::of::<tank_tscn::AnimationPlayerNode>(),
+
+            terrain: TerrainLookup::new(),
         }
     }
+
+    /// A tank without a maximum amount of ammo never runs out.
+    #[inline]
+    pub fn has_unlimited_ammo(&self) -> bool {
+        self.max_ammo == 0
+    }
+
+    /// Steps the drivetrain, with the current drive modifiers applied.
+    #[inline]
+    pub fn step_drivetrain(&mut self, input: DriveInput, delta: f32) -> DriveStep {
+        self.drivetrain
+            .modified(&self.drive_modifiers)
+            .step(&mut self.drive_state, input, delta)
+    }
+}
+
+/// What a hit did to a tank, see `hit`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// The damage that passed through the shield.
+    pub amount: u8,
+    pub shield_changed: bool,
+    /// Whether the hit took the last of the tank's health.
+    pub destroyed: bool,
+}
+
+/// Applies `damage` to the `shield` and `health` of a tank. A tank that is no
+/// longer `alive` is not hit at all, so a wreck that waits for its explosion
+/// to finish does not explode again.
+pub fn hit(alive: bool, health: &mut u8, shield: &mut Shield, damage: Damage) -> Option<Hit> {
+    if !alive {
+        return None;
+    }
+
+    let shield_value = shield.value();
+    let amount = shield.absorb(damage.amount, damage.typ);
+    *health = health.saturating_sub(amount);
+    Some(Hit {
+        amount,
+        shield_changed: shield.value() != shield_value,
+        destroyed: amount > 0 && *health == 0,
+    })
+}
+
+/// Adds `amount` to the `ammo` of a tank, up to `max_ammo`. Returns None when
+/// the tank has unlimited ammo or is already fully stocked.
+pub fn refill_ammo(ammo: u16, max_ammo: u16, amount: u16) -> Option<u16> {
+    if max_ammo == 0 || ammo >= max_ammo {
+        return None;
+    }
+    Some(ammo.saturating_add(amount).min(max_ammo))
 }
 
 impl Default for TankProperties {
@@ -81,133 +206,231 @@ where
     where
         <C as gdnative::prelude::NativeClass>::UserData: MapMut,
         <C as gdnative::prelude::NativeClass>::UserData: Map,
+        C: NativeClass<Base = KinematicBody2D>,
         Self: Sized,
     {
-        let default = TankProperties::new();
+        TankProperties::register(builder, |t: &C| t.props(), |t: &mut C| t.props_mut());
 
         builder
-            .add_property::<Ref<PackedScene>>("bullet_scene")
-            .with_setter(|t: &mut C, _, v: Ref<PackedScene>| t.props_mut().bullet_scene = v)
-            .with_getter(|t: &C, _| -> Ref<PackedScene> { t.props().bullet_scene.clone() })
-            .done();
-
-        builder
-            .add_property("max_speed")
-            .with_default(default.max_speed)
-            .with_setter(|t: &mut C, _, v: f32| t.props_mut().max_speed = v)
-            .with_getter(|t: &C, _| -> f32 { t.props().max_speed })
-            .done();
-
-        builder
-            .add_property("rotation_speed")
-            .with_default(default.rotation_speed)
-            .with_setter(|t: &mut C, _, v: f32| t.props_mut().rotation_speed = v)
-            .with_getter(|t: &C, _| -> f32 { t.props().rotation_speed })
-            .done();
-
-        builder
-            .add_property("gun_cooldown")
-            .with_default(default.gun_cooldown)
-            .with_setter(|t: &mut C, _, v: f64| t.props_mut().gun_cooldown = v)
-            .with_getter(|t: &C, _| -> f64 { t.props().gun_cooldown })
-            .done();
-
-        builder
-            .add_property("max_health")
-            .with_default(default.max_health)
-            .with_setter(|t: &mut C, _, v: u8| t.props_mut().max_health = v)
-            .with_getter(|t: &C, _| -> u8 { t.props().max_health })
+            .add_property::<StringArray>("status_immunities")
+            .with_setter(|t: &mut C, owner, value: StringArray| {
+                t.set_status_immunities(owner, parse_immunities(value));
+            })
+            .with_getter(|t: &C, _| immunities_to_export(t.props().status_effects.immunities()))
+            .with_default(StringArray::new())
             .done();
     }
 
     fn register_tank_signals(builder: &ClassBuilder<C>) {
-        let scene = Variant::from_object::<Ref<PackedScene>>(PackedScene::new().into_shared());
+        builder.add_signal(Signal {
+            name: SIGNAL_HEALTH_CHANGED,
+            args: &[SignalArgument {
+                name: "percentage",
+                default: Variant::from_f64(100.0),
+                export_info: ExportInfo::new(VariantType::F64),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+
+        builder.add_signal(Signal {
+            name: SIGNAL_SHIELD_CHANGED,
+            args: &[SignalArgument {
+                name: "percentage",
+                default: Variant::from_f64(100.0),
+                export_info: ExportInfo::new(VariantType::F64),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
 
         builder.add_signal(Signal {
-            name: SIGNAL_SHOOT,
+            name: SIGNAL_AMMO_CHANGED,
+            args: &[SignalArgument {
+                name: "ammo",
+                default: Variant::from_i64(0),
+                export_info: ExportInfo::new(VariantType::I64),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+
+        builder.add_signal(Signal {
+            name: SIGNAL_STATUS_EFFECT_APPLIED,
             args: &[
                 SignalArgument {
-                    name: "bullet",
-                    default: scene.clone(),
-                    export_info: ExportInfo::new(scene.get_type()),
-                    usage: PropertyUsage::DEFAULT,
-                },
-                SignalArgument {
-                    name: "position",
-                    default: Variant::from_vector2(Vector2::zero().borrow()),
-                    export_info: ExportInfo::new(VariantType::Vector2),
+                    name: "effect",
+                    default: Variant::from_str(""),
+                    export_info: ExportInfo::new(VariantType::GodotString),
                     usage: PropertyUsage::DEFAULT,
                 },
                 SignalArgument {
-                    name: "direction",
-                    default: Variant::from_vector2(Vector2::zero().borrow()),
-                    export_info: ExportInfo::new(VariantType::Vector2),
+                    name: "duration",
+                    default: Variant::from_f64(0.0),
+                    export_info: ExportInfo::new(VariantType::F64),
                     usage: PropertyUsage::DEFAULT,
                 },
             ],
         });
 
         builder.add_signal(Signal {
-            name: SIGNAL_HEALTH_CHANGED,
+            name: SIGNAL_STATUS_EFFECT_EXPIRED,
             args: &[SignalArgument {
-                name: "percentage",
-                default: Variant::from_f64(100.0),
-                export_info: ExportInfo::new(VariantType::F64),
+                name: "effect",
+                default: Variant::from_str(""),
+                export_info: ExportInfo::new(VariantType::GodotString),
                 usage: PropertyUsage::DEFAULT,
             }],
         });
-
-        builder.add_signal(Signal {
-            name: SIGNAL_DEAD,
-            args: &[],
-        });
     }
 
+    const KIND: TankKind;
+
     fn props(&self) -> &TankProperties;
     fn props_mut(&mut self) -> &mut TankProperties;
     fn control(&mut self, owner: TRef<KinematicBody2D>, delta: f32);
 
     #[inline]
-    fn emit_signal_shoot(
+    fn publish_tank_fired(
         &self,
         owner: &KinematicBody2D,
         bullet: &Ref<PackedScene>,
         pos: Vector2,
         dir: Vector2,
     ) {
-        owner.emit_signal(
-            SIGNAL_SHOOT,
-            &[
-                Variant::from_object(bullet),
-                Variant::from_vector2(pos.borrow()),
-                Variant::from_vector2(dir.borrow()),
-            ],
+        events::publish(
+            owner,
+            TankFired {
+                tank: owner.get_instance_id(),
+                kind: Self::KIND,
+                bullet: bullet.clone(),
+                position: pos,
+                direction: dir,
+            },
         );
     }
 
     #[inline]
     fn emit_signal_health_changed(&self, owner: &KinematicBody2D, percentage: f64) {
         owner.emit_signal(SIGNAL_HEALTH_CHANGED, &[Variant::from_f64(percentage)]);
+        events::publish(
+            owner,
+            TankHealthChanged {
+                tank: owner.get_instance_id(),
+                kind: Self::KIND,
+                health: percentage,
+            },
+        );
+    }
+
+    #[inline]
+    fn emit_signal_shield_changed(&self, owner: &KinematicBody2D, percentage: f64) {
+        owner.emit_signal(SIGNAL_SHIELD_CHANGED, &[Variant::from_f64(percentage)]);
+        events::publish(
+            owner,
+            TankShieldChanged {
+                tank: owner.get_instance_id(),
+                kind: Self::KIND,
+                shield: percentage,
+            },
+        );
     }
 
     #[inline]
-    fn emit_signal_dead(&self, owner: TRef<KinematicBody2D>) {
-        owner.emit_signal(SIGNAL_DEAD, &[]);
+    fn emit_signal_ammo_changed(&self, owner: &KinematicBody2D, ammo: u16) {
+        owner.emit_signal(SIGNAL_AMMO_CHANGED, &[Variant::from_i64(ammo as i64)]);
+    }
+
+    #[inline]
+    fn publish_loot_dropped(&self, owner: &KinematicBody2D, typ: PickupType, pos: Vector2) {
+        events::publish(
+            owner,
+            LootDropped {
+                pickup_type: typ.name().to_string(),
+                position: pos,
+            },
+        );
+    }
+
+    /// Publishes `TankDestroyed` with the instance id of the tank that caused
+    /// the last damage as the killer.
+    #[inline]
+    fn publish_tank_destroyed(&self, owner: TRef<KinematicBody2D>) {
+        events::publish(
+            owner.as_ref(),
+            TankDestroyed {
+                tank: owner.get_instance_id(),
+                kind: Self::KIND,
+                killer: self.props().last_attacker.unwrap_or(0),
+                position: owner.global_position(),
+            },
+        );
+    }
+
+    #[inline]
+    fn emit_signal_status_effect_applied(
+        &self,
+        owner: &KinematicBody2D,
+        typ: StatusEffectType,
+        duration: f32,
+    ) {
+        owner.emit_signal(
+            SIGNAL_STATUS_EFFECT_APPLIED,
+            &[
+                Variant::from_str(typ.name()),
+                Variant::from_f64(duration as f64),
+            ],
+        );
+    }
+
+    #[inline]
+    fn emit_signal_status_effect_expired(&self, owner: &KinematicBody2D, typ: StatusEffectType) {
+        owner.emit_signal(
+            SIGNAL_STATUS_EFFECT_EXPIRED,
+            &[Variant::from_str(typ.name())],
+        );
+    }
+
+    /// Steps the drivetrain and applies the resulting rotation and velocity
+    /// to the tank.
+    fn drive(&mut self, owner: TRef<KinematicBody2D>, input: DriveInput, delta: f32) -> DriveStep {
+        let props = self.props_mut();
+        let step = props.step_drivetrain(input, delta);
+
+        let rotation = owner.rotation() + step.rotation as f64;
+        owner.set_rotation(rotation);
+
+        props.velocity =
+            Vector2::new(step.velocity(delta), 0.0).rotated(Angle::radians(rotation as f32));
+        step
     }
 
     fn shoot(&mut self, owner: TRef<KinematicBody2D>) {
-        if !self.props().can_shoot {
+        let props = self.props();
+        if !props.can_shoot
+            || props.status_effects.weapons_disabled()
+            || !props.has_unlimited_ammo() && props.ammo == 0
+        {
             return;
         }
 
-        self.props_mut().can_shoot = false;
-        self.props()
-            .anim_player_node
+        let props = self.props_mut();
+        props.can_shoot = false;
+        if !props.has_unlimited_ammo() {
+            props.ammo -= 1;
+            let ammo = props.ammo;
+            self.emit_signal_ammo_changed(owner.as_ref(), ammo);
+        }
+
+        let anim_player = self.props().anim_player_node.get_ref();
+        anim_player.play(ANIM_MUZZLE_FLASH, -1.0, 1.0, false);
+        if let Some(typ) = self.props().status_effects.latest() {
+            anim_player.queue(typ.animation());
+        }
+        let props = self.props();
+        props
+            .gun_timer_node
             .get_ref()
-            .play(ANIM_MUZZLE_FLASH, -1.0, 1.0, false);
-        self.props().gun_timer_node.get_ref().start(0.0);
+            .start(props.gun_cooldown * props.status_effects.gun_cooldown_factor());
 
-        self.emit_signal_shoot(
+        self.publish_tank_fired(
             owner.as_ref(),
             self.props().bullet_scene.borrow(),
             self.props().turret_muzzle_node.get_ref().global_position(),
@@ -217,53 +440,335 @@ where
         );
     }
 
-    fn take_damage(&mut self, owner: TRef<KinematicBody2D>, amount: u8) {
-        let health = (self.props().health - amount).max(0);
-        self.props_mut().health = health;
+    fn take_damage(&mut self, owner: TRef<KinematicBody2D>, damage: Damage) {
+        let props = self.props_mut();
+        let hit = match hit(props.alive, &mut props.health, &mut props.shield, damage) {
+            Some(hit) => hit,
+            None => return,
+        };
+        if damage.source.is_some() {
+            props.last_attacker = damage.source;
+        }
+        if hit.shield_changed {
+            let percentage = props.shield.percentage();
+            self.emit_signal_shield_changed(owner.as_ref(), percentage);
+        }
+
+        events::publish(
+            owner.as_ref(),
+            TankDamaged {
+                tank: owner.get_instance_id(),
+                kind: Self::KIND,
+                amount: hit.amount,
+                damage_type: damage.typ.name().to_string(),
+                source: damage.source.unwrap_or(0),
+            },
+        );
+        if hit.amount == 0 {
+            return;
+        }
 
-        let health = health as f64 / self.props().max_health as f64;
+        let health = self.props().health as f64 / self.props().max_health as f64;
         self.emit_signal_health_changed(owner.as_ref(), health * 100.0);
 
-        if health <= 0.0 {
+        if hit.destroyed {
             self.explode(owner);
         }
     }
 
+    /// Applies a status effect, unless the tank is immune to it. Returns true
+    /// when the effect was applied.
+    fn apply_status_effect(&mut self, owner: TRef<KinematicBody2D>, effect: StatusEffect) -> bool {
+        if !self.props().alive {
+            return false;
+        }
+
+        let status_effects = &mut self.props_mut().status_effects;
+        if status_effects.apply(effect) == Applied::Immune {
+            return false;
+        }
+
+        let remaining = status_effects
+            .get(effect.typ)
+            .map_or(effect.duration, |active| active.remaining);
+
+        self.emit_signal_status_effect_applied(owner.as_ref(), effect.typ, remaining);
+        self.play_status_animation();
+        true
+    }
+
+    /// Sets the status effects the tank is immune to, the active effects it
+    /// becomes immune to expire.
+    fn set_status_immunities(
+        &mut self,
+        owner: TRef<KinematicBody2D>,
+        immunities: Vec<StatusEffectType>,
+    ) {
+        let removed = self.props_mut().status_effects.set_immunities(immunities);
+        for typ in removed.iter() {
+            self.emit_signal_status_effect_expired(owner.as_ref(), *typ);
+        }
+        if !removed.is_empty() {
+            self.play_status_animation();
+        }
+    }
+
+    /// Advances the status effects and applies the damage they cause.
+    fn update_status_effects(&mut self, owner: TRef<KinematicBody2D>, delta: f32) {
+        let tick = self.props_mut().status_effects.update(delta);
+        for typ in tick.expired.iter() {
+            self.emit_signal_status_effect_expired(owner.as_ref(), *typ);
+        }
+        if !tick.expired.is_empty() {
+            self.play_status_animation();
+        }
+        if tick.damage > 0 {
+            self.take_damage(owner, Damage::new(tick.damage, DamageType::Fire));
+        }
+    }
+
+    /// Applies the effect of a pickup. Returns false when the pickup is of no
+    /// use to the tank, so it is left for later.
+    fn collect_pickup(&mut self, owner: TRef<KinematicBody2D>, pickup: &PickupEffect) -> bool {
+        if !self.props().alive {
+            return false;
+        }
+
+        let props = self.props_mut();
+        match pickup.typ {
+            PickupType::Repair => {
+                if props.health >= props.max_health {
+                    return false;
+                }
+
+                props.health = props
+                    .health
+                    .saturating_add(pickup.amount as u8)
+                    .min(props.max_health);
+
+                let health = props.health as f64 / props.max_health as f64;
+                self.emit_signal_health_changed(owner.as_ref(), health * 100.0);
+            }
+            PickupType::Ammo => {
+                props.ammo = match refill_ammo(props.ammo, props.max_ammo, pickup.amount as u16) {
+                    Some(ammo) => ammo,
+                    None => return false,
+                };
+
+                let ammo = props.ammo;
+                self.emit_signal_ammo_changed(owner.as_ref(), ammo);
+            }
+            PickupType::Shield => {
+                if !props.shield.is_enabled() || !props.shield.recharge(pickup.amount as u8) {
+                    return false;
+                }
+
+                let percentage = props.shield.percentage();
+                self.emit_signal_shield_changed(owner.as_ref(), percentage);
+            }
+            PickupType::SpeedBoost => {
+                return self.apply_status_effect(
+                    owner,
+                    StatusEffect::new(StatusEffectType::SpeedBoost, pickup.duration, pickup.amount),
+                );
+            }
+            PickupType::RapidFire => {
+                return self.apply_status_effect(
+                    owner,
+                    StatusEffect::new(StatusEffectType::RapidFire, pickup.duration, pickup.amount),
+                );
+            }
+            PickupType::Weapon => match pickup.weapon_scene.as_ref() {
+                Some(scene) => props.bullet_scene = scene.clone(),
+                None => return false,
+            },
+        }
+
+        true
+    }
+
+    /// Regenerates the shield.
+    fn update_shield(&mut self, owner: TRef<KinematicBody2D>, delta: f32) {
+        let shield = &mut self.props_mut().shield;
+        if shield.update(delta) {
+            let percentage = shield.percentage();
+            self.emit_signal_shield_changed(owner.as_ref(), percentage);
+        }
+    }
+
+    /// Plays the animation of the most recently applied status effect, or
+    /// resets the tank's looks when there are no effects left.
+    fn play_status_animation(&self) {
+        let animation = match self.props().status_effects.latest() {
+            Some(typ) => typ.animation(),
+            None => ANIM_INIT,
+        };
+
+        let anim_player = self.props().anim_player_node.get_ref();
+        if anim_player.is_playing()
+            && anim_player.current_animation().to_string() == ANIM_MUZZLE_FLASH
+        {
+            anim_player.clear_queue();
+            anim_player.queue(animation);
+        } else {
+            anim_player.play(animation, -1.0, 1.0, false);
+        }
+    }
+
+    /// Applies a ram by another body and returns the mass of this tank, so
+    /// the other body can apply the collision to itself.
+    fn take_ram(&mut self, owner: TRef<KinematicBody2D>, ram: &Ram) -> f32 {
+        let response = ram.response(self.props().mass);
+
+        let props = self.props_mut();
+        props.ram_cooldown = RAM_COOLDOWN;
+
+        // the part of the push along the tank's tracks changes its speed,
+        // the rest pushes it sideways
+        let forward =
+            Vector2::new(1.0, 0.0).rotated(Angle::radians(owner.global_rotation() as f32));
+        let forward_push = forward.dot(response.push);
+        props.drive_state.speed += forward_push;
+        props.knockback += response.push - forward * forward_push;
+
+        if props.alive && response.damage > 0 {
+            self.take_damage(
+                owner,
+                Damage::new(response.damage, DamageType::Impact).with_source(ram.source),
+            );
+        }
+
+        self.props().mass
+    }
+
+    /// Rams the first tank that was hit hard enough during the last call to
+    /// `move_and_slide`.
+    fn detect_ram(&mut self, owner: TRef<KinematicBody2D>, velocity: Vector2) {
+        for i in 0..owner.get_slide_count() {
+            let collision = match owner.get_slide_collision(i) {
+                Some(collision) => collision,
+                None => continue,
+            };
+            let collision = unsafe { collision.assume_safe() };
+
+            let direction = -collision.normal();
+            let impact_speed = (velocity - collision.collider_velocity()).dot(direction);
+            if impact_speed < RAM_MIN_SPEED {
+                continue;
+            }
+
+            let target = match collision
+                .collider()
+                .and_then(|collider| unsafe { collider.assume_safe() }.cast::<Node>())
+            {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let ram = Ram::new(
+                self.props().mass,
+                impact_speed,
+                direction,
+                Some(owner.get_instance_id()),
+            );
+            if let Some(mass) = take_ram(target.claim(), &ram) {
+                self.take_ram(owner, &ram.reversed(mass, Some(target.get_instance_id())));
+                return;
+            }
+        }
+    }
+
     fn explode(&mut self, owner: TRef<KinematicBody2D>) {
         self.props_mut().alive = false;
         // owner.set_physics_process(false);
 
-        unsafe {
-            get_node_as::<CollisionShape2D>(owner.as_ref(), "CollisionShape2D")
-                .call_deferred("set_disabled", &[Variant::from_bool(true)]);
+        if let Some(shape) = report_error(try_get_child_as::<tank_tscn::CollisionShape2DNode>(
+            owner.as_ref(),
+        )) {
+            unsafe { shape.call_deferred("set_disabled", &[Variant::from_bool(true)]) };
         }
 
-        self.props().turret_node.get_ref().hide();
-        self.props().body_node.get_ref().hide();
+        if let Some(turret) = report_error(self.props().turret_node.try_get_ref()) {
+            turret.hide();
+        }
+        if let Some(body) = report_error(self.props().body_node.try_get_ref()) {
+            body.hide();
+        }
+
+        match report_error(try_get_child_as::<tank_tscn::ExplosionNode>(owner.as_ref())) {
+            Some(explosion) => {
+                explosion.show();
+                explosion.play(tank_tscn::ANIM_FIRE, false);
+            }
+            // the tank is destroyed once its explosion finishes
+            None => self._on_Explosion_animation_finished(owner),
+        }
 
-        let explosion = get_node_as::<AnimatedSprite>(owner.as_ref(), "Explosion");
-        explosion.show();
-        explosion.play("fire", false);
+        for typ in self.props_mut().status_effects.clear() {
+            self.emit_signal_status_effect_expired(owner.as_ref(), typ);
+        }
+        if let Some(effect) = self.props().explosion_effect {
+            apply_status_effect_around(owner, effect, self.props().explosion_radius);
+        }
+
+        let loot_table = &self.props().loot_table;
+        if !loot_table.is_empty() {
+            let rng = RandomNumberGenerator::new();
+            rng.randomize();
+            if let Some(typ) = loot_table.pick(rng.randf() as f32) {
+                self.publish_loot_dropped(owner.as_ref(), typ, owner.global_position());
+            }
+        }
     }
 
+    /// Finds the tank's child nodes. A tank of which any of these nodes is
+    /// missing, reports the errors and is disabled instead of crashing the
+    /// game.
     #[inline]
     fn _ready(&mut self, owner: TRef<KinematicBody2D>) {
-        let mut props = self.props_mut();
+        let props = self.props_mut();
 
+        let body = owner.upcast::<Node2D>();
         let owner = owner.as_ref();
-        props.body_node.get_from(owner);
-        props.turret_node.get_from(owner);
-        props.turret_muzzle_node.get_from(owner);
-        props.turret_flash_node.get_from(owner);
-        props.anim_player_node.get_from(owner);
+        let errors = [
+            props.body_node.try_get_from(owner).err(),
+            props.turret_node.try_get_from(owner).err(),
+            props.turret_muzzle_node.try_get_from(owner).err(),
+            props.turret_flash_node.try_get_from(owner).err(),
+            props.anim_player_node.try_get_from(owner).err(),
+            props.gun_timer_node.try_get_from(owner).err(),
+        ];
+        if errors.iter().any(Option::is_some) {
+            for err in errors.iter().flatten() {
+                godot_error!("{}", err);
+            }
 
+            props.alive = false;
+            owner.set_process(false);
+            owner.set_physics_process(false);
+            return;
+        }
+
+        props.terrain.find_ground(owner);
         props.health = props.max_health;
+        props.shield.reset();
+        props.ammo = props.max_ammo;
         props
             .gun_timer_node
-            .get_ref_or_from(owner)
+            .get_ref()
             .set_wait_time(props.gun_cooldown);
 
+        let kind = if Self::KIND.is_player() {
+            UnitKind::Player
+        } else {
+            UnitKind::Enemy
+        };
+        units::register(body, kind, Vector2::zero(), props.vision_radius);
+
         self.emit_signal_health_changed(owner, 100.0);
+        if self.props().shield.is_enabled() {
+            self.emit_signal_shield_changed(owner, 100.0);
+        }
     }
 
     #[inline]
@@ -272,15 +777,35 @@ where
             return;
         }
 
+        self.update_shield(owner, delta);
+        self.update_status_effects(owner, delta);
+        if !self.props().alive {
+            return;
+        }
+
+        let props = self.props_mut();
+        props.drive_modifiers = props.terrain.modifiers_at(owner.global_position())
+            * props.status_effects.drive_modifiers();
+
         self.control(owner, delta);
+
+        let props = self.props_mut();
+        props.knockback = ramming::damp_knockback(props.knockback, delta);
+        props.ram_cooldown = (props.ram_cooldown - delta).max(0.0);
+
+        let velocity = props.velocity + props.knockback;
         owner.move_and_slide(
-            self.props().velocity,
+            velocity,
             Vector2::zero(),
             false,
             4,
             std::f64::consts::FRAC_PI_4,
             true,
         );
+
+        if self.props().ram_cooldown <= 0.0 {
+            self.detect_ram(owner, velocity);
+        }
     }
 
     #[allow(non_snake_case)]
@@ -292,14 +817,14 @@ where
     #[allow(non_snake_case)]
     #[inline]
     fn _on_Explosion_animation_finished(&self, owner: TRef<KinematicBody2D>) {
-        owner.emit_signal(SIGNAL_DEAD, &[]);
+        self.publish_tank_destroyed(owner);
         owner.queue_free();
     }
 }
 
 #[inline]
-pub fn take_damage(target: Ref<Node>, damage: u8) {
-    if !unsafe { target.assume_safe() }.is_in_group("damage_taker") {
+pub fn take_damage(target: Ref<Node>, damage: Damage) {
+    if !unsafe { target.assume_safe() }.is_in_group(GROUP_DAMAGE_TAKER) {
         return;
     }
 
@@ -312,41 +837,243 @@ pub fn take_damage(target: Ref<Node>, damage: u8) {
     if GunTurret::try_take_damage(target, damage) {
         return;
     }
+    if Obstacle::try_take_damage(target, damage) {
+        return;
+    }
 
-    panic!(
+    godot_error!(
         "Cannot take damage, `target` {} is not checked",
         unsafe { target.assume_safe() }.name()
+    );
+}
+
+#[inline]
+fn immunities_to_export(immunities: &[StatusEffectType]) -> StringArray {
+    StringArray::from_vec(
+        immunities
+            .iter()
+            .map(|typ| GodotString::from_str(typ.name()))
+            .collect(),
     )
 }
 
+fn parse_immunities(value: StringArray) -> Vec<StatusEffectType> {
+    let mut immunities = Vec::new();
+    for name in value.read().iter() {
+        match name.to_string().parse::<StatusEffectType>() {
+            Ok(typ) => immunities.push(typ),
+            Err(err) => godot_warn!("{}", err),
+        }
+    }
+    immunities
+}
+
+impl ExportValue for Option<StatusEffect> {
+    type Export = String;
+
+    fn to_export(&self) -> String {
+        self.map(|effect| effect.to_string()).unwrap_or_default()
+    }
+
+    fn set_export(&mut self, value: String) {
+        *self = parse_status_effect(value.as_str()).unwrap_or_else(|err| {
+            godot_warn!("{}", err);
+            None
+        })
+    }
+}
+
+/// Lets `target` collect a pickup when it is a tank. Returns true when the
+/// pickup was collected.
+#[inline]
+pub fn collect_pickup(target: Ref<Node>, pickup: &PickupEffect) -> bool {
+    if !unsafe { target.assume_safe() }.is_in_group(GROUP_DAMAGE_TAKER) {
+        return false;
+    }
+
+    Player::try_collect_pickup(target, pickup)
+        .or_else(|| EnemyTank::try_collect_pickup(target, pickup))
+        .or_else(|| GunTurret::try_collect_pickup(target, pickup))
+        .unwrap_or(false)
+}
+
+/// Applies `effect` to `target` when it is a tank. Returns true when the
+/// effect was applied.
+#[inline]
+pub fn apply_status_effect(target: Ref<Node>, effect: StatusEffect) -> bool {
+    if !unsafe { target.assume_safe() }.is_in_group(GROUP_DAMAGE_TAKER) {
+        return false;
+    }
+
+    Player::try_apply_status_effect(target, effect)
+        .or_else(|| EnemyTank::try_apply_status_effect(target, effect))
+        .or_else(|| GunTurret::try_apply_status_effect(target, effect))
+        .unwrap_or(false)
+}
+
+/// Applies `effect` to all tanks, other than `origin` itself, that are within
+/// `radius` of `origin`.
+pub fn apply_status_effect_around(
+    origin: TRef<KinematicBody2D>,
+    effect: StatusEffect,
+    radius: f32,
+) {
+    let tree = match origin.get_tree() {
+        Some(tree) => unsafe { tree.assume_safe() },
+        None => return,
+    };
+
+    let position = origin.global_position();
+    for node in tree.get_nodes_in_group(GROUP_DAMAGE_TAKER).iter() {
+        let node = match node.try_to_object::<Node2D>() {
+            Some(node) => node,
+            None => continue,
+        };
+
+        let target = unsafe { node.assume_safe() };
+        if target.get_instance_id() == origin.get_instance_id()
+            || target.global_position().distance_to(position) > radius
+        {
+            continue;
+        }
+
+        apply_status_effect(target.upcast::<Node>().claim(), effect);
+    }
+}
+
+/// Applies `ram` to `target` when it is a tank, and returns the target's mass.
+#[inline]
+pub fn take_ram(target: Ref<Node>, ram: &Ram) -> Option<f32> {
+    if !unsafe { target.assume_safe() }.is_in_group(GROUP_DAMAGE_TAKER) {
+        return None;
+    }
+
+    Player::try_take_ram(target, ram)
+        .or_else(|| EnemyTank::try_take_ram(target, ram))
+        .or_else(|| GunTurret::try_take_ram(target, ram))
+}
+
 pub trait DamageTaker<C>:
     InstanceFrom<C, gdnative::prelude::KinematicBody2D> + BasicTank<C>
 where
     C: NativeClass<Base = gdnative::prelude::KinematicBody2D> + BasicTank<C>,
 {
     #[inline]
-    fn try_take_damage(node: Ref<Node>, damage: u8) -> bool
+    fn try_take_damage(node: Ref<Node>, damage: Damage) -> bool
     where
         <C as gdnative::prelude::NativeClass>::UserData: MapMut,
     {
-        let target = Self::try_instance_from(node);
-        if target.is_none() {
-            return false;
-        }
+        let target = match Self::try_instance_from(node) {
+            Ok(target) => target,
+            Err(_) => return false,
+        };
 
-        target
-            .unwrap()
+        if let Err(err) = target
             .borrow()
-            .map_mut(|target, node| {
-                target.take_damage(node, damage);
-                true
-            })
+            .map_mut(|target, node| target.take_damage(node, damage))
+        {
+            godot_error!(
+                "Failed to apply damage to {}: {:?}",
+                unsafe { node.assume_safe() }.name(),
+                err
+            );
+        }
+        true
+    }
+
+    #[inline]
+    fn try_take_ram(node: Ref<Node>, ram: &Ram) -> Option<f32>
+    where
+        <C as gdnative::prelude::NativeClass>::UserData: MapMut,
+    {
+        Self::try_instance_from(node)
+            .ok()?
+            .map_mut(|target, node| target.take_ram(node, ram))
+            .ok()
+    }
+
+    #[inline]
+    fn try_apply_status_effect(node: Ref<Node>, effect: StatusEffect) -> Option<bool>
+    where
+        <C as gdnative::prelude::NativeClass>::UserData: MapMut,
+    {
+        Self::try_instance_from(node)
+            .ok()?
+            .map_mut(|target, node| target.apply_status_effect(node, effect))
             .ok()
-            .unwrap_or_else(|| {
-                panic!(
-                    "Failed to apply damage to {}",
-                    unsafe { node.assume_safe() }.name()
-                )
+    }
+
+    #[inline]
+    fn try_collect_pickup(node: Ref<Node>, pickup: &PickupEffect) -> Option<bool>
+    where
+        <C as gdnative::prelude::NativeClass>::UserData: MapMut,
+    {
+        Self::try_instance_from(node)
+            .ok()?
+            .map_mut(|target, node| target.collect_pickup(node, pickup))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destroyed_tank_is_not_hit_again() {
+        let mut health = 10;
+        let mut shield = Shield::new();
+        let damage = Damage::new(25, DamageType::Kinetic);
+
+        let first = hit(true, &mut health, &mut shield, damage);
+        assert_eq!(
+            first,
+            Some(Hit {
+                amount: 25,
+                shield_changed: false,
+                destroyed: true,
             })
+        );
+        assert_eq!(health, 0);
+
+        // `explode` marks the tank as no longer alive
+        assert_eq!(hit(false, &mut health, &mut shield, damage), None);
+        assert_eq!(health, 0);
+    }
+
+    #[test]
+    fn hit_absorbed_by_the_shield_does_not_destroy() {
+        let mut health = 10;
+        let mut shield = Shield::new();
+        shield.capacity = 50;
+        shield.reset();
+
+        let result = hit(
+            true,
+            &mut health,
+            &mut shield,
+            Damage::new(20, DamageType::Kinetic),
+        );
+        assert_eq!(
+            result,
+            Some(Hit {
+                amount: 0,
+                shield_changed: true,
+                destroyed: false,
+            })
+        );
+        assert_eq!((health, shield.value()), (10, 30));
+    }
+
+    #[test]
+    fn ammo_refills_up_to_max_ammo() {
+        assert_eq!(refill_ammo(5, 20, 10), Some(15));
+        assert_eq!(refill_ammo(15, 20, 10), Some(20));
+        assert_eq!(refill_ammo(20, 20, 10), None);
+    }
+
+    #[test]
+    fn unlimited_ammo_is_not_refilled() {
+        assert_eq!(refill_ammo(0, 0, 10), None);
     }
 }
@@ -0,0 +1,180 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Builds the ground TileSet from its definition, see `tiles.rs`. In the
+//! editor the TileSet is made by checking `make` of the `TileSetMaker` node,
+//! without the editor by running its scene, which quits when it is done:
+//!
+//! ```text
+//! godot --no-window --path godot res://terrain/TileSetMaker.tscn
+//! ```
+//!
+//! The terrain type of a tile is stored as its name, so the names the editor
+//! shows are those of the terrain types instead of names that describe the
+//! tiles. Renaming a tile in the editor changes its terrain type, and a
+//! TileSet that is made again loses those changes.
+
+use gdnative::api::{
+    ConcavePolygonShape2D, ConvexPolygonShape2D, Engine, File, NavigationPolygon, ResourceLoader,
+    ResourceSaver, Shape2D, TileSet,
+};
+use gdnative::nativescript::property::{EnumHint, StringHint};
+use gdnative::prelude::*;
+
+use super::{TileSetDef, RES_TILE_DEFINITION};
+
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct TileSetMaker {
+    /// Path of the tile definition file.
+    definition: String,
+}
+
+#[methods]
+impl TileSetMaker {
+    fn register(builder: &ClassBuilder<Self>) {
+        builder
+            .add_property::<String>("definition")
+            .with_default(String::from(RES_TILE_DEFINITION))
+            .with_hint(StringHint::File(EnumHint::new(vec![String::from(
+                "*.tiles",
+            )])))
+            .with_setter(|t: &mut TileSetMaker, _, definition: String| t.definition = definition)
+            .with_getter(|t: &TileSetMaker, _| -> String { t.definition.clone() })
+            .done();
+
+        // works as a button in the editor, it is never checked
+        builder
+            .add_property::<bool>("make")
+            .with_default(false)
+            .with_setter(|t: &mut TileSetMaker, _, make: bool| {
+                if make && Engine::godot_singleton().is_editor_hint() {
+                    t.report(t.make());
+                }
+            })
+            .with_getter(|_, _| false)
+            .done();
+    }
+
+    fn new(_owner: TRef<Node>) -> Self {
+        TileSetMaker {
+            definition: String::from(RES_TILE_DEFINITION),
+        }
+    }
+
+    #[export]
+    fn _ready(&self, owner: TRef<Node>) {
+        if Engine::godot_singleton().is_editor_hint() {
+            return;
+        }
+
+        let code = if self.report(self.make()) { 0 } else { 1 };
+        if let Some(tree) = owner.get_tree() {
+            unsafe { tree.assume_safe() }.quit(code);
+        }
+    }
+
+    /// Builds the TileSet and saves it, returns the path it is saved to.
+    pub fn make(&self) -> Result<String, String> {
+        let path = self.definition.as_str();
+        let file = File::new();
+        file.open(path, File::READ)
+            .map_err(|err| format!("Failed to open `{}`: {:?}", path, err))?;
+        let text = file.get_as_text().to_string();
+        file.close();
+
+        let def = text
+            .parse::<TileSetDef>()
+            .map_err(|err| format!("Failed to read `{}`, {}", path, err))?;
+        let texture = ResourceLoader::godot_singleton()
+            .load(def.texture.as_str(), "Texture", false)
+            .and_then(|resource| resource.cast::<Texture>())
+            .ok_or_else(|| format!("Failed to load `{}`", def.texture))?;
+        let size = unsafe { texture.assume_safe() }.get_size();
+        let errors = def.check(size);
+        if !errors.is_empty() {
+            return Err(format!("{}: {}", path, errors.join(", ")));
+        }
+
+        let tile_set = TileSet::new();
+        for tile in def.tiles(size) {
+            tile_set.create_tile(tile.id);
+            tile_set.tile_set_name(tile.id, tile.terrain.name());
+            tile_set.tile_set_texture(tile.id, texture.clone());
+            tile_set.tile_set_region(tile.id, tile.region);
+
+            for outline in tile.collision.iter() {
+                tile_set.tile_add_shape(
+                    tile.id,
+                    collision_shape(outline),
+                    Transform2D::identity(),
+                    false,
+                    Vector2::zero(),
+                );
+            }
+            if !tile.navigation.is_empty() {
+                let navigation = NavigationPolygon::new();
+                for outline in tile.navigation.iter() {
+                    navigation.add_outline(Vector2Array::from_slice(outline));
+                }
+                navigation.make_polygons_from_outlines();
+                tile_set.tile_set_navigation_polygon(tile.id, navigation);
+            }
+        }
+
+        ResourceSaver::godot_singleton()
+            .save(def.output.as_str(), tile_set, 0)
+            .map_err(|err| format!("Failed to save `{}`: {:?}", def.output, err))?;
+        Ok(def.output)
+    }
+
+    /// Prints the result of `make`, returns whether it succeeded.
+    fn report(&self, result: Result<String, String>) -> bool {
+        match result {
+            Ok(output) => {
+                godot_print!("Saved the TileSet to `{}`", output);
+                true
+            }
+            Err(err) => {
+                godot_error!("{}", err);
+                false
+            }
+        }
+    }
+}
+
+/// A convex shape when `outline` is convex, a concave one otherwise.
+fn collision_shape(outline: &[Vector2]) -> Ref<Shape2D> {
+    if is_convex(outline) {
+        let shape = ConvexPolygonShape2D::new();
+        shape.set_points(Vector2Array::from_slice(outline));
+        return shape.upcast::<Shape2D>().into_shared();
+    }
+
+    let mut segments = Vector2Array::new();
+    for (i, point) in outline.iter().enumerate() {
+        segments.push(*point);
+        segments.push(outline[(i + 1) % outline.len()]);
+    }
+    let shape = ConcavePolygonShape2D::new();
+    shape.set_segments(segments);
+    shape.upcast::<Shape2D>().into_shared()
+}
+
+/// Whether all corners of `outline` turn the same way.
+fn is_convex(outline: &[Vector2]) -> bool {
+    let turns = (0..outline.len()).map(|i| {
+        let a = outline[i];
+        let b = outline[(i + 1) % outline.len()];
+        let c = outline[(i + 2) % outline.len()];
+        (b - a).cross(c - b)
+    });
+    let (mut left, mut right) = (false, false);
+    for turn in turns {
+        left |= turn > 0.0;
+        right |= turn < 0.0;
+    }
+    !(left && right)
+}
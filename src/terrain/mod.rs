@@ -0,0 +1,167 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use gdnative::api::TileMap;
+use gdnative::prelude::*;
+
+use crate::drivetrain::DriveModifiers;
+use crate::utils::node::NodeRef;
+
+pub use maker::*;
+pub use tiles::*;
+
+mod maker;
+mod tiles;
+
+/// Path of the ground `TileMap`, relative to the map scene.
+pub const GROUND_NODE: &str = "Ground";
+
+/// The kind of surface of a terrain tile. The type of a tile is stored as the
+/// tile's name in the TileSet created by `TileSetMaker`, as Godot 3 tiles
+/// have no other data of their own. The downside is that tiles cannot have a
+/// descriptive name of their own, the editor lists them by terrain type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TerrainType {
+    Road,
+    Grass,
+    Sand,
+    Mud,
+    Water,
+}
+
+impl TerrainType {
+    #[inline]
+    pub fn all() -> Vec<TerrainType> {
+        vec![Self::Road, Self::Grass, Self::Sand, Self::Mud, Self::Water]
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Road => "road",
+            Self::Grass => "grass",
+            Self::Sand => "sand",
+            Self::Mud => "mud",
+            Self::Water => "water",
+        }
+    }
+
+    #[inline]
+    pub fn modifiers(&self) -> DriveModifiers {
+        match self {
+            Self::Road => DriveModifiers {
+                max_speed: 1.2,
+                traction: 1.0,
+                rotation_speed: 1.0,
+            },
+            Self::Grass => DriveModifiers {
+                max_speed: 1.0,
+                traction: 0.9,
+                rotation_speed: 1.0,
+            },
+            Self::Sand => DriveModifiers {
+                max_speed: 0.75,
+                traction: 0.7,
+                rotation_speed: 0.85,
+            },
+            Self::Mud => DriveModifiers {
+                max_speed: 0.5,
+                traction: 0.5,
+                rotation_speed: 0.7,
+            },
+            Self::Water => DriveModifiers {
+                max_speed: 0.3,
+                traction: 0.3,
+                rotation_speed: 0.5,
+            },
+        }
+    }
+}
+
+impl FromStr for TerrainType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|typ| typ.name().eq(s))
+            .ok_or_else(|| format!("unknown terrain type `{}`", s))
+    }
+}
+
+/// Looks up the terrain type below a position on the ground `TileMap` of the
+/// current map.
+pub struct TerrainLookup {
+    ground_node: NodeRef<TileMap>,
+    types: HashMap<i64, TerrainType>,
+}
+
+impl TerrainLookup {
+    pub fn new() -> Self {
+        TerrainLookup {
+            ground_node: NodeRef::new(GROUND_NODE),
+            types: HashMap::new(),
+        }
+    }
+
+    /// Finds the ground `TileMap` in the current scene and reads the terrain
+    /// type of each of its tiles. Tiles with a name that is not a terrain type
+    /// are reported and have no effect on driving.
+    pub fn find_ground(&mut self, owner: &Node) {
+        let scene = owner
+            .get_tree()
+            .and_then(|tree| unsafe { tree.assume_safe() }.current_scene());
+
+        let ground = match scene {
+            Some(scene) => self
+                .ground_node
+                .try_get_from(unsafe { scene.assume_safe() }.as_ref())
+                .ok(),
+            None => None,
+        };
+        let tile_set = ground.and_then(|ground| unsafe { ground.assume_safe() }.tileset());
+
+        self.types.clear();
+        if let Some(tile_set) = tile_set {
+            let tile_set = unsafe { tile_set.assume_safe() };
+            for id in tile_set.get_tiles_ids().iter() {
+                let id = id.to_i64();
+                match tile_set.tile_get_name(id).to_string().parse() {
+                    Ok(typ) => {
+                        self.types.insert(id, typ);
+                    }
+                    Err(err) => godot_warn!("Tile {} of the ground: {}", id, err),
+                }
+            }
+        }
+    }
+
+    /// Returns the terrain type at `global_position`, or `None` when there is
+    /// no ground or no tile at that position.
+    pub fn terrain_at(&self, global_position: Vector2) -> Option<TerrainType> {
+        if !self.ground_node.has_ref() {
+            return None;
+        }
+
+        let ground = self.ground_node.get_ref();
+        let cell = ground.world_to_map(ground.to_local(global_position));
+        self.types.get(&ground.get_cellv(cell)).copied()
+    }
+
+    #[inline]
+    pub fn modifiers_at(&self, global_position: Vector2) -> DriveModifiers {
+        self.terrain_at(global_position)
+            .map(|typ| typ.modifiers())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for TerrainLookup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,448 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! The definition of the ground TileSet, which `TileSetMaker` builds. The
+//! texture is sliced into tiles of the same size, with ids `x + y * columns`.
+//! The definition is a versioned text file, like the obstacle atlas:
+//!
+//! ```text
+//! tiles 1                       format version, always the first record
+//! texture res://terrain/terrainTiles_retina.png
+//! output res://terrain/terrain_tiles.tres
+//! size 128 128                  size of a tile, in pixels
+//! columns 10                    columns of tile ids, at least those of the texture
+//! default grass                 terrain type of tiles that are not listed
+//! terrain sand 20 30            terrain type and the ids of its tiles
+//! navigation grass full         outline of a terrain type or a tile id
+//! collision 27 0,0 128,0 64,64
+//! ```
+//!
+//! Outlines are either `full`, the whole tile, or at least three points
+//! within the tile, so `size` comes before them. A terrain type or tile may
+//! have several collision and navigation outlines, those of a tile id replace
+//! those of its terrain type. Lines starting with `#` are comments.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use gdnative::prelude::*;
+
+use super::TerrainType;
+use crate::utils::{read_records, RecordError};
+
+/// Version of the tile definition files that can be read.
+pub const TILES_VERSION: u32 = 1;
+
+/// The definition of the ground TileSet.
+pub const RES_TILE_DEFINITION: &str = "res://terrain/terrain_tiles.tiles";
+
+/// What the outlines of a definition apply to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileKey {
+    Terrain(TerrainType),
+    Tile(i64),
+}
+
+impl FromStr for TileKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<i64>() {
+            Ok(id) if id >= 0 => Ok(Self::Tile(id)),
+            Ok(_) => Err(format!("invalid tile id `{}`", s)),
+            Err(_) => s.parse().map(Self::Terrain),
+        }
+    }
+}
+
+/// An outline of a collision or navigation polygon, in pixels of the tile.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outline {
+    Full,
+    Points(Vec<Vector2>),
+}
+
+impl Outline {
+    /// The points of the outline on a tile of `size`.
+    pub fn points(&self, size: Vector2) -> Vec<Vector2> {
+        match self {
+            Self::Full => vec![
+                Vector2::zero(),
+                Vector2::new(size.x, 0.0),
+                size,
+                Vector2::new(0.0, size.y),
+            ],
+            Self::Points(points) => points.clone(),
+        }
+    }
+}
+
+/// A tile of the TileSet, with its outlines on the tile.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileDef {
+    pub id: i64,
+    /// Region of the texture.
+    pub region: Rect2,
+    pub terrain: TerrainType,
+    pub collision: Vec<Vec<Vector2>>,
+    pub navigation: Vec<Vec<Vector2>>,
+}
+
+/// The records of a tile definition file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileSetDef {
+    /// Path of the texture the tiles are sliced from.
+    pub texture: String,
+    /// Path the TileSet is saved to.
+    pub output: String,
+    pub tile_size: Vector2,
+    pub columns: i64,
+    pub default_terrain: TerrainType,
+    /// Terrain types of the tiles that do not have the default.
+    pub terrain: BTreeMap<i64, TerrainType>,
+    pub collision: Vec<(TileKey, Outline)>,
+    pub navigation: Vec<(TileKey, Outline)>,
+}
+
+impl Default for TileSetDef {
+    fn default() -> Self {
+        TileSetDef {
+            texture: String::new(),
+            output: String::new(),
+            tile_size: Vector2::new(128.0, 128.0),
+            columns: 10,
+            default_terrain: TerrainType::Grass,
+            terrain: BTreeMap::new(),
+            collision: Vec::new(),
+            navigation: Vec::new(),
+        }
+    }
+}
+
+impl TileSetDef {
+    #[inline]
+    pub fn terrain_of(&self, id: i64) -> TerrainType {
+        self.terrain
+            .get(&id)
+            .copied()
+            .unwrap_or(self.default_terrain)
+    }
+
+    /// The tiles of a texture of `texture_size`, by id. Parts of the texture
+    /// that are smaller than a tile are left out.
+    pub fn tiles(&self, texture_size: Vector2) -> Vec<TileDef> {
+        let (columns, rows) = self.grid(texture_size);
+        let mut tiles = Vec::new();
+        for y in 0..rows {
+            for x in 0..columns.min(self.columns) {
+                let id = x + y * self.columns;
+                let terrain = self.terrain_of(id);
+                tiles.push(TileDef {
+                    id,
+                    region: Rect2::new(
+                        Point2::new(x as f32 * self.tile_size.x, y as f32 * self.tile_size.y),
+                        self.tile_size.to_size(),
+                    ),
+                    terrain,
+                    collision: self.outlines(&self.collision, id, terrain),
+                    navigation: self.outlines(&self.navigation, id, terrain),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// Describes the problems of the definition with a texture of
+    /// `texture_size`: ids that collide because the texture has more columns
+    /// than the definition, and tile ids that are not in the texture.
+    pub fn check(&self, texture_size: Vector2) -> Vec<String> {
+        let (columns, rows) = self.grid(texture_size);
+        let mut errors = Vec::new();
+        if columns > self.columns {
+            errors.push(format!(
+                "`{}` has {} columns of tiles, but ids have {}",
+                self.texture, columns, self.columns
+            ));
+        }
+
+        let exists = |id: i64| id % self.columns < columns && id / self.columns < rows;
+        let ids = self.terrain.keys().copied().chain(
+            self.collision
+                .iter()
+                .chain(self.navigation.iter())
+                .filter_map(|(key, _)| match key {
+                    TileKey::Tile(id) => Some(*id),
+                    TileKey::Terrain(_) => None,
+                }),
+        );
+        for id in ids.filter(|id| !exists(*id)) {
+            errors.push(format!("tile {} is not in `{}`", id, self.texture));
+        }
+        errors
+    }
+
+    #[inline]
+    fn grid(&self, texture_size: Vector2) -> (i64, i64) {
+        (
+            (texture_size.x / self.tile_size.x) as i64,
+            (texture_size.y / self.tile_size.y) as i64,
+        )
+    }
+
+    fn outlines(
+        &self,
+        outlines: &[(TileKey, Outline)],
+        id: i64,
+        terrain: TerrainType,
+    ) -> Vec<Vec<Vector2>> {
+        let of = |key: TileKey| {
+            outlines
+                .iter()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, outline)| outline.points(self.tile_size))
+                .collect::<Vec<_>>()
+        };
+        let tile = of(TileKey::Tile(id));
+        if tile.is_empty() {
+            of(TileKey::Terrain(terrain))
+        } else {
+            tile
+        }
+    }
+}
+
+impl FromStr for TileSetDef {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut def = TileSetDef::default();
+        let (_, records) = read_records(s, "tiles", TILES_VERSION)?;
+
+        for mut record in records {
+            match record.keyword {
+                "texture" => def.texture = record.text("texture")?,
+                "output" => def.output = record.text("output")?,
+                "size" => {
+                    def.tile_size = record.vector("size")?;
+                    if def.tile_size.x < 1.0 || def.tile_size.y < 1.0 {
+                        return Err(record.error(String::from("tiles are at least 1x1")));
+                    }
+                }
+                "columns" => {
+                    def.columns = record.number("columns")?;
+                    if def.columns < 1 {
+                        return Err(record.error(String::from("there is at least one column")));
+                    }
+                }
+                "default" => def.default_terrain = record.parse("terrain type")?,
+                "terrain" => {
+                    let terrain = record.parse::<TerrainType>("terrain type")?;
+                    for field in record.remaining() {
+                        let id = field
+                            .parse::<i64>()
+                            .ok()
+                            .filter(|id| *id >= 0)
+                            .ok_or_else(|| record.error(format!("invalid tile id `{}`", field)))?;
+                        if def.terrain.insert(id, terrain).is_some() {
+                            return Err(record.error(format!("tile {} has two terrain types", id)));
+                        }
+                    }
+                }
+                "collision" | "navigation" => {
+                    let key = record.parse::<TileKey>("terrain type or tile id")?;
+                    let outline = outline(record.remaining(), def.tile_size)
+                        .map_err(|message| record.error(message))?;
+                    if record.keyword == "collision" {
+                        def.collision.push((key, outline));
+                    } else {
+                        def.navigation.push((key, outline));
+                    }
+                }
+                _ => return Err(record.error(format!("unknown record `{}`", record.keyword))),
+            }
+            record.end()?;
+        }
+
+        if def.texture.is_empty() || def.output.is_empty() {
+            return Err(RecordError::at_end(
+                s,
+                String::from("the definition needs a texture and an output"),
+            ));
+        }
+        Ok(def)
+    }
+}
+
+/// Reads `fields` as an outline on a tile of `size`.
+fn outline(fields: Vec<&str>, size: Vector2) -> Result<Outline, String> {
+    if fields == ["full"] {
+        return Ok(Outline::Full);
+    }
+
+    let mut points = Vec::new();
+    for field in fields {
+        let point = field
+            .split_once(',')
+            .and_then(|(x, y)| Some(Vector2::new(x.parse().ok()?, y.parse().ok()?)))
+            .ok_or_else(|| format!("invalid point `{}`", field))?;
+        if point.x < 0.0 || point.y < 0.0 || point.x > size.x || point.y > size.y {
+            return Err(format!("point `{}` lies outside of the tile", field));
+        }
+        points.push(point);
+    }
+    if points.len() < 3 {
+        return Err(String::from("an outline has at least three points"));
+    }
+    Ok(Outline::Points(points))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILES: &str = "\
+tiles 1
+texture res://tiles.png
+output res://tiles.tres
+size 64 64
+columns 3
+default grass
+terrain road 1 2
+terrain water 4
+# the water is not driven over
+navigation grass full
+navigation road full
+collision water full
+collision 5 0,0 64,0 32,32
+";
+
+    fn error(text: &str) -> String {
+        text.parse::<TileSetDef>().unwrap_err().message
+    }
+
+    fn with(line: &str) -> String {
+        format!("{}{}\n", TILES, line)
+    }
+
+    #[test]
+    fn definition_is_read() {
+        let def = TILES.parse::<TileSetDef>().unwrap();
+        assert_eq!(
+            (def.texture.as_str(), def.output.as_str()),
+            ("res://tiles.png", "res://tiles.tres")
+        );
+        assert_eq!((def.tile_size, def.columns), (Vector2::new(64.0, 64.0), 3));
+        assert_eq!(def.terrain_of(1), TerrainType::Road);
+        assert_eq!(def.terrain_of(4), TerrainType::Water);
+        assert_eq!(def.terrain_of(0), TerrainType::Grass);
+        assert_eq!(
+            def.navigation[0],
+            (TileKey::Terrain(TerrainType::Grass), Outline::Full)
+        );
+        assert_eq!(
+            def.collision[1],
+            (
+                TileKey::Tile(5),
+                Outline::Points(vec![
+                    Vector2::zero(),
+                    Vector2::new(64.0, 0.0),
+                    Vector2::new(32.0, 32.0)
+                ])
+            )
+        );
+    }
+
+    #[test]
+    fn tiles_take_the_outlines_of_their_id_or_terrain() {
+        let def = TILES.parse::<TileSetDef>().unwrap();
+        let tiles = def.tiles(Vector2::new(192.0, 150.0));
+        assert_eq!(
+            tiles.iter().map(|t| t.id).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4, 5]
+        );
+
+        let full = Outline::Full.points(def.tile_size);
+        assert_eq!(tiles[1].navigation, vec![full.clone()]);
+        assert!(tiles[1].collision.is_empty());
+        assert_eq!(tiles[4].collision, vec![full]);
+        assert!(tiles[4].navigation.is_empty());
+        assert_eq!(tiles[5].collision[0].len(), 3);
+        assert_eq!(tiles[5].region.origin, Point2::new(128.0, 64.0));
+    }
+
+    #[test]
+    fn texture_is_checked() {
+        let def = TILES.parse::<TileSetDef>().unwrap();
+        assert!(def.check(Vector2::new(192.0, 128.0)).is_empty());
+        // a fourth column collides with the ids, tile 4 and 5 are left out
+        assert_eq!(def.check(Vector2::new(256.0, 64.0)).len(), 3);
+    }
+
+    #[test]
+    fn terrain_is_checked() {
+        assert_eq!(
+            error(&with("terrain lava 7")),
+            "unknown terrain type `lava`"
+        );
+        assert_eq!(
+            error(&with("terrain sand 2")),
+            "tile 2 has two terrain types"
+        );
+        assert_eq!(error(&with("terrain sand -3")), "invalid tile id `-3`");
+        assert_eq!(error(&with("terrain sand x")), "invalid tile id `x`");
+        assert_eq!(error(&with("default lava")), "unknown terrain type `lava`");
+    }
+
+    #[test]
+    fn outlines_are_checked() {
+        assert_eq!(
+            error(&with("collision 3 0,0 64,0")),
+            "an outline has at least three points"
+        );
+        assert_eq!(
+            error(&with("collision 3 0,0 65,0 0,64")),
+            "point `65,0` lies outside of the tile"
+        );
+        assert_eq!(
+            error(&with("collision 3 0,0 64 0,64")),
+            "invalid point `64`"
+        );
+        assert_eq!(error(&with("navigation -1 full")), "invalid tile id `-1`");
+        assert_eq!(
+            error(&with("navigation lava full")),
+            "unknown terrain type `lava`"
+        );
+        assert_eq!(
+            error(&with("navigation 3 full full")),
+            "invalid point `full`"
+        );
+    }
+
+    #[test]
+    fn sizes_are_checked() {
+        assert_eq!(
+            error(&TILES.replace("size 64 64", "size 0 64")),
+            "tiles are at least 1x1"
+        );
+        assert_eq!(
+            error(&TILES.replace("columns 3", "columns 0")),
+            "there is at least one column"
+        );
+        assert_eq!(error(&with("size 64")), "missing size");
+    }
+
+    #[test]
+    fn texture_and_output_are_required() {
+        let err = TILES
+            .replace("output res://tiles.tres\n", "")
+            .parse::<TileSetDef>()
+            .unwrap_err();
+        assert_eq!(err.message, "the definition needs a texture and an output");
+        assert_eq!(
+            error(&TILES.replace("tiles 1", "tiles 2")),
+            "tiles version 2 is not supported, the latest is 1"
+        );
+        assert_eq!(error(&with("unknown 1")), "unknown record `unknown`");
+    }
+}
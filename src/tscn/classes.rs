@@ -0,0 +1,94 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! The inheritance of the Godot node classes that are used in the project's
+//! scenes, so scenes can be checked without running the engine.
+
+const PARENTS: &[(&str, &str)] = &[
+    ("Node", "Object"),
+    ("CanvasItem", "Node"),
+    ("CanvasLayer", "Node"),
+    ("Timer", "Node"),
+    ("Tween", "Node"),
+    ("AnimationPlayer", "Node"),
+    ("AudioStreamPlayer", "Node"),
+    ("Viewport", "Node"),
+    ("ParallaxBackground", "CanvasLayer"),
+    // 2d nodes
+    ("Node2D", "CanvasItem"),
+    ("CollisionObject2D", "Node2D"),
+    ("Area2D", "CollisionObject2D"),
+    ("PhysicsBody2D", "CollisionObject2D"),
+    ("KinematicBody2D", "PhysicsBody2D"),
+    ("RigidBody2D", "PhysicsBody2D"),
+    ("StaticBody2D", "PhysicsBody2D"),
+    ("AnimatedSprite", "Node2D"),
+    ("AudioStreamPlayer2D", "Node2D"),
+    ("Camera2D", "Node2D"),
+    ("CollisionPolygon2D", "Node2D"),
+    ("CollisionShape2D", "Node2D"),
+    ("CPUParticles2D", "Node2D"),
+    ("Light2D", "Node2D"),
+    ("Line2D", "Node2D"),
+    ("Navigation2D", "Node2D"),
+    ("ParallaxLayer", "Node2D"),
+    ("Particles2D", "Node2D"),
+    ("Path2D", "Node2D"),
+    ("PathFollow2D", "Node2D"),
+    ("Polygon2D", "Node2D"),
+    ("Position2D", "Node2D"),
+    ("RayCast2D", "Node2D"),
+    ("RemoteTransform2D", "Node2D"),
+    ("Sprite", "Node2D"),
+    ("TileMap", "Node2D"),
+    ("VisibilityNotifier2D", "Node2D"),
+    ("YSort", "Node2D"),
+    // user interface
+    ("Control", "CanvasItem"),
+    ("BaseButton", "Control"),
+    ("Button", "BaseButton"),
+    ("TextureButton", "BaseButton"),
+    ("Container", "Control"),
+    ("BoxContainer", "Container"),
+    ("HBoxContainer", "BoxContainer"),
+    ("VBoxContainer", "BoxContainer"),
+    ("CenterContainer", "Container"),
+    ("GridContainer", "Container"),
+    ("MarginContainer", "Container"),
+    ("PanelContainer", "Container"),
+    ("ColorRect", "Control"),
+    ("Label", "Control"),
+    ("NinePatchRect", "Control"),
+    ("Panel", "Control"),
+    ("Range", "Control"),
+    ("ProgressBar", "Range"),
+    ("TextureProgress", "Range"),
+    ("TextureRect", "Control"),
+];
+
+/// The class `class` directly inherits from.
+#[inline]
+pub fn parent_class(class: &str) -> Option<&'static str> {
+    PARENTS
+        .iter()
+        .find(|(name, _)| *name == class)
+        .map(|(_, parent)| *parent)
+}
+
+#[inline]
+pub fn is_known_class(class: &str) -> bool {
+    class == "Object" || parent_class(class).is_some()
+}
+
+/// Checks if `class` is `base` or one of its subclasses.
+pub fn is_subclass(class: &str, base: &str) -> bool {
+    let mut current = Some(class);
+    while let Some(class) = current {
+        if class == base {
+            return true;
+        }
+        current = parent_class(class);
+    }
+    false
+}
@@ -0,0 +1,233 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Generates a Rust module per scene of the Godot project, with a type per
+//! node, constants of the animation names and a list of the signal
+//! connections. The modules are nested by directory, e.g.
+//! `res://tanks/Tank.tscn` becomes `tanks::tank_tscn`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::{Connection, ResolvedScene, SceneLoader};
+
+/// Path of the module with the `ChildNodeRef` trait and `Connection` struct
+/// the generated code uses.
+pub const SCENES_MODULE: &str = "crate::scenes";
+
+#[derive(Default)]
+struct Module {
+    modules: BTreeMap<String, Module>,
+    scenes: Vec<String>,
+}
+
+impl Module {
+    fn write(&self, out: &mut String, indent: usize) {
+        for (i, scene) in self.scenes.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            for line in scene.lines() {
+                if line.is_empty() {
+                    out.push('\n');
+                } else {
+                    let _ = writeln!(out, "{:indent$}{}", "", line, indent = indent);
+                }
+            }
+        }
+
+        for (i, (name, module)) in self.modules.iter().enumerate() {
+            if i > 0 || !self.scenes.is_empty() {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "{:indent$}pub mod {} {{", "", name, indent = indent);
+            module.write(out, indent + 4);
+            let _ = writeln!(out, "{:indent$}}}", "", indent = indent);
+        }
+    }
+}
+
+/// Generates the modules of all scenes the loader can find.
+pub fn generate(loader: &mut SceneLoader) -> Result<String, String> {
+    let mut root = Module::default();
+    for file in loader.scene_files()? {
+        let res_path = loader.res_path(file.as_path());
+        let scene = loader.resolve(res_path.as_str())?;
+
+        let mut parts = res_path
+            .trim_start_matches(super::RES_PREFIX)
+            .split('/')
+            .collect::<Vec<_>>();
+        let file_name = parts.pop().unwrap_or_default();
+
+        let mut module = &mut root;
+        for dir in parts {
+            module = module.modules.entry(snake_case(dir)).or_default();
+        }
+
+        let name = format!("{}_tscn", snake_case(file_name.trim_end_matches(".tscn")));
+        module
+            .modules
+            .entry(name)
+            .or_default()
+            .scenes
+            .push(generate_scene(&scene));
+    }
+
+    let mut out =
+        String::from("// Generated from the scenes of the Godot project, do not edit.\n\n");
+    root.write(&mut out, 0);
+    Ok(out)
+}
+
+/// Generates the contents of the module of a single scene. Nodes that are
+/// part of scenes that are instanced within the scene are left out.
+pub fn generate_scene(scene: &ResolvedScene) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "pub const RES_PATH: &str = {:?};", scene.res_path);
+
+    let nodes = scene
+        .nodes
+        .iter()
+        .filter(|node| node.path != "." && !scene.is_instanced_child(node.path.as_str()))
+        .collect::<Vec<_>>();
+
+    let mut animations = Vec::<&str>::new();
+    for node in scene.nodes.iter() {
+        if node.path != "." && scene.is_instanced_child(node.path.as_str()) {
+            continue;
+        }
+        for name in node.animations.iter() {
+            if !animations.contains(&name.as_str()) {
+                animations.push(name.as_str());
+            }
+        }
+    }
+    if !animations.is_empty() {
+        out.push('\n');
+        for name in animations {
+            let _ = writeln!(
+                out,
+                "pub const ANIM_{}: &str = {:?};",
+                snake_case(name).to_uppercase(),
+                name
+            );
+        }
+    }
+
+    let mut type_names = Vec::<String>::new();
+    for node in nodes {
+        let class = match node.class.as_deref() {
+            Some(class) => class,
+            None => continue,
+        };
+
+        let mut type_name = type_name(node.path.as_str());
+        if type_names.contains(&type_name) {
+            type_name = format!("{}{}", type_name, type_names.len());
+        }
+
+        let _ = write!(
+            out,
+            "\npub struct {name};\n\n\
+            impl {module}::ChildNodeRef for {name} {{\n    \
+                type Base = gdnative::api::{class};\n\n    \
+                #[inline]\n    \
+                fn path() -> &'static str {{\n        \
+                    {path:?}\n    \
+                }}\n\
+            }}\n",
+            name = type_name,
+            module = SCENES_MODULE,
+            class = class,
+            path = node.path,
+        );
+        type_names.push(type_name);
+    }
+
+    let _ = write!(
+        out,
+        "\npub const CONNECTIONS: &[{}::Connection] = &[",
+        SCENES_MODULE
+    );
+    if !scene.connections.is_empty() {
+        out.push('\n');
+        for connection in scene.connections.iter() {
+            out.push_str(generate_connection(connection).as_str());
+        }
+    }
+    out.push_str("];\n");
+    out
+}
+
+fn generate_connection(connection: &Connection) -> String {
+    format!(
+        "    {}::Connection {{\n        \
+            signal: {:?},\n        \
+            from: {:?},\n        \
+            to: {:?},\n        \
+            method: {:?},\n        \
+            flags: {},\n    \
+        }},\n",
+        SCENES_MODULE,
+        connection.signal,
+        connection.from,
+        connection.to,
+        connection.method,
+        connection.flags
+    )
+}
+
+/// Converts a node name or file name to snake case, e.g. `EnemyTank` becomes
+/// `enemy_tank` and `HUD` becomes `hud`.
+pub fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().copied().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+
+        if c.is_ascii_uppercase() && i > 0 && !out.ends_with('_') {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_is_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+
+    let out = out.trim_end_matches('_').to_string();
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", out)
+    } else {
+        out
+    }
+}
+
+/// Name of the type of the node at `path`, e.g. `Turret/Muzzle` becomes
+/// `TurretMuzzleNode`.
+pub fn type_name(path: &str) -> String {
+    let mut out = String::new();
+    for word in path.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars);
+        }
+    }
+
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, 'N');
+    }
+    out.push_str("Node");
+    out
+}
@@ -0,0 +1,564 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! A minimal parser of Godot's text scene format (`.tscn`). It only depends
+//! on `std`, so it can be used outside of the engine, e.g. by tools and build
+//! scripts.
+
+use std::fmt;
+use std::str::FromStr;
+
+pub use resolve::*;
+
+pub mod classes;
+pub mod codegen;
+mod resolve;
+
+/// The bracketed line that starts a section, e.g.
+/// `[node name="Body" type="Sprite" parent="."]`. Attribute values are kept
+/// as they are written in the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heading {
+    pub tag: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Heading {
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The value of attribute `key` without its quotes.
+    #[inline]
+    pub fn get_string(&self, key: &str) -> Option<String> {
+        self.get(key).and_then(unquote)
+    }
+}
+
+/// A heading and the `key = value` properties that follow it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub heading: Heading,
+    pub properties: Vec<(String, String)>,
+}
+
+impl Section {
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtResource {
+    pub id: i64,
+    pub path: String,
+    pub typ: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneNode {
+    pub name: String,
+    /// Class of the node, not set for nodes that are instanced or that
+    /// override a node of an inherited or instanced scene.
+    pub typ: Option<String>,
+    /// Path of the parent relative to the root, `None` for the root itself.
+    pub parent: Option<String>,
+    /// Id of the `ExtResource` of the scene that is instanced.
+    pub instance: Option<i64>,
+    pub groups: Vec<String>,
+    /// Id of the `ExtResource` of the node's script.
+    pub script: Option<i64>,
+    pub properties: Vec<(String, String)>,
+}
+
+impl SceneNode {
+    /// Path of the node relative to the root, where the root itself is `.`.
+    pub fn path(&self) -> String {
+        match self.parent.as_deref() {
+            None => String::from("."),
+            Some(".") => self.name.clone(),
+            Some(parent) => format!("{}/{}", parent, self.name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Connection {
+    pub signal: String,
+    pub from: String,
+    pub to: String,
+    pub method: String,
+    pub flags: i64,
+}
+
+/// A parsed `.tscn` file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneFile {
+    pub sections: Vec<Section>,
+}
+
+impl SceneFile {
+    pub fn ext_resources(&self) -> Vec<ExtResource> {
+        self.sections_of("ext_resource")
+            .filter_map(|section| {
+                let heading = &section.heading;
+                Some(ExtResource {
+                    id: heading.get("id")?.trim().parse().ok()?,
+                    path: heading.get_string("path")?,
+                    typ: heading.get_string("type").unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    #[inline]
+    pub fn ext_resource(&self, id: i64) -> Option<ExtResource> {
+        self.ext_resources().into_iter().find(|res| res.id == id)
+    }
+
+    #[inline]
+    pub fn sub_resource(&self, id: i64) -> Option<&Section> {
+        self.sections_of("sub_resource").find(|section| {
+            section
+                .heading
+                .get("id")
+                .and_then(|value| value.trim().parse::<i64>().ok())
+                == Some(id)
+        })
+    }
+
+    /// The sub resources of type `typ`, e.g. `Animation`.
+    pub fn sub_resources<'a>(&'a self, typ: &'a str) -> impl Iterator<Item = &'a Section> + 'a {
+        self.sections_of("sub_resource")
+            .filter(move |section| section.heading.get_string("type").as_deref() == Some(typ))
+    }
+
+    pub fn nodes(&self) -> Vec<SceneNode> {
+        self.sections_of("node")
+            .filter_map(|section| {
+                let heading = &section.heading;
+                Some(SceneNode {
+                    name: heading.get_string("name")?,
+                    typ: heading.get_string("type"),
+                    parent: heading.get_string("parent"),
+                    instance: heading.get("instance").and_then(parse_ext_resource_id),
+                    groups: heading
+                        .get("groups")
+                        .map(parse_string_array)
+                        .unwrap_or_default(),
+                    script: section.get("script").and_then(parse_ext_resource_id),
+                    properties: section.properties.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Names of the animations of a node. These are the `anims/` of an
+    /// `AnimationPlayer` or the animations of the `SpriteFrames` of an
+    /// `AnimatedSprite`, when they are defined in this file.
+    pub fn animations(&self, node: &SceneNode) -> Vec<String> {
+        let mut animations = Vec::new();
+        for (key, value) in node.properties.iter() {
+            if let Some(name) = key.strip_prefix("anims/") {
+                animations.push(name.to_string());
+            } else if key == "frames" {
+                let frames = parse_sub_resource_id(value)
+                    .and_then(|id| self.sub_resource(id))
+                    .and_then(|section| section.get("animations"));
+                if let Some(frames) = frames {
+                    animations.extend(parse_sprite_frames_names(frames));
+                }
+            }
+        }
+        animations
+    }
+
+    pub fn connections(&self) -> Vec<Connection> {
+        self.sections_of("connection")
+            .filter_map(|section| {
+                let heading = &section.heading;
+                Some(Connection {
+                    signal: heading.get_string("signal")?,
+                    from: heading.get_string("from")?,
+                    to: heading.get_string("to")?,
+                    method: heading.get_string("method")?,
+                    flags: heading
+                        .get("flags")
+                        .and_then(|flags| flags.trim().parse().ok())
+                        .unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn sections_of<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Section> + 'a {
+        self.sections
+            .iter()
+            .filter(move |section| section.heading.tag == tag)
+    }
+}
+
+/// Error of a line that cannot be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for SceneFile {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scene = SceneFile::default();
+        let mut lines = s.lines().enumerate();
+
+        while let Some((index, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            // headings and values may continue on the next lines, as long as
+            // any brackets or strings are left open
+            let mut text = line.to_string();
+            while depth(text.as_str()) > 0 || is_in_string(text.as_str()) {
+                match lines.next() {
+                    Some((_, next)) => {
+                        text.push('\n');
+                        text.push_str(next);
+                    }
+                    None => {
+                        return Err(ParseError {
+                            line: index + 1,
+                            message: String::from("unexpected end of file"),
+                        })
+                    }
+                }
+            }
+
+            if text.starts_with('[') {
+                let heading = parse_heading(text.as_str()).map_err(|message| ParseError {
+                    line: index + 1,
+                    message,
+                })?;
+                scene.sections.push(Section {
+                    heading,
+                    properties: Vec::new(),
+                });
+                continue;
+            }
+
+            let (key, value) = text.split_once('=').ok_or_else(|| ParseError {
+                line: index + 1,
+                message: format!("expected `key = value`, found `{}`", line),
+            })?;
+            match scene.sections.last_mut() {
+                Some(section) => section
+                    .properties
+                    .push((key.trim().to_string(), value.trim().to_string())),
+                None => {
+                    return Err(ParseError {
+                        line: index + 1,
+                        message: String::from("property outside of a section"),
+                    })
+                }
+            }
+        }
+
+        Ok(scene)
+    }
+}
+
+/// Removes the quotes around a string value and reads its escapes. Escapes
+/// that are not known are kept as they are.
+pub fn unquote(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return None;
+    }
+
+    let mut string = String::new();
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('t') => string.push('\t'),
+            Some('r') => string.push('\r'),
+            Some(c @ '"') | Some(c @ '\\') => string.push(c),
+            Some(c) => {
+                string.push('\\');
+                string.push(c);
+            }
+            None => string.push('\\'),
+        }
+    }
+    Some(string)
+}
+
+/// Reads the id of a value like `ExtResource( 2 )`.
+#[inline]
+pub fn parse_ext_resource_id(value: &str) -> Option<i64> {
+    parse_resource_id(value, "ExtResource")
+}
+
+/// Reads the id of a value like `SubResource( 1 )`.
+#[inline]
+pub fn parse_sub_resource_id(value: &str) -> Option<i64> {
+    parse_resource_id(value, "SubResource")
+}
+
+fn parse_resource_id(value: &str, kind: &str) -> Option<i64> {
+    value
+        .trim()
+        .strip_prefix(kind)?
+        .trim()
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Reads the strings of an array value like `[ "a", "b" ]`.
+pub fn parse_string_array(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .unwrap_or(value);
+
+    split_top_level(value, ',')
+        .iter()
+        .filter_map(|item| unquote(item))
+        .collect()
+}
+
+/// Reads the names of the animations of a `SpriteFrames` resource, e.g.
+/// `[ { "frames": [...], "name": "fire" } ]`.
+fn parse_sprite_frames_names(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .unwrap_or(value);
+
+    split_top_level(value, ',')
+        .iter()
+        .filter_map(|animation| {
+            let animation = animation.trim().strip_prefix('{')?.strip_suffix('}')?;
+            split_top_level(animation, ',')
+                .iter()
+                .find_map(|entry| match entry.split_once(':') {
+                    Some((key, value)) if unquote(key).as_deref() == Some("name") => unquote(value),
+                    _ => None,
+                })
+        })
+        .collect()
+}
+
+/// Number of brackets, braces and parentheses that are left open in `text`.
+fn depth(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '[' | '{' | '(' => depth += 1,
+            ']' | '}' | ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Splits `text` on `separator`, except when it is inside a string or
+/// brackets.
+fn split_top_level(text: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        if c == separator && depth(current.as_str()) == 0 && !is_in_string(current.as_str()) {
+            parts.push(current.trim().to_string());
+            current.clear();
+            continue;
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+#[inline]
+fn is_in_string(text: &str) -> bool {
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ => {}
+        }
+    }
+    in_string
+}
+
+fn parse_heading(text: &str) -> Result<Heading, String> {
+    let inner = text
+        .trim()
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+        .ok_or_else(|| format!("invalid heading `{}`", text))?
+        .trim();
+
+    let (tag, rest) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+    let mut attributes = Vec::new();
+    for attribute in split_top_level(rest, ' ')
+        .into_iter()
+        .filter(|part| !part.is_empty())
+    {
+        // values such as `ExtResource( 1 )` contain spaces, glue them back to
+        // the attribute they belong to
+        match attribute.split_once('=') {
+            Some((key, value)) if !key.contains('(') && !key.contains('"') => {
+                attributes.push((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => match attributes.last_mut() {
+                Some((_, value)) => {
+                    value.push(' ');
+                    value.push_str(attribute.as_str());
+                }
+                None => return Err(format!("invalid attribute `{}`", attribute)),
+            },
+        }
+    }
+
+    Ok(Heading {
+        tag: tag.to_string(),
+        attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heading_with_quoted_and_nested_values() {
+        let heading = parse_heading(
+            r#"[node name="Gun Turret" parent="." instance=ExtResource( 2 ) groups=[ "a b", "c" ]]"#,
+        )
+        .unwrap();
+
+        assert_eq!(heading.tag, "node");
+        assert_eq!(heading.get_string("name").as_deref(), Some("Gun Turret"));
+        assert_eq!(heading.get("instance"), Some("ExtResource( 2 )"));
+        assert_eq!(
+            heading.get("groups").map(parse_string_array),
+            Some(vec![String::from("a b"), String::from("c")])
+        );
+        assert!(parse_heading("[node").is_err());
+    }
+
+    #[test]
+    fn multi_line_values_and_headings() {
+        let scene = r#"
+; a comment
+[gd_scene load_steps=2 format=2]
+
+[node name="Root" type="Node2D" groups=[
+"units",
+]]
+position = Vector2( 1, 2 )
+text = "first line
+second line"
+anims/idle = {
+"length": 1.0
+}
+"#
+        .parse::<SceneFile>()
+        .unwrap();
+
+        let nodes = scene.nodes();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].groups, ["units"]);
+        assert_eq!(nodes[0].properties.len(), 3);
+        assert_eq!(
+            unquote(scene.sections[1].get("text").unwrap()).as_deref(),
+            Some("first line\nsecond line")
+        );
+        assert_eq!(scene.animations(&nodes[0]), ["idle"]);
+    }
+
+    #[test]
+    fn parse_errors_have_a_line() {
+        let err = "[node name=\"a\"]\nthis is not a property"
+            .parse::<SceneFile>()
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = "[node name=\"a\"]\ntext = \"open"
+            .parse::<SceneFile>()
+            .unwrap_err();
+        assert_eq!(err.message, "unexpected end of file");
+
+        let err = "key = 1".parse::<SceneFile>().unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+
+    #[test]
+    fn unquote_reads_escapes() {
+        assert_eq!(unquote(r#""a \"b\"""#).as_deref(), Some(r#"a "b""#));
+        assert_eq!(unquote(r#""back\\slash""#).as_deref(), Some(r"back\slash"));
+        assert_eq!(
+            unquote(r#""two\nlines\tand a tab""#).as_deref(),
+            Some("two\nlines\tand a tab")
+        );
+        assert_eq!(unquote(r#""\\n""#).as_deref(), Some(r"\n"));
+        assert_eq!(unquote(r#""keep \q""#).as_deref(), Some(r"keep \q"));
+        assert_eq!(unquote("not quoted"), None);
+        assert_eq!(unquote("\""), None);
+    }
+
+    #[test]
+    fn resource_ids() {
+        assert_eq!(parse_ext_resource_id("ExtResource( 3 )"), Some(3));
+        assert_eq!(parse_sub_resource_id("SubResource(12)"), Some(12));
+        assert_eq!(parse_sub_resource_id("ExtResource( 3 )"), None);
+    }
+}
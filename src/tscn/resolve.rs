@@ -0,0 +1,428 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Connection, SceneFile};
+
+pub const RES_PREFIX: &str = "res://";
+
+/// A node of a scene after all inherited and instanced scenes are merged
+/// into it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedNode {
+    /// Path relative to the scene's root, where the root itself is `.`.
+    pub path: String,
+    pub class: Option<String>,
+    /// Resource path of the attached script.
+    pub script: Option<String>,
+    /// Class name of the attached NativeScript.
+    pub script_class: Option<String>,
+    pub groups: Vec<String>,
+    pub animations: Vec<String>,
+    /// Resource path of the scene that is instanced as this node.
+    pub instance: Option<String>,
+    /// True when the node is defined or overridden in the scene's own file.
+    pub is_local: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResolvedScene {
+    pub res_path: String,
+    pub nodes: Vec<ResolvedNode>,
+    /// The connections of the scene and of the scene it inherits from.
+    /// Connections within instanced scenes are not included.
+    pub connections: Vec<Connection>,
+}
+
+impl ResolvedScene {
+    #[inline]
+    pub fn get(&self, path: &str) -> Option<&ResolvedNode> {
+        self.nodes.iter().find(|node| node.path == path)
+    }
+
+    #[inline]
+    pub fn root(&self) -> Option<&ResolvedNode> {
+        self.get(".")
+    }
+
+    /// Checks if the node at `path` is part of a scene that is instanced
+    /// within this scene, excluding the instanced node itself.
+    pub fn is_instanced_child(&self, path: &str) -> bool {
+        self.nodes.iter().any(|node| {
+            node.instance.is_some()
+                && node.path != "."
+                && path.starts_with(node.path.as_str())
+                && path[node.path.len()..].starts_with('/')
+        })
+    }
+
+    fn get_mut(&mut self, path: &str) -> Option<&mut ResolvedNode> {
+        self.nodes.iter_mut().find(|node| node.path == path)
+    }
+
+    /// Adds the nodes of `scene` with their paths relative to `parent`. The
+    /// nodes are not local to this scene.
+    fn graft(&mut self, parent: &str, scene: &ResolvedScene) {
+        for node in scene.nodes.iter() {
+            let mut node = ResolvedNode {
+                path: join_path(parent, node.path.as_str()),
+                is_local: false,
+                ..node.clone()
+            };
+            if node.path == parent {
+                node.instance = Some(scene.res_path.clone());
+            }
+            self.nodes.push(node);
+        }
+    }
+}
+
+/// Loads and resolves the scenes of a Godot project directory.
+pub struct SceneLoader {
+    dir: PathBuf,
+    resolved: HashMap<String, ResolvedScene>,
+    loading: Vec<String>,
+}
+
+impl SceneLoader {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        SceneLoader {
+            dir: dir.as_ref().to_path_buf(),
+            resolved: HashMap::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    /// Converts a `res://` path to a path on the file system.
+    pub fn file_path(&self, res_path: &str) -> PathBuf {
+        self.dir
+            .join(res_path.strip_prefix(RES_PREFIX).unwrap_or(res_path))
+    }
+
+    /// Converts a path on the file system to a `res://` path.
+    pub fn res_path(&self, file: &Path) -> String {
+        let relative = file.strip_prefix(&self.dir).unwrap_or(file);
+        let parts = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+
+        format!("{}{}", RES_PREFIX, parts.join("/"))
+    }
+
+    /// All `.tscn` files in the project directory, sorted by path.
+    pub fn scene_files(&self) -> Result<Vec<PathBuf>, String> {
+        let mut files = Vec::new();
+        collect_files(self.dir.as_path(), "tscn", &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn load(&self, res_path: &str) -> Result<SceneFile, String> {
+        let file = self.file_path(res_path);
+        let text = fs::read_to_string(&file)
+            .map_err(|err| format!("Unable to read `{}`: {}", file.display(), err))?;
+
+        text.parse()
+            .map_err(|err| format!("Unable to parse `{}`: {}", res_path, err))
+    }
+
+    /// Reads the class name of a NativeScript resource (`.gdns`).
+    pub fn load_script_class(&self, res_path: &str) -> Result<Option<String>, String> {
+        if !res_path.ends_with(".gdns") {
+            return Ok(None);
+        }
+
+        let script = self.load(res_path)?;
+        Ok(script
+            .sections
+            .iter()
+            .find(|section| section.heading.tag == "resource")
+            .and_then(|section| section.get("class_name"))
+            .and_then(super::unquote))
+    }
+
+    /// Loads the scene at `res_path` and merges the nodes of the scenes it
+    /// inherits from or instances into it.
+    pub fn resolve(&mut self, res_path: &str) -> Result<ResolvedScene, String> {
+        if let Some(scene) = self.resolved.get(res_path) {
+            return Ok(scene.clone());
+        }
+        if self.loading.iter().any(|path| path == res_path) {
+            return Err(format!("Scene `{}` instances itself", res_path));
+        }
+
+        self.loading.push(res_path.to_string());
+        let result = self.resolve_file(res_path);
+        self.loading.pop();
+
+        let scene = result?;
+        self.resolved.insert(res_path.to_string(), scene.clone());
+        Ok(scene)
+    }
+
+    fn resolve_file(&mut self, res_path: &str) -> Result<ResolvedScene, String> {
+        let file = self.load(res_path)?;
+        let resources = file.ext_resources();
+        let resource_path = |id: i64| -> Result<String, String> {
+            resources
+                .iter()
+                .find(|res| res.id == id)
+                .map(|res| res.path.clone())
+                .ok_or_else(|| format!("Missing ExtResource( {} ) in `{}`", id, res_path))
+        };
+
+        let mut scene = ResolvedScene {
+            res_path: res_path.to_string(),
+            nodes: Vec::new(),
+            connections: Vec::new(),
+        };
+
+        for node in file.nodes() {
+            let path = node.path();
+            if let Some(id) = node.instance {
+                let instanced = self.resolve(resource_path(id)?.as_str())?;
+                scene.graft(path.as_str(), &instanced);
+                if path == "." {
+                    scene.connections.extend(instanced.connections);
+                }
+            }
+
+            let script = match node.script {
+                Some(id) => Some(resource_path(id)?),
+                None => None,
+            };
+            let script_class = match script.as_deref() {
+                Some(script) => self.load_script_class(script)?,
+                None => None,
+            };
+
+            let animations = file.animations(&node);
+            match scene.get_mut(path.as_str()) {
+                Some(existing) => {
+                    // an override of an inherited or instanced node
+                    existing.is_local = true;
+                    if node.typ.is_some() {
+                        existing.class = node.typ.clone();
+                    }
+                    if script.is_some() {
+                        existing.script = script;
+                        existing.script_class = script_class;
+                    }
+                    existing.groups.extend(node.groups.iter().cloned());
+                    for name in animations {
+                        if !existing.animations.contains(&name) {
+                            existing.animations.push(name);
+                        }
+                    }
+                }
+                None => scene.nodes.push(ResolvedNode {
+                    path,
+                    class: node.typ.clone(),
+                    script,
+                    script_class,
+                    groups: node.groups.clone(),
+                    animations,
+                    instance: None,
+                    is_local: true,
+                }),
+            }
+        }
+
+        scene.connections.extend(file.connections());
+        Ok(scene)
+    }
+}
+
+/// Joins a node path to the path of its parent, where `.` is the root.
+pub fn join_path(parent: &str, path: &str) -> String {
+    match (parent, path) {
+        (".", path) => path.to_string(),
+        (parent, ".") => parent.to_string(),
+        (parent, path) => format!("{}/{}", parent, path),
+    }
+}
+
+fn collect_files(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| format!("Unable to read directory `{}`: {}", dir.display(), err))?;
+
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.is_dir() {
+            collect_files(path.as_path(), extension, files)?;
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TANK: &str = r#"[gd_scene load_steps=3 format=2]
+
+[ext_resource path="res://Tank.gdns" type="Script" id=1]
+
+[sub_resource type="SpriteFrames" id=1]
+animations = [ {
+"frames": [  ],
+"name": "fire"
+} ]
+
+[node name="Tank" type="KinematicBody2D" groups=[
+"damage_taker",
+]]
+script = ExtResource( 1 )
+
+[node name="Explosion" type="AnimatedSprite" parent="."]
+frames = SubResource( 1 )
+
+[connection signal="animation_finished" from="Explosion" to="." method="_on_Explosion_animation_finished"]
+"#;
+
+    const PLAYER: &str = r#"[gd_scene load_steps=3 format=2]
+
+[ext_resource path="res://Tank.tscn" type="PackedScene" id=1]
+[ext_resource path="res://Player.gdns" type="Script" id=2]
+
+[node name="Player" instance=ExtResource( 1 )]
+script = ExtResource( 2 )
+
+[node name="Camera" type="Camera2D" parent="."]
+"#;
+
+    const MAP: &str = r#"[gd_scene load_steps=2 format=2]
+
+[ext_resource path="res://Player.tscn" type="PackedScene" id=1]
+
+[node name="Map" type="Node2D"]
+
+[node name="Player" parent="." instance=ExtResource( 1 )]
+
+[node name="Explosion" parent="Player" index="1"]
+playing = true
+"#;
+
+    const TANK_SCRIPT: &str = r#"[gd_resource type="NativeScript" format=2]
+
+[resource]
+class_name = "Tank"
+"#;
+
+    const PLAYER_SCRIPT: &str = r#"[gd_resource type="NativeScript" format=2]
+
+[resource]
+class_name = "Player"
+"#;
+
+    /// A project directory with the given files, which is removed again when
+    /// it is dropped.
+    struct Project(PathBuf);
+
+    impl Project {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("tscn-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            for (path, text) in files.iter() {
+                fs::write(dir.join(path), text).unwrap();
+            }
+            Project(dir)
+        }
+    }
+
+    impl Drop for Project {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn inherited_scene_is_merged() {
+        let project = Project::new(
+            "inherited",
+            &[
+                ("Tank.tscn", TANK),
+                ("Player.tscn", PLAYER),
+                ("Tank.gdns", TANK_SCRIPT),
+                ("Player.gdns", PLAYER_SCRIPT),
+            ],
+        );
+        let scene = SceneLoader::new(&project.0)
+            .resolve("res://Player.tscn")
+            .unwrap();
+
+        let root = scene.root().unwrap();
+        assert_eq!(root.class.as_deref(), Some("KinematicBody2D"));
+        assert_eq!(root.script.as_deref(), Some("res://Player.gdns"));
+        assert_eq!(root.script_class.as_deref(), Some("Player"));
+        assert_eq!(root.groups, ["damage_taker"]);
+        assert!(root.is_local);
+
+        let explosion = scene.get("Explosion").unwrap();
+        assert_eq!(explosion.animations, ["fire"]);
+        assert!(!explosion.is_local);
+        assert!(scene.get("Camera").unwrap().is_local);
+        assert_eq!(scene.connections.len(), 1);
+    }
+
+    #[test]
+    fn instanced_scene_is_grafted() {
+        let project = Project::new(
+            "instanced",
+            &[
+                ("Tank.tscn", TANK),
+                ("Player.tscn", PLAYER),
+                ("Tank.gdns", TANK_SCRIPT),
+                ("Player.gdns", PLAYER_SCRIPT),
+                ("Map.tscn", MAP),
+            ],
+        );
+        let scene = SceneLoader::new(&project.0)
+            .resolve("res://Map.tscn")
+            .unwrap();
+
+        let player = scene.get("Player").unwrap();
+        assert_eq!(player.instance.as_deref(), Some("res://Player.tscn"));
+        assert_eq!(player.script_class.as_deref(), Some("Player"));
+        assert!(player.is_local);
+
+        // overridden in the map itself
+        assert!(scene.get("Player/Explosion").unwrap().is_local);
+        assert!(!scene.get("Player/Camera").unwrap().is_local);
+        assert!(scene.is_instanced_child("Player/Camera"));
+        assert!(!scene.is_instanced_child("Player"));
+
+        // connections within an instanced scene are its own
+        assert!(scene.connections.is_empty());
+    }
+
+    #[test]
+    fn scene_instancing_itself_is_an_error() {
+        let looped = r#"[gd_scene format=2]
+
+[ext_resource path="res://Loop.tscn" type="PackedScene" id=1]
+
+[node name="Loop" type="Node"]
+
+[node name="Again" parent="." instance=ExtResource( 1 )]
+"#;
+        let project = Project::new("loop", &[("Loop.tscn", looped)]);
+        assert!(SceneLoader::new(&project.0)
+            .resolve("res://Loop.tscn")
+            .is_err());
+    }
+
+    #[test]
+    fn paths_are_joined_from_the_root() {
+        assert_eq!(join_path(".", "Body"), "Body");
+        assert_eq!(join_path("Player", "."), "Player");
+        assert_eq!(join_path("Player", "Body/Gun"), "Player/Body/Gun");
+    }
+}
@@ -2,12 +2,15 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
-use gdnative::api::{AnimationPlayer, CanvasLayer, TextureProgress};
+use gdnative::api::{AnimationPlayer, CanvasLayer, Label, TextureProgress};
 use gdnative::prelude::*;
 
+use crate::events::{self, ScoreChanged, TankHealthChanged, TankShieldChanged};
+use crate::resources::ResourceRef;
+use crate::scenes::ui::hud_tscn::{self, ANIM_HEALTHBAR_FLASH};
 use crate::ui::*;
 use crate::utils::node::NodeRef;
-use crate::utils::preload::*;
+use crate::utils::*;
 
 #[derive(Clone, Copy, PartialEq)]
 enum HealthBarColor {
@@ -19,9 +22,9 @@ enum HealthBarColor {
 #[derive(NativeClass)]
 #[inherit(CanvasLayer)]
 pub struct Hud {
-    healthbar_red_texture: Option<Ref<Texture>>,
-    healthbar_yellow_texture: Option<Ref<Texture>>,
-    healthbar_green_texture: Option<Ref<Texture>>,
+    healthbar_red_texture: ResourceRef<Texture>,
+    healthbar_yellow_texture: ResourceRef<Texture>,
+    healthbar_green_texture: ResourceRef<Texture>,
 
     healthbar_color: HealthBarColor,
 
@@ -29,39 +32,93 @@ pub struct Hud {
     healthbar_node: NodeRef<TextureProgress>,
     healthbar_tween_node: NodeRef<Tween>,
     healthbar_anim_node: NodeRef<AnimationPlayer>,
+    shieldbar_node: NodeRef<TextureProgress>,
+    score_label_node: NodeRef<Label>,
 }
 
 #[methods]
 impl Hud {
     fn new(_owner: TRef<CanvasLayer>) -> Self {
         Hud {
-            healthbar_red_texture: None,
-            healthbar_yellow_texture: None,
-            healthbar_green_texture: None,
+            healthbar_red_texture: ResourceRef::new(RES_HEALTHBAR_RED_TEXTURE),
+            healthbar_yellow_texture: ResourceRef::new(RES_HEALTHBAR_YELLOW_TEXTURE),
+            healthbar_green_texture: ResourceRef::new(RES_HEALTHBAR_GREEN_TEXTURE),
 
             healthbar_color: HealthBarColor::Green,
 
-            healthbar_node: NodeRef::new("Margin/HBoxContainer/HealthBar"),
-            healthbar_tween_node: NodeRef::new("Margin/HBoxContainer/HealthBar/Tween"),
-            healthbar_anim_node: NodeRef::new("AnimationPlayer"),
+            healthbar_node: NodeRef::of::<hud_tscn::MarginHBoxContainerHealthBarNode>(),
+            healthbar_tween_node: NodeRef::of::<hud_tscn::MarginHBoxContainerHealthBarTweenNode>(),
+            healthbar_anim_node: NodeRef::of::<hud_tscn::AnimationPlayerNode>(),
+            shieldbar_node: NodeRef::of::<hud_tscn::MarginHBoxContainerHealthBarShieldBarNode>(),
+            score_label_node: NodeRef::of::<hud_tscn::MarginHBoxContainerScoreLabelNode>(),
         }
     }
 
     #[export]
     fn _ready(&mut self, owner: TRef<CanvasLayer>) {
-        self.healthbar_red_texture = try_preload::<Texture>(RES_HEALTHBAR_RED_TEXTURE, "");
-        self.healthbar_yellow_texture = try_preload::<Texture>(RES_HEALTHBAR_YELLOW_TEXTURE, "");
-        self.healthbar_green_texture = try_preload::<Texture>(RES_HEALTHBAR_GREEN_TEXTURE, "");
-
-        let owner = owner.as_ref();
-        self.healthbar_node.get_from(owner);
-        self.healthbar_tween_node.get_from(owner);
-        self.healthbar_anim_node.get_from(owner);
+        // missing nodes and textures are reported once, the parts of the hud
+        // that use them are not updated
+        let node = owner.as_ref();
+        report_error(self.healthbar_red_texture.try_get_from(node));
+        report_error(self.healthbar_yellow_texture.try_get_from(node));
+        report_error(self.healthbar_green_texture.try_get_from(node));
+        report_error(self.healthbar_node.try_get_from(node));
+        report_error(self.healthbar_tween_node.try_get_from(node));
+        report_error(self.healthbar_anim_node.try_get_from(node));
+        report_error(self.shieldbar_node.try_get_from(node));
+        report_error(self.score_label_node.try_get_from(node));
+
+        events::subscribe::<TankHealthChanged, _>(
+            node,
+            owner,
+            "_on_Events_tank_health_changed",
+            Object::CONNECT_DEFERRED,
+        );
+        events::subscribe::<TankShieldChanged, _>(
+            node,
+            owner,
+            "_on_Events_tank_shield_changed",
+            Object::CONNECT_DEFERRED,
+        );
+        events::subscribe::<ScoreChanged, _>(node, owner, "_on_Events_score_changed", 0);
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_tank_health_changed(
+        &mut self,
+        _owner: TRef<CanvasLayer>,
+        event: TankHealthChanged,
+    ) {
+        if event.kind.is_player() {
+            self.set_health(event.health);
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_Events_tank_shield_changed(
+        &mut self,
+        _owner: TRef<CanvasLayer>,
+        event: TankShieldChanged,
+    ) {
+        if event.kind.is_player() {
+            self.set_shield(event.shield);
+        }
     }
 
     #[allow(non_snake_case)]
     #[export]
-    fn _on_Player_health_changed(&mut self, _owner: TRef<CanvasLayer>, value: f64) {
+    fn _on_Events_score_changed(&mut self, _owner: TRef<CanvasLayer>, event: ScoreChanged) {
+        if let Ok(label) = self.score_label_node.try_get_ref() {
+            label.set_text(format!(
+                "{}  x{:.1}  {:.0}%",
+                event.score, event.multiplier, event.accuracy
+            ));
+        }
+    }
+
+    fn set_health(&mut self, value: f64) {
         if value < 25.0 {
             self.healthbar_color = HealthBarColor::Red;
         } else if value < 60.0 {
@@ -70,8 +127,13 @@ impl Hud {
             self.healthbar_color = HealthBarColor::Green;
         }
 
-        let healthbar = self.healthbar_node.get_ref();
-        let tween = self.healthbar_tween_node.get_ref();
+        let (healthbar, tween) = match (
+            self.healthbar_node.try_get_ref(),
+            self.healthbar_tween_node.try_get_ref(),
+        ) {
+            (Ok(healthbar), Ok(tween)) => (healthbar, tween),
+            _ => return,
+        };
 
         tween.interpolate_property(
             healthbar,
@@ -85,9 +147,31 @@ impl Hud {
         );
         tween.start();
 
-        self.healthbar_anim_node
-            .get_ref()
-            .play(ANIM_HEALTHBAR_FLASH, -1.0, 1.0, false);
+        if let Ok(anim_player) = self.healthbar_anim_node.try_get_ref() {
+            anim_player.play(ANIM_HEALTHBAR_FLASH, -1.0, 1.0, false);
+        }
+    }
+
+    fn set_shield(&mut self, value: f64) {
+        let (shieldbar, tween) = match (
+            self.shieldbar_node.try_get_ref(),
+            self.healthbar_tween_node.try_get_ref(),
+        ) {
+            (Ok(shieldbar), Ok(tween)) => (shieldbar, tween),
+            _ => return,
+        };
+
+        tween.interpolate_property(
+            shieldbar,
+            "value",
+            shieldbar.value(),
+            value,
+            0.2,
+            Tween::TRANS_LINEAR,
+            Tween::EASE_IN_OUT,
+            0.0,
+        );
+        tween.start();
     }
 
     #[allow(non_snake_case)]
@@ -98,14 +182,12 @@ impl Hud {
         }
 
         let texture = match self.healthbar_color {
-            HealthBarColor::Red => self.healthbar_red_texture.as_ref(),
-            HealthBarColor::Yellow => self.healthbar_yellow_texture.as_ref(),
-            HealthBarColor::Green => self.healthbar_green_texture.as_ref(),
+            HealthBarColor::Red => self.healthbar_red_texture.try_get_ref(),
+            HealthBarColor::Yellow => self.healthbar_yellow_texture.try_get_ref(),
+            HealthBarColor::Green => self.healthbar_green_texture.try_get_ref(),
         };
-        if let Some(texture) = texture {
-            self.healthbar_node.get_ref().set_progress_texture(texture);
+        if let (Ok(texture), Ok(healthbar)) = (texture, self.healthbar_node.try_get_ref()) {
+            healthbar.set_progress_texture(texture);
         }
     }
 }
-
-const ANIM_HEALTHBAR_FLASH: &str = "healthbar_flash";
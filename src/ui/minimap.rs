@@ -0,0 +1,343 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::f64::consts::TAU;
+
+use gdnative::api::Control;
+use gdnative::prelude::*;
+
+use crate::units::{self, Unit, UnitKind};
+use crate::utils::ExportGroup;
+
+#[derive(Clone, Debug, PartialEq, ExportGroup)]
+pub struct MinimapSettings {
+    /// Enemies within this distance of the player are shown.
+    #[export]
+    pub radar_range: f32,
+    /// Seconds between updates of the minimap.
+    #[export(range(0.0, 1.0, 0.05))]
+    pub update_interval: f32,
+    /// Size of the markers of the player, enemies and pickups.
+    #[export(range(1.0, 16.0, 0.5))]
+    pub marker_size: f32,
+    #[export]
+    pub background_color: Color,
+    #[export]
+    pub ground_color: Color,
+    #[export]
+    pub obstacle_color: Color,
+    #[export]
+    pub pickup_color: Color,
+    #[export]
+    pub enemy_color: Color,
+    #[export]
+    pub player_color: Color,
+    #[export]
+    pub radar_color: Color,
+    /// Color of the areas the player has not explored.
+    #[export]
+    pub fog_color: Color,
+    /// Color of the explored areas the player does not see right now.
+    #[export]
+    pub hidden_color: Color,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        MinimapSettings {
+            radar_range: 1500.0,
+            update_interval: 0.1,
+            marker_size: 4.0,
+            background_color: Color::rgba(0.1, 0.1, 0.1, 0.6),
+            ground_color: Color::rgba(0.35, 0.45, 0.3, 0.8),
+            obstacle_color: Color::rgb(0.55, 0.45, 0.35),
+            pickup_color: Color::rgb(1.0, 0.9, 0.4),
+            enemy_color: Color::rgb(0.9, 0.2, 0.2),
+            player_color: Color::rgb(0.3, 0.8, 1.0),
+            radar_color: Color::rgba(0.3, 0.8, 1.0, 0.35),
+            fog_color: Color::rgba(0.0, 0.0, 0.0, 0.75),
+            hidden_color: Color::rgba(0.0, 0.0, 0.0, 0.3),
+        }
+    }
+}
+
+/// The position of a unit at the last update of the minimap.
+#[derive(Clone, Copy, Debug)]
+struct Blip {
+    kind: UnitKind,
+    position: Vector2,
+    rotation: f32,
+    extents: Vector2,
+}
+
+impl From<&Unit> for Blip {
+    #[inline]
+    fn from(unit: &Unit) -> Self {
+        Blip {
+            kind: unit.kind,
+            position: unit.position(),
+            rotation: unit.rotation(),
+            extents: unit.extents,
+        }
+    }
+}
+
+/// The fog of the vision grid at the last update of the minimap, as runs of
+/// cells.
+#[derive(Clone, Debug)]
+struct Fog {
+    origin: Vector2,
+    cell_size: f32,
+    unexplored: Vec<(usize, usize, usize)>,
+    hidden: Vec<(usize, usize, usize)>,
+}
+
+impl Fog {
+    #[inline]
+    fn run_rect(&self, (row, column, length): (usize, usize, usize)) -> Rect2 {
+        Rect2::new(
+            (self.origin + Vector2::new(column as f32, row as f32) * self.cell_size).to_point(),
+            Size2::new(length as f32 * self.cell_size, self.cell_size),
+        )
+    }
+}
+
+/// Scales the map to fit within the size of the minimap.
+#[derive(Clone, Copy, Debug)]
+struct Projection {
+    origin: Vector2,
+    offset: Vector2,
+    scale: f32,
+}
+
+impl Projection {
+    fn new(bounds: Rect2, size: Vector2) -> Self {
+        let scale = (size.x / bounds.width().max(1.0)).min(size.y / bounds.height().max(1.0));
+        Projection {
+            origin: bounds.origin.to_vector(),
+            offset: (size - bounds.size.to_vector() * scale) * 0.5,
+            scale,
+        }
+    }
+
+    #[inline]
+    fn point(&self, position: Vector2) -> Vector2 {
+        self.offset + (position - self.origin) * self.scale
+    }
+
+    #[inline]
+    fn rect(&self, rect: Rect2) -> Rect2 {
+        Rect2::new(
+            self.point(rect.origin.to_vector()).to_point(),
+            rect.size * self.scale,
+        )
+    }
+}
+
+/// Shows the ground, obstacles, pickups, the player and the enemies within
+/// radar range. Areas the player has not explored are covered by fog, areas
+/// the player does not see right now are darkened. The units and the fog are
+/// taken from the `Units` registry.
+#[derive(NativeClass)]
+#[inherit(Control)]
+#[register_with(Self::register)]
+pub struct Minimap {
+    settings: MinimapSettings,
+
+    fog: Option<Fog>,
+    bounds: Option<Rect2>,
+    player: Option<Blip>,
+    blips: Vec<Blip>,
+    elapsed: f32,
+}
+
+#[methods]
+impl Minimap {
+    fn register(builder: &ClassBuilder<Self>) {
+        MinimapSettings::register(
+            builder,
+            |m: &Self| &m.settings,
+            |m: &mut Self| &mut m.settings,
+        );
+    }
+
+    fn new(_owner: TRef<Control>) -> Self {
+        Minimap {
+            settings: MinimapSettings::default(),
+
+            fog: None,
+            bounds: None,
+            player: None,
+            blips: Vec::new(),
+            elapsed: 0.0,
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: TRef<Control>) {
+        owner.set_clip_contents(true);
+        self.refresh(owner);
+    }
+
+    #[export]
+    fn _process(&mut self, owner: TRef<Control>, delta: f32) {
+        self.elapsed += delta;
+        if self.elapsed < self.settings.update_interval {
+            return;
+        }
+
+        self.elapsed = 0.0;
+        self.refresh(owner);
+    }
+
+    #[export]
+    fn _draw(&self, owner: TRef<Control>) {
+        let settings = &self.settings;
+        let size = owner.size();
+        owner.draw_rect(
+            Rect2::new(Point2::zero(), size.to_size()),
+            settings.background_color,
+            true,
+            1.0,
+            false,
+        );
+
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let projection = Projection::new(bounds, size);
+        owner.draw_rect(
+            projection.rect(bounds),
+            settings.ground_color,
+            true,
+            1.0,
+            false,
+        );
+
+        for blip in self.blips.iter() {
+            match blip.kind {
+                UnitKind::Obstacle | UnitKind::Cover => {
+                    let extents = (blip.extents * projection.scale).max(Vector2::new(0.5, 0.5));
+                    owner.draw_rect(
+                        Rect2::new(
+                            (projection.point(blip.position) - extents).to_point(),
+                            (extents * 2.0).to_size(),
+                        ),
+                        settings.obstacle_color,
+                        true,
+                        1.0,
+                        false,
+                    );
+                }
+                UnitKind::Pickup => owner.draw_circle(
+                    projection.point(blip.position),
+                    settings.marker_size as f64 * 0.75,
+                    settings.pickup_color,
+                ),
+                _ => {}
+            }
+        }
+
+        if let Some(fog) = &self.fog {
+            for (runs, color) in [
+                (&fog.unexplored, settings.fog_color),
+                (&fog.hidden, settings.hidden_color),
+            ] {
+                for run in runs.iter() {
+                    owner.draw_rect(projection.rect(fog.run_rect(*run)), color, true, 1.0, false);
+                }
+            }
+        }
+
+        let player = match self.player {
+            Some(player) => player,
+            None => return,
+        };
+        let center = projection.point(player.position);
+        owner.draw_arc(
+            center,
+            (settings.radar_range * projection.scale) as f64,
+            0.0,
+            TAU,
+            48,
+            settings.radar_color,
+            1.0,
+            true,
+        );
+
+        for blip in self
+            .blips
+            .iter()
+            .filter(|blip| blip.kind == UnitKind::Enemy)
+        {
+            owner.draw_circle(
+                projection.point(blip.position),
+                settings.marker_size as f64,
+                settings.enemy_color,
+            );
+        }
+
+        let heading = Angle::radians(player.rotation);
+        let marker = settings.marker_size * 1.5;
+        let points = Vector2Array::from_vec(vec![
+            center + Vector2::new(marker, 0.0).rotated(heading),
+            center + Vector2::new(-marker, marker * 0.7).rotated(heading),
+            center + Vector2::new(-marker, -marker * 0.7).rotated(heading),
+        ]);
+        owner.draw_colored_polygon(
+            points,
+            settings.player_color,
+            Vector2Array::new(),
+            Null::null(),
+            Null::null(),
+            true,
+        );
+    }
+
+    /// Takes the positions of the units and the fog from the registry and
+    /// redraws the minimap.
+    fn refresh(&mut self, owner: TRef<Control>) {
+        let radar_range = self.settings.radar_range;
+        let snapshot = units::with_registry(owner.upcast::<Node>().as_ref(), |registry| {
+            let player = registry.player().map(Blip::from);
+            let blips = registry
+                .iter()
+                .filter(|unit| match unit.kind {
+                    // the radar sees enemies the player does not
+                    UnitKind::Enemy => true,
+                    UnitKind::Player => false,
+                    _ => unit.is_visible(),
+                })
+                .map(Blip::from)
+                .filter(|blip| match (blip.kind, player) {
+                    (UnitKind::Enemy, Some(player)) => {
+                        blip.position.distance_to(player.position) <= radar_range
+                    }
+                    (UnitKind::Enemy, None) => false,
+                    _ => true,
+                })
+                .collect::<Vec<_>>();
+            let fog = registry.vision().map(|vision| Fog {
+                origin: vision.bounds().origin.to_vector(),
+                cell_size: vision.cell_size(),
+                unexplored: vision.unexplored_runs(),
+                hidden: vision.hidden_runs(),
+            });
+
+            (registry.bounds(), player, blips, fog)
+        });
+
+        let (bounds, player, blips, fog) = match snapshot {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        self.bounds = bounds;
+        self.player = player;
+        self.blips = blips;
+        self.fog = fog;
+        owner.update();
+    }
+}
@@ -3,11 +3,13 @@
 // license that can be found in the LICENSE file.
 
 pub use hud::Hud;
+pub use minimap::Minimap;
 pub use unit_display::UnitDisplay;
 
 mod hud;
+mod minimap;
 mod unit_display;
 
-const RES_HEALTHBAR_RED_TEXTURE: &str = "res://ui/barHorizontal_red_mid 200.png";
-const RES_HEALTHBAR_YELLOW_TEXTURE: &str = "res://ui/barHorizontal_yellow_mid 200.png";
-const RES_HEALTHBAR_GREEN_TEXTURE: &str = "res://ui/barHorizontal_green_mid 200.png";
+pub const RES_HEALTHBAR_RED_TEXTURE: &str = "res://ui/barHorizontal_red_mid 200.png";
+pub const RES_HEALTHBAR_YELLOW_TEXTURE: &str = "res://ui/barHorizontal_yellow_mid 200.png";
+pub const RES_HEALTHBAR_GREEN_TEXTURE: &str = "res://ui/barHorizontal_green_mid 200.png";
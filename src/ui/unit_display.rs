@@ -5,39 +5,45 @@
 use gdnative::api::{Node2D, TextureProgress};
 use gdnative::prelude::*;
 
+use crate::resources::ResourceRef;
+use crate::scenes::ui::unit_display_tscn;
 use crate::ui::*;
 use crate::utils::node::NodeRef;
-use crate::utils::preload::*;
+use crate::utils::*;
 
 #[derive(NativeClass)]
 #[inherit(Node2D)]
 pub struct UnitDisplay {
-    healthbar_red_texture: Option<Ref<Texture>>,
-    healthbar_yellow_texture: Option<Ref<Texture>>,
-    healthbar_green_texture: Option<Ref<Texture>>,
+    healthbar_red_texture: ResourceRef<Texture>,
+    healthbar_yellow_texture: ResourceRef<Texture>,
 
     // child node(s)
     healthbar_node: NodeRef<TextureProgress>,
+    shieldbar_node: NodeRef<TextureProgress>,
 }
 
 #[methods]
 impl UnitDisplay {
     fn new(_owner: TRef<Node2D>) -> Self {
         UnitDisplay {
-            healthbar_red_texture: None,
-            healthbar_yellow_texture: None,
-            healthbar_green_texture: None,
+            healthbar_red_texture: ResourceRef::new(RES_HEALTHBAR_RED_TEXTURE),
+            healthbar_yellow_texture: ResourceRef::new(RES_HEALTHBAR_YELLOW_TEXTURE),
 
-            healthbar_node: NodeRef::new("HealthBar"),
+            healthbar_node: NodeRef::of::<unit_display_tscn::HealthBarNode>(),
+            shieldbar_node: NodeRef::of::<unit_display_tscn::ShieldBarNode>(),
         }
     }
 
     #[export]
     fn _ready(&mut self, owner: TRef<Node2D>) {
-        self.healthbar_red_texture = Some(preload::<Texture>(RES_HEALTHBAR_RED_TEXTURE));
-        self.healthbar_yellow_texture = Some(preload::<Texture>(RES_HEALTHBAR_YELLOW_TEXTURE));
-        self.healthbar_green_texture = Some(preload::<Texture>(RES_HEALTHBAR_GREEN_TEXTURE));
-        self.healthbar_node.get_from(owner.as_ref());
+        // the textures are shared by all instances through the resource
+        // cache, missing nodes and textures are reported once and the bars
+        // that use them are not updated
+        let node = owner.as_ref();
+        report_error(self.healthbar_red_texture.try_get_from(node));
+        report_error(self.healthbar_yellow_texture.try_get_from(node));
+        report_error(self.healthbar_node.try_get_from(node));
+        report_error(self.shieldbar_node.try_get_from(node));
         owner.hide();
     }
 
@@ -49,20 +55,35 @@ impl UnitDisplay {
     #[allow(non_snake_case)]
     #[export]
     fn _on_health_changed(&mut self, owner: TRef<Node2D>, value: f64) {
-        let mut change_texture: Option<&Ref<Texture>> = None;
+        let mut change_texture: Option<Ref<Texture>> = None;
         if value < 25.0 {
-            change_texture = self.healthbar_red_texture.as_ref();
+            change_texture = self.healthbar_red_texture.try_get_ref().ok();
         } else if value < 60.0 {
-            change_texture = self.healthbar_yellow_texture.as_ref();
+            change_texture = self.healthbar_yellow_texture.try_get_ref().ok();
         } else if value < 100.0 {
             owner.show();
         }
 
-        let healthbar = self.healthbar_node.get_ref();
+        let healthbar = match self.healthbar_node.try_get_ref() {
+            Ok(healthbar) => healthbar,
+            Err(_) => return,
+        };
         healthbar.set_value(value);
 
         if let Some(texture) = change_texture {
             healthbar.set_progress_texture(texture);
         }
     }
+
+    #[allow(non_snake_case)]
+    #[export]
+    fn _on_shield_changed(&mut self, owner: TRef<Node2D>, value: f64) {
+        if value < 100.0 {
+            owner.show();
+        }
+
+        if let Ok(shieldbar) = self.shieldbar_node.try_get_ref() {
+            shieldbar.set_value(value);
+        }
+    }
 }
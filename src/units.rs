@@ -0,0 +1,327 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use std::collections::BTreeMap;
+
+use gdnative::prelude::*;
+
+use crate::fog::{Team, VisionGrid, VISION_CELL_SIZE};
+use crate::utils::SingletonInstance;
+
+/// Seconds between updates of the vision grid.
+const VISION_INTERVAL: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnitKind {
+    Player,
+    Enemy,
+    Obstacle,
+    /// An obstacle that does not block the line of sight.
+    Cover,
+    Pickup,
+}
+
+impl UnitKind {
+    #[inline]
+    pub fn all() -> Vec<UnitKind> {
+        vec![
+            Self::Player,
+            Self::Enemy,
+            Self::Obstacle,
+            Self::Cover,
+            Self::Pickup,
+        ]
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Player => "player",
+            Self::Enemy => "enemy",
+            Self::Obstacle => "obstacle",
+            Self::Cover => "cover",
+            Self::Pickup => "pickup",
+        }
+    }
+
+    /// The team that shares what units of this kind see.
+    #[inline]
+    pub fn team(&self) -> Option<Team> {
+        match self {
+            Self::Player => Some(Team::Player),
+            Self::Enemy => Some(Team::Enemy),
+            _ => None,
+        }
+    }
+}
+
+/// A node in the scene tree that other systems, like the minimap, need to
+/// know about.
+#[derive(Clone, Debug)]
+pub struct Unit {
+    pub id: i64,
+    pub kind: UnitKind,
+    pub node: Ref<Node2D>,
+    /// Half of the size of the unit, zero when its size does not matter.
+    pub extents: Vector2,
+    /// How far the unit can see, zero when it does not see.
+    pub vision: f32,
+}
+
+impl Unit {
+    #[inline]
+    pub fn position(&self) -> Vector2 {
+        unsafe { self.node.assume_safe() }.global_position()
+    }
+
+    #[inline]
+    pub fn rotation(&self) -> f32 {
+        unsafe { self.node.assume_safe() }.global_rotation() as f32
+    }
+
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        unsafe { self.node.assume_safe() }.is_visible_in_tree()
+    }
+
+    #[inline]
+    pub fn set_visible(&self, visible: bool) {
+        unsafe { self.node.assume_safe() }.set_visible(visible);
+    }
+
+    /// The area the unit covers, in global coordinates.
+    #[inline]
+    pub fn rect(&self) -> Rect2 {
+        Rect2::new(
+            (self.position() - self.extents).to_point(),
+            (self.extents * 2.0).to_size(),
+        )
+    }
+}
+
+/// The active units, by instance id. A unit is removed as soon as its node
+/// leaves the scene tree, so the nodes of all units are safe to use.
+#[derive(Default)]
+pub struct UnitRegistry {
+    units: BTreeMap<i64, Unit>,
+    bounds: Option<Rect2>,
+    vision: Option<VisionGrid>,
+    blockers_changed: bool,
+}
+
+impl UnitRegistry {
+    pub fn new() -> Self {
+        UnitRegistry::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    #[inline]
+    pub fn insert(&mut self, unit: Unit) {
+        self.blockers_changed |= unit.kind == UnitKind::Obstacle;
+        self.units.insert(unit.id, unit);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, id: i64) -> Option<Unit> {
+        let unit = self.units.remove(&id);
+        self.blockers_changed |= unit
+            .as_ref()
+            .is_some_and(|unit| unit.kind == UnitKind::Obstacle);
+        unit
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Unit> {
+        self.units.values()
+    }
+
+    #[inline]
+    pub fn of_kind(&self, kind: UnitKind) -> impl Iterator<Item = &Unit> {
+        self.iter().filter(move |unit| unit.kind == kind)
+    }
+
+    #[inline]
+    pub fn player(&self) -> Option<&Unit> {
+        self.of_kind(UnitKind::Player).next()
+    }
+
+    /// The area of the current map, in global coordinates.
+    #[inline]
+    pub fn bounds(&self) -> Option<Rect2> {
+        self.bounds
+    }
+
+    /// Sets the area of the current map, which starts a new vision grid.
+    #[inline]
+    pub fn set_bounds(&mut self, bounds: Rect2) {
+        self.bounds = Some(bounds);
+        self.vision = Some(VisionGrid::new(bounds, VISION_CELL_SIZE));
+        self.blockers_changed = true;
+    }
+
+    /// What the teams see and what the player has explored, once the area of
+    /// the map is known.
+    #[inline]
+    pub fn vision(&self) -> Option<&VisionGrid> {
+        self.vision.as_ref()
+    }
+
+    /// Whether `team` sees `position`. Everything is visible when there is
+    /// no vision grid.
+    #[inline]
+    pub fn is_visible_to(&self, team: Team, position: Vector2) -> bool {
+        self.vision
+            .as_ref()
+            .is_none_or(|vision| vision.is_visible_at(team, position))
+    }
+
+    /// Recomputes what each team sees from the positions of its units, with
+    /// obstacles blocking the line of sight.
+    pub fn update_vision(&mut self) {
+        let vision = match self.vision.as_mut() {
+            Some(vision) => vision,
+            None => return,
+        };
+
+        if self.blockers_changed {
+            self.blockers_changed = false;
+            vision.clear_blocked();
+            for unit in self.units.values() {
+                if unit.kind == UnitKind::Obstacle {
+                    vision.block_rect(unit.rect());
+                }
+            }
+        }
+
+        vision.clear_visible();
+        for unit in self.units.values().filter(|unit| unit.vision > 0.0) {
+            if let Some(team) = unit.kind.team() {
+                vision.reveal(team, unit.position(), unit.vision);
+            }
+        }
+    }
+}
+
+/// Autoloaded node that keeps the registry of active units.
+#[derive(NativeClass)]
+#[inherit(Node)]
+pub struct Units {
+    registry: UnitRegistry,
+    elapsed: f32,
+}
+
+#[methods]
+impl Units {
+    fn new(_owner: &Node) -> Self {
+        Units {
+            registry: UnitRegistry::new(),
+            elapsed: 0.0,
+        }
+    }
+
+    #[export]
+    fn _process(&mut self, _owner: &Node, delta: f32) {
+        self.elapsed += delta;
+        if self.elapsed < VISION_INTERVAL {
+            return;
+        }
+
+        self.elapsed = 0.0;
+        self.registry.update_vision();
+
+        // enemies are hidden outside the vision of the player, as long as
+        // there is a player to see them
+        if self.registry.player().is_none() {
+            return;
+        }
+        for unit in self.registry.of_kind(UnitKind::Enemy) {
+            unit.set_visible(self.registry.is_visible_to(Team::Player, unit.position()));
+        }
+    }
+
+    #[export]
+    fn get_unit_count(&self, _owner: &Node, kind: String) -> usize {
+        match UnitKind::all().into_iter().find(|typ| typ.name() == kind) {
+            Some(kind) => self.registry.of_kind(kind).count(),
+            None => self.registry.len(),
+        }
+    }
+
+    #[export]
+    fn _on_unit_tree_exiting(&mut self, _owner: &Node, id: i64) {
+        self.registry.remove(id);
+    }
+}
+
+impl SingletonInstance<Self, Node> for Units {
+    #[inline]
+    fn node_path<'a>() -> &'a str {
+        "/root/Units"
+    }
+}
+
+/// Adds `node` to the registry until it leaves the scene tree. Units of a
+/// team see `vision` pixels far. Does nothing when the `Units` autoload is not
+/// available, e.g. in the editor.
+pub fn register(node: TRef<Node2D>, kind: UnitKind, extents: Vector2, vision: f32) {
+    let units = match Units::try_singleton(node.upcast::<Node>().as_ref()) {
+        Some(units) => units,
+        None => return,
+    };
+
+    let id = node.get_instance_id();
+    let _ = units.map_mut(|units, _| {
+        units.registry.insert(Unit {
+            id,
+            kind,
+            node: node.claim(),
+            extents,
+            vision,
+        })
+    });
+
+    let binds = VariantArray::new();
+    binds.push(id);
+    if let Err(err) = node.connect(
+        "tree_exiting",
+        units.base(),
+        "_on_unit_tree_exiting",
+        binds.into_shared(),
+        Object::CONNECT_ONESHOT,
+    ) {
+        godot_error!("Failed to register unit `{}`: {:?}", node.name(), err);
+    }
+}
+
+/// Sets the area of the current map.
+pub fn set_bounds(node: &Node, bounds: Rect2) {
+    if let Some(units) = Units::try_singleton(node) {
+        let _ = units.map_mut(|units, _| units.registry.set_bounds(bounds));
+    }
+}
+
+/// Whether `team` sees `position`. Everything is visible when the `Units`
+/// autoload is not available.
+pub fn is_visible_to(node: &Node, team: Team, position: Vector2) -> bool {
+    with_registry(node, |registry| registry.is_visible_to(team, position)).unwrap_or(true)
+}
+
+/// Calls `func` with the registry, returns `None` when the `Units` autoload is
+/// not available.
+pub fn with_registry<F, R>(node: &Node, func: F) -> Option<R>
+where
+    F: FnOnce(&UnitRegistry) -> R,
+{
+    Units::try_singleton(node).and_then(|units| units.map(|units, _| func(&units.registry)).ok())
+}
@@ -4,21 +4,36 @@
 
 use gdnative::prelude::*;
 
+use crate::utils::error::*;
+
 pub trait InstanceFrom<T, U>
 where
     T: NativeClass + NativeClass<Base = U>,
     U: GodotObject + SubClass<Node>,
 {
     #[inline]
-    fn try_instance_from<'l>(node: Ref<Node>) -> Option<RefInstance<'l, T, Shared>> {
-        unsafe { node.assume_safe() }
-            .cast::<U>()?
-            .cast_instance::<T>()
+    fn try_instance_from<'l>(node: Ref<Node>) -> NodeResult<RefInstance<'l, T, Shared>> {
+        let node = unsafe { node.assume_safe() };
+        let base = node.cast::<U>().ok_or_else(|| {
+            let found = node.get_class().to_string();
+            NodeError::new(
+                NodeErrorKind::WrongClass(found),
+                describe_node(&node),
+                U::class_name(),
+            )
+        })?;
+
+        base.cast_instance::<T>().ok_or_else(|| {
+            NodeError::new(
+                NodeErrorKind::NoInstance,
+                describe_node(&node),
+                T::class_name(),
+            )
+        })
     }
 
     #[inline]
     fn instance_from<'l>(node: Ref<Node>) -> RefInstance<'l, T, Shared> {
-        Self::try_instance_from(node)
-            .unwrap_or_else(|| panic!("Failed to convert node {:?} to {}", node, T::class_name()))
+        Self::try_instance_from(node).unwrap_or_else(|err| panic!("{}", err))
     }
 }
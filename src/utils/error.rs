@@ -0,0 +1,94 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+#![allow(dead_code)]
+
+use std::error::Error;
+use std::fmt;
+
+use gdnative::prelude::*;
+
+pub type NodeResult<T> = Result<T, NodeError>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeErrorKind {
+    /// There is no node or resource at the path.
+    NotFound,
+    /// The node or resource exists, but is of the contained class.
+    WrongClass(String),
+    /// A `NodeRef` was used before its node was found.
+    Unresolved,
+    /// The node does not have an instance of the expected `NativeClass`.
+    NoInstance,
+    /// A scene could not be instanced.
+    InstanceFailed,
+}
+
+/// Error returned by the fallible node and resource utilities.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeError {
+    pub kind: NodeErrorKind,
+    /// Path of the node, resource or scene.
+    pub path: String,
+    /// Name of the class that was expected.
+    pub expected: &'static str,
+    /// Path of the node from which the path was resolved, if any.
+    pub owner: Option<String>,
+}
+
+impl NodeError {
+    #[inline]
+    pub fn new(kind: NodeErrorKind, path: impl ToString, expected: &'static str) -> Self {
+        NodeError {
+            kind,
+            path: path.to_string(),
+            expected,
+            owner: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_owner(self, owner: &Node) -> Self {
+        NodeError {
+            owner: Some(describe_node(owner)),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to get `{}` as {}", self.path, self.expected)?;
+        if let Some(owner) = &self.owner {
+            write!(f, " from `{}`", owner)?;
+        }
+
+        match &self.kind {
+            NodeErrorKind::NotFound => write!(f, ", it does not exist"),
+            NodeErrorKind::WrongClass(found) => write!(f, ", it is a {}", found),
+            NodeErrorKind::Unresolved => write!(f, ", make sure it is found first"),
+            NodeErrorKind::NoInstance => write!(f, ", it has no instance of that class"),
+            NodeErrorKind::InstanceFailed => write!(f, ", the scene cannot be instanced"),
+        }
+    }
+}
+
+impl Error for NodeError {}
+
+/// Path of `node` when it is inside the scene tree, otherwise its name.
+#[inline]
+pub fn describe_node(node: &Node) -> String {
+    if node.is_inside_tree() {
+        node.get_path().to_string()
+    } else {
+        node.name().to_string()
+    }
+}
+
+/// Reports a failed `result` with `godot_error!` and returns its value, if
+/// any.
+#[inline]
+pub fn report_error<T>(result: NodeResult<T>) -> Option<T> {
+    result.map_err(|err| godot_error!("{}", err)).ok()
+}
@@ -0,0 +1,162 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+use gdnative::nativescript::init::property::hint::{FloatHint, IntHint, RangeHint};
+use gdnative::nativescript::init::property::Export;
+use gdnative::nativescript::Map;
+use gdnative::prelude::user_data::MapMut;
+use gdnative::prelude::*;
+
+pub use game_derive::ExportGroup;
+
+/// A value that is exported as a property of type `Export`. Values that have
+/// no Godot counterpart are converted, e.g. a list of enum values is exported
+/// as a `StringArray` of their names.
+pub trait ExportValue {
+    type Export: Export + FromVariant + ToVariant;
+
+    fn to_export(&self) -> Self::Export;
+
+    fn set_export(&mut self, value: Self::Export);
+}
+
+macro_rules! impl_export_value {
+    ($($ty:ty),*) => {
+        $(
+            impl ExportValue for $ty {
+                type Export = $ty;
+
+                #[inline]
+                fn to_export(&self) -> Self::Export {
+                    self.clone()
+                }
+
+                #[inline]
+                fn set_export(&mut self, value: Self::Export) {
+                    *self = value;
+                }
+            }
+        )*
+    };
+}
+
+impl_export_value!(bool, f32, f64, i32, i64, u8, u16, u32, String, Vector2, Color);
+
+impl<T: GodotObject> ExportValue for Ref<T, Shared> {
+    type Export = Ref<T, Shared>;
+
+    #[inline]
+    fn to_export(&self) -> Self::Export {
+        self.clone()
+    }
+
+    #[inline]
+    fn set_export(&mut self, value: Self::Export) {
+        *self = value;
+    }
+}
+
+/// A group of fields that are registered as properties of the `NativeClass`
+/// that embeds it. Implement it with `#[derive(ExportGroup)]`.
+pub trait ExportGroup: Sized + 'static {
+    /// Registers the exported fields of the group, with `prefix` in front of
+    /// their names. `get` and `get_mut` access the group within the class,
+    /// `changed` is called after any of the fields is set.
+    fn register_group_with<C, G, M, N>(
+        builder: &ClassBuilder<C>,
+        prefix: &str,
+        default: &Self,
+        get: G,
+        get_mut: M,
+        changed: N,
+    ) where
+        C: NativeClass,
+        C::UserData: Map + MapMut,
+        G: Fn(&C) -> &Self + Clone + 'static,
+        M: Fn(&mut C) -> &mut Self + Clone + 'static,
+        N: Fn(&mut C, TRef<C::Base>) + Clone + 'static;
+
+    /// Registers the exported fields of the group, with `prefix` in front of
+    /// their names. `get` and `get_mut` access the group within the class.
+    #[inline]
+    fn register_group<C, G, M>(
+        builder: &ClassBuilder<C>,
+        prefix: &str,
+        default: &Self,
+        get: G,
+        get_mut: M,
+    ) where
+        C: NativeClass,
+        C::UserData: Map + MapMut,
+        G: Fn(&C) -> &Self + Clone + 'static,
+        M: Fn(&mut C) -> &mut Self + Clone + 'static,
+    {
+        Self::register_group_with(builder, prefix, default, get, get_mut, |_: &mut C, _| {})
+    }
+
+    /// Registers the exported fields of the group with the values of its
+    /// default as their defaults.
+    #[inline]
+    fn register<C, G, M>(builder: &ClassBuilder<C>, get: G, get_mut: M)
+    where
+        Self: Default,
+        C: NativeClass,
+        C::UserData: Map + MapMut,
+        G: Fn(&C) -> &Self + Clone + 'static,
+        M: Fn(&mut C) -> &mut Self + Clone + 'static,
+    {
+        Self::register_group(builder, "", &Self::default(), get, get_mut)
+    }
+}
+
+/// Composes `get` with the accessor of a field of the group it returns.
+#[inline]
+pub fn field_ref<C, G, T, U>(get: G, field: fn(&T) -> &U) -> impl Fn(&C) -> &U + Clone + 'static
+where
+    G: Fn(&C) -> &T + Clone + 'static,
+    T: 'static,
+    U: 'static,
+{
+    move |t: &C| field(get(t))
+}
+
+/// Composes `get_mut` with the mutable accessor of a field of the group it
+/// returns.
+#[inline]
+pub fn field_mut<C, M, T, U>(
+    get_mut: M,
+    field: fn(&mut T) -> &mut U,
+) -> impl Fn(&mut C) -> &mut U + Clone + 'static
+where
+    M: Fn(&mut C) -> &mut T + Clone + 'static,
+    T: 'static,
+    U: 'static,
+{
+    move |t: &mut C| field(get_mut(t))
+}
+
+/// An exported number type that can be limited to a range in the editor.
+pub trait RangeExport: Export {
+    fn range_hint(min: f64, max: f64, step: Option<f64>) -> Self::Hint;
+}
+
+macro_rules! impl_range_export {
+    ($hint:ident: $($ty:ty),*) => {
+        $(
+            impl RangeExport for $ty {
+                #[inline]
+                fn range_hint(min: f64, max: f64, step: Option<f64>) -> Self::Hint {
+                    let mut range = RangeHint::new(min as $ty, max as $ty);
+                    if let Some(step) = step {
+                        range = range.with_step(step as $ty);
+                    }
+                    $hint::Range(range)
+                }
+            }
+        )*
+    };
+}
+
+impl_range_export!(FloatHint: f32, f64);
+impl_range_export!(IntHint: i32, i64, u8, u16, u32);
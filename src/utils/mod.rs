@@ -3,11 +3,19 @@
 // license that can be found in the LICENSE file.
 
 pub use convert::*;
+pub use error::*;
+pub use export::*;
+pub use record::*;
+pub use rng::*;
 pub use scene::*;
 pub use singleton::*;
 
 mod convert;
+mod error;
+mod export;
 pub mod node;
 pub mod preload;
+mod record;
+mod rng;
 mod scene;
 mod singleton;
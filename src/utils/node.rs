@@ -6,8 +6,11 @@
 
 use gdnative::prelude::*;
 
+use crate::scenes::ChildNodeRef;
+use crate::utils::error::*;
+
 #[inline]
-pub unsafe fn get_parent_as<U>(node: &Node) -> Option<TRef<U>>
+pub unsafe fn get_parent_as<U>(node: &Node) -> Option<TRef<'_, U>>
 where
     U: SubClass<Node>,
 {
@@ -15,15 +18,12 @@ where
 }
 
 #[inline]
-pub fn with_parent_as<U, F, R>(node: &Node, mut func: F) -> Option<R>
+pub fn with_parent_as<U, F, R>(node: &Node, func: F) -> Option<R>
 where
     U: SubClass<Node>,
-    F: FnMut(TRef<U>) -> R,
+    F: FnOnce(TRef<U>) -> R,
 {
-    match unsafe { get_parent_as::<U>(node) } {
-        Some(parent) => Some(func(parent)),
-        _ => None,
-    }
+    unsafe { get_parent_as::<U>(node) }.map(func)
 }
 
 #[inline]
@@ -31,8 +31,44 @@ pub fn get_node_as<'l, U>(owner: &Node, path: &str) -> TRef<'l, U>
 where
     U: SubClass<Node>,
 {
-    unsafe { owner.get_node_as::<U>(path) }
-        .unwrap_or_else(|| panic!("Failed to get {} as {}", path, U::class_name()))
+    try_get_node_as(owner, path).unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[inline]
+pub fn try_get_node_as<'l, U>(owner: &Node, path: &str) -> NodeResult<TRef<'l, U>>
+where
+    U: SubClass<Node>,
+{
+    let node = owner
+        .get_node_or_null(path)
+        .map(|node| unsafe { node.assume_safe() })
+        .ok_or_else(|| {
+            NodeError::new(NodeErrorKind::NotFound, path, U::class_name()).with_owner(owner)
+        })?;
+
+    node.cast::<U>().ok_or_else(|| {
+        let found = node.get_class().to_string();
+        NodeError::new(NodeErrorKind::WrongClass(found), path, U::class_name()).with_owner(owner)
+    })
+}
+
+/// Gets the node of scene node type `N`, see `crate::scenes`.
+#[inline]
+pub fn get_child_as<'l, N>(owner: &Node) -> TRef<'l, N::Base>
+where
+    N: ChildNodeRef,
+    N::Base: SubClass<Node>,
+{
+    get_node_as(owner, N::path())
+}
+
+#[inline]
+pub fn try_get_child_as<'l, N>(owner: &Node) -> NodeResult<TRef<'l, N::Base>>
+where
+    N: ChildNodeRef,
+    N::Base: SubClass<Node>,
+{
+    try_get_node_as(owner, N::path())
 }
 
 #[inline]
@@ -65,6 +101,12 @@ where
         }
     }
 
+    /// A reference to the node of scene node type `N`, see `crate::scenes`.
+    #[inline]
+    pub fn of<N: ChildNodeRef<Base = T>>() -> Self {
+        Self::new(N::path())
+    }
+
     #[inline]
     pub fn path(&self) -> &str {
         self.path.as_str()
@@ -78,32 +120,32 @@ where
     #[inline]
     pub fn get_from(&mut self, owner: &Node) -> Ref<T> {
         self.try_get_from(owner)
-            .unwrap_or_else(|| panic!("Failed to find node `{}`", self.path))
+            .unwrap_or_else(|err| panic!("{}", err))
     }
 
+    /// Finds the node relative to `owner` and keeps a reference to it. On
+    /// failure a previously found node is kept.
     #[inline]
-    pub fn try_get_from(&mut self, owner: &Node) -> Option<Ref<T>> {
-        if let Some(node_tref) = unsafe { owner.get_node_as::<T>(self.path()) } {
-            self.node = Some(node_tref.claim())
-        }
-
-        self.node
+    pub fn try_get_from(&mut self, owner: &Node) -> NodeResult<Ref<T>> {
+        let node = try_get_node_as::<T>(owner, self.path())?.claim();
+        self.node = Some(node);
+        Ok(node)
     }
 
     #[inline]
     pub fn get_ref<'l>(&self) -> TRef<'l, T> {
-        if self.node.is_none() {
-            panic!(
-                "Failed to get node `{}`, make sure it is found first.",
-                self.path
-            )
-        }
+        self.try_get_ref().unwrap_or_else(|err| panic!("{}", err))
+    }
 
-        unsafe { self.node.unwrap().assume_safe() }
+    #[inline]
+    pub fn try_get_ref<'l>(&self) -> NodeResult<TRef<'l, T>> {
+        self.node
+            .map(|node| unsafe { node.assume_safe() })
+            .ok_or_else(|| NodeError::new(NodeErrorKind::Unresolved, self.path(), T::class_name()))
     }
 
     #[inline]
-    pub fn get_ref_or_from(&mut self, owner: &Node) -> TRef<T> {
+    pub fn get_ref_or_from(&mut self, owner: &Node) -> TRef<'_, T> {
         if self.node.is_none() {
             self.get_from(owner);
         }
@@ -2,12 +2,17 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
-use gdnative::api::Resource;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use gdnative::api::{Resource, ResourceInteractiveLoader};
 use gdnative::prelude::*;
 
+use crate::utils::error::*;
+
 #[allow(dead_code)]
 #[inline]
-pub fn try_preload<T>(path: &str, type_hint: &str) -> Option<Ref<T, Shared>>
+pub fn try_preload<T>(path: &str, type_hint: &str) -> NodeResult<Ref<T, Shared>>
 where
     T: GodotObject<RefKind = <Resource as GodotObject>::RefKind>
         + gdnative::prelude::SubClass<Resource>,
@@ -17,11 +22,16 @@ where
         type_hint = T::class_name()
     }
 
-    ResourceLoader::godot_singleton()
+    let resource = ResourceLoader::godot_singleton()
         .load(path, type_hint, false)
         .map(|resource| unsafe { resource.assume_unique() })
         .map(|resource| resource.into_shared())
-        .and_then(|resource| resource.cast::<T>())
+        .ok_or_else(|| NodeError::new(NodeErrorKind::NotFound, path, T::class_name()))?;
+
+    let found = unsafe { resource.assume_safe() }.get_class().to_string();
+    resource
+        .cast::<T>()
+        .ok_or_else(|| NodeError::new(NodeErrorKind::WrongClass(found), path, T::class_name()))
 }
 
 #[allow(dead_code)]
@@ -41,47 +51,225 @@ where
     T: GodotObject<RefKind = <Resource as GodotObject>::RefKind>
         + gdnative::prelude::SubClass<Resource>,
 {
-    try_preload(path, type_hint)
-        .unwrap_or_else(|| panic!("Failed to preload `{}`", path.to_string()))
+    try_preload(path, type_hint).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// A resource that is loaded ahead of time, see `PreloadQueue`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Preload {
+    pub path: &'static str,
+    /// Name of the resource's class.
+    pub type_hint: &'static str,
+}
+
+impl Preload {
+    #[inline]
+    pub const fn new(path: &'static str, type_hint: &'static str) -> Self {
+        Preload { path, type_hint }
+    }
+}
+
+/// Loaded resources by path and class, so every user of a resource shares the
+/// same instance and it stays loaded while nothing else references it.
+/// Resources that fail to load are remembered and not loaded again.
+#[derive(Default)]
+pub struct ResourceCache {
+    resources: HashMap<(String, &'static str), Ref<Resource>>,
+    missing: Vec<NodeError>,
+}
+
+impl ResourceCache {
+    pub fn new() -> Self {
+        ResourceCache::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    #[allow(dead_code)]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    #[inline]
+    pub fn contains(&self, path: &str, type_hint: &'static str) -> bool {
+        self.resources.contains_key(&(path.to_string(), type_hint))
+    }
+
+    /// The resources that could not be loaded.
+    #[inline]
+    pub fn missing(&self) -> &[NodeError] {
+        self.missing.as_slice()
+    }
+
+    #[inline]
+    pub fn is_missing(&self, path: &str, type_hint: &'static str) -> bool {
+        self.missing
+            .iter()
+            .any(|err| err.path == path && err.expected == type_hint)
+    }
+
+    /// Returns the cached resource at `path`, loading it when it is not in
+    /// the cache yet.
+    pub fn try_load<T>(&mut self, path: &str) -> NodeResult<Ref<T, Shared>>
+    where
+        T: GodotObject<RefKind = <Resource as GodotObject>::RefKind>
+            + gdnative::prelude::SubClass<Resource>,
+    {
+        let key = (path.to_string(), T::class_name());
+        if let Some(err) = self
+            .missing
+            .iter()
+            .find(|err| err.path == key.0 && err.expected == key.1)
+        {
+            return Err(err.clone());
+        }
+
+        let resource = match self.resources.get(&key) {
+            Some(resource) => resource.clone(),
+            None => return self.record(path, key.1, try_preload::<T>(path, "")),
+        };
+
+        let found = unsafe { resource.assume_safe() }.get_class().to_string();
+        resource
+            .cast::<T>()
+            .ok_or_else(|| NodeError::new(NodeErrorKind::WrongClass(found), path, T::class_name()))
+    }
+
+    #[inline]
+    pub fn insert(&mut self, path: &str, type_hint: &'static str, resource: Ref<Resource>) {
+        self.resources
+            .insert((path.to_string(), type_hint), resource);
+    }
+
+    /// Drops all cached resources, e.g. before the engine shuts down.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.resources.clear();
+    }
+
+    fn record<T>(
+        &mut self,
+        path: &str,
+        type_hint: &'static str,
+        result: NodeResult<Ref<T, Shared>>,
+    ) -> NodeResult<Ref<T, Shared>>
+    where
+        T: GodotObject<RefKind = <Resource as GodotObject>::RefKind>
+            + gdnative::prelude::SubClass<Resource>,
+    {
+        match result {
+            Ok(resource) => {
+                self.insert(path, type_hint, resource.clone().upcast::<Resource>());
+                Ok(resource)
+            }
+            Err(err) => {
+                self.missing.push(err.clone());
+                Err(err)
+            }
+        }
+    }
 }
 
-// pub struct ResourceRef<T>
-//     where T: GodotObject<RefKind=<Resource as GodotObject>::RefKind> + gdnative::prelude::SubClass<Resource>
-// {
-//     resource: Option<Ref<T>>,
-//     type_hint: String,
-// }
-//
-// impl<T> ResourceRef<T>
-//     where T: GodotObject<RefKind=<Resource as GodotObject>::RefKind> + gdnative::prelude::SubClass<Resource>
-// {
-//     #[allow(dead_code)]
-//     #[inline]
-//     pub fn get_type_hint(&self) -> &str { self.type_hint.as_str() }
-//
-//     #[allow(dead_code)]
-//     pub fn preload(&mut self, path: &str) {
-//         self.resource = Some(preload::<T>(path, self.type_hint.as_str()));
-//     }
-//
-//     #[allow(dead_code)]
-//     pub fn try_preload(&mut self, path: &str) {
-//         self.resource = try_preload::<T>(path, self.type_hint.as_str());
-//     }
-//
-//     #[allow(dead_code)]
-//     pub fn get_ref(&self) -> &Ref<T> {
-//         self.resource.as_ref().expect("Failed to get resource")
-//     }
-// }
-//
-// impl<T> Default for ResourceRef<T>
-//     where T: GodotObject<RefKind=<Resource as GodotObject>::RefKind> + gdnative::prelude::SubClass<Resource> {
-//     #[inline]
-//     fn default() -> Self {
-//         ResourceRef {
-//             resource: None,
-//             type_hint: String::from(T::class_name()),
-//         }
-//     }
-// }
+/// Loads a list of resources into a `ResourceCache` in small steps, so it can
+/// be polled every frame without blocking the game.
+#[derive(Default)]
+pub struct PreloadQueue {
+    pending: VecDeque<Preload>,
+    current: Option<(Preload, Ref<ResourceInteractiveLoader>)>,
+}
+
+impl PreloadQueue {
+    pub fn new() -> Self {
+        PreloadQueue::default()
+    }
+
+    #[inline]
+    pub fn extend(&mut self, manifest: &[Preload]) {
+        self.pending.extend(manifest.iter().copied());
+    }
+
+    /// The number of resources that still need to be loaded.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.pending.len() + self.current.iter().count()
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Loads resources into `cache` for at most `budget`. Returns true when
+    /// all resources are loaded.
+    pub fn poll(&mut self, cache: &mut ResourceCache, budget: Duration) -> bool {
+        let started = Instant::now();
+        while started.elapsed() < budget {
+            let (preload, loader) = match self.current.take() {
+                Some(current) => current,
+                None => match self.start_next(cache) {
+                    Some(current) => current,
+                    None => return true,
+                },
+            };
+
+            match unsafe { loader.assume_safe() }.poll() {
+                Ok(()) => self.current = Some((preload, loader)),
+                Err(GodotError::FileEof) => {
+                    let resource = unsafe { loader.assume_safe() }.get_resource();
+                    Self::finish(cache, preload, resource);
+                }
+                Err(err) => {
+                    godot_error!("Failed to preload `{}`: {:?}", preload.path, err);
+                    Self::finish(cache, preload, None);
+                }
+            }
+        }
+        self.is_done()
+    }
+
+    fn start_next(
+        &mut self,
+        cache: &mut ResourceCache,
+    ) -> Option<(Preload, Ref<ResourceInteractiveLoader>)> {
+        while let Some(preload) = self.pending.pop_front() {
+            if cache.contains(preload.path, preload.type_hint)
+                || cache.is_missing(preload.path, preload.type_hint)
+            {
+                continue;
+            }
+
+            match ResourceLoader::godot_singleton()
+                .load_interactive(preload.path, preload.type_hint)
+            {
+                Some(loader) => return Some((preload, loader)),
+                None => Self::finish(cache, preload, None),
+            }
+        }
+        None
+    }
+
+    fn finish(cache: &mut ResourceCache, preload: Preload, resource: Option<Ref<Resource>>) {
+        let result = resource
+            .ok_or_else(|| NodeError::new(NodeErrorKind::NotFound, preload.path, preload.type_hint))
+            .and_then(|resource| {
+                let found = unsafe { resource.assume_safe() }.get_class().to_string();
+                if unsafe { resource.assume_safe() }.is_class(preload.type_hint) {
+                    Ok(resource)
+                } else {
+                    Err(NodeError::new(
+                        NodeErrorKind::WrongClass(found),
+                        preload.path,
+                        preload.type_hint,
+                    ))
+                }
+            });
+
+        if let Err(err) = cache.record(preload.path, preload.type_hint, result) {
+            godot_error!("{}", err);
+        }
+    }
+}
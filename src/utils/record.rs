@@ -0,0 +1,268 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Reading of the versioned text files of the game, like levels, the obstacle
+//! atlas and the tile definition. Each line is a record of a keyword and its
+//! whitespace separated fields. The first record is the keyword of the format
+//! and its version, empty lines and lines starting with `#` are skipped.
+
+use std::fmt;
+use std::str::{FromStr, SplitWhitespace};
+
+use gdnative::prelude::*;
+
+/// An error in a record file and the line it is on, starting at 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl RecordError {
+    /// An error about the file `text` as a whole, which is put on its last
+    /// line.
+    #[inline]
+    pub fn at_end(text: &str, message: String) -> Self {
+        RecordError {
+            line: text.lines().count(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// Reads the records of `text`, a file of `format` of which versions up to
+/// `latest` can be read. Returns the version of the file and the records
+/// after the version record.
+pub fn read_records<'a>(
+    text: &'a str,
+    format: &str,
+    latest: u32,
+) -> Result<(u32, Vec<Record<'a>>), RecordError> {
+    let mut records = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, text)| Record::new(line, text));
+
+    let mut first = match records.next() {
+        Some(record) if record.keyword == format => record,
+        Some(record) => {
+            return Err(record.error(format!("expected the `{}` record first", format)));
+        }
+        None => {
+            return Err(RecordError::at_end(
+                text,
+                format!("expected the `{}` record first", format),
+            ));
+        }
+    };
+    let version = first.number::<u32>("version")?;
+    if version == 0 || version > latest {
+        return Err(first.error(format!(
+            "{} version {} is not supported, the latest is {}",
+            format, version, latest
+        )));
+    }
+    first.end()?;
+
+    Ok((version, records.collect()))
+}
+
+/// A record of a keyword and its fields. Reading the fields consumes them,
+/// `end` checks that they are all read.
+#[derive(Clone, Debug)]
+pub struct Record<'a> {
+    /// The line of the record, starting at 1.
+    pub line: usize,
+    pub keyword: &'a str,
+    rest: &'a str,
+    fields: SplitWhitespace<'a>,
+}
+
+impl<'a> Record<'a> {
+    #[inline]
+    fn new(line: usize, text: &'a str) -> Self {
+        let (keyword, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        Record {
+            line,
+            keyword,
+            rest,
+            fields: rest.split_whitespace(),
+        }
+    }
+
+    #[inline]
+    pub fn error(&self, message: String) -> RecordError {
+        RecordError {
+            line: self.line,
+            message,
+        }
+    }
+
+    /// Everything after the keyword, as one field.
+    #[inline]
+    pub fn rest(&mut self) -> &'a str {
+        self.fields = "".split_whitespace();
+        self.rest.trim()
+    }
+
+    #[inline]
+    pub fn text(&mut self, name: &str) -> Result<String, RecordError> {
+        match self.fields.next() {
+            Some(field) => Ok(String::from(field)),
+            None => Err(self.error(format!("missing {}", name))),
+        }
+    }
+
+    /// A field parsed as `T`, which describes why it is invalid.
+    pub fn parse<T: FromStr<Err = String>>(&mut self, name: &str) -> Result<T, RecordError> {
+        let field = self.text(name)?;
+        field.parse().map_err(|message| self.error(message))
+    }
+
+    pub fn number<T: FromStr>(&mut self, name: &str) -> Result<T, RecordError> {
+        let field = self.text(name)?;
+        field
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} `{}`", name, field)))
+    }
+
+    /// A number that is zero when it is left out.
+    pub fn optional<T: FromStr + Default>(&mut self, name: &str) -> Result<T, RecordError> {
+        match self.fields.clone().next() {
+            Some(_) => self.number(name),
+            None => Ok(T::default()),
+        }
+    }
+
+    #[inline]
+    pub fn vector(&mut self, name: &str) -> Result<Vector2, RecordError> {
+        Ok(Vector2::new(self.number(name)?, self.number(name)?))
+    }
+
+    #[inline]
+    pub fn optional_vector(&mut self, name: &str) -> Result<Vector2, RecordError> {
+        Ok(Vector2::new(self.optional(name)?, self.optional(name)?))
+    }
+
+    /// A color written as `r,g,b,a`.
+    pub fn color(&mut self, name: &str) -> Result<Color, RecordError> {
+        let field = self.text(name)?;
+        let values = field
+            .split(',')
+            .map(|value| value.parse::<f32>().ok())
+            .collect::<Option<Vec<_>>>()
+            .filter(|values| values.len() == 4)
+            .ok_or_else(|| self.error(format!("invalid {} `{}`", name, field)))?;
+        Ok(Color::rgba(values[0], values[1], values[2], values[3]))
+    }
+
+    /// The fields that are left.
+    #[inline]
+    pub fn remaining(&mut self) -> Vec<&'a str> {
+        self.fields.by_ref().collect()
+    }
+
+    pub fn end(&mut self) -> Result<(), RecordError> {
+        match self.fields.next() {
+            Some(field) => Err(self.error(format!("unexpected `{}`", field))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_and_empty_lines_are_skipped() {
+        let text = "# a file\n\nformat 2\n  # indented\nfirst a b\n\nsecond\n";
+        let (version, records) = read_records(text, "format", 2).unwrap();
+        assert_eq!(version, 2);
+        let keywords = records
+            .iter()
+            .map(|r| (r.line, r.keyword))
+            .collect::<Vec<_>>();
+        assert_eq!(keywords, [(5, "first"), (7, "second")]);
+    }
+
+    #[test]
+    fn format_record_comes_first() {
+        let error = read_records("other 1\nformat 1\n", "format", 1).unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(read_records("# only\n", "format", 1).unwrap_err().line, 1);
+        assert_eq!(read_records("format x\n", "format", 1).unwrap_err().line, 1);
+        assert_eq!(
+            read_records("format 1 2\n", "format", 1).unwrap_err().line,
+            1
+        );
+        assert_eq!(
+            read_records("\nformat 2\n", "format", 1).unwrap_err(),
+            RecordError {
+                line: 2,
+                message: String::from("format version 2 is not supported, the latest is 1"),
+            }
+        );
+    }
+
+    #[test]
+    fn fields_are_read_in_order() {
+        let mut record = Record::new(3, "keyword a 1.5 2,0.5,1,1 4 5 x");
+        assert_eq!(record.text("text").unwrap(), "a");
+        assert_eq!(record.number::<f32>("number").unwrap(), 1.5);
+        assert_eq!(
+            record.color("color").unwrap(),
+            Color::rgba(2.0, 0.5, 1.0, 1.0)
+        );
+        assert_eq!(record.vector("vector").unwrap(), Vector2::new(4.0, 5.0));
+        assert_eq!(record.end().unwrap_err().message, "unexpected `x`");
+        assert!(record.end().is_ok());
+    }
+
+    #[test]
+    fn optional_fields_default_to_zero() {
+        let mut record = Record::new(1, "keyword 3");
+        assert_eq!(record.optional::<u32>("first").unwrap(), 3);
+        assert_eq!(record.optional::<u32>("second").unwrap(), 0);
+        assert_eq!(record.optional_vector("vector").unwrap(), Vector2::zero());
+        assert_eq!(record.text("text").unwrap_err().message, "missing text");
+    }
+
+    #[test]
+    fn invalid_fields_are_errors() {
+        let mut record = Record::new(1, "keyword x 1,2,3 y");
+        assert_eq!(
+            record.number::<u32>("count").unwrap_err().message,
+            "invalid count `x`"
+        );
+        assert_eq!(
+            record.color("tint").unwrap_err().message,
+            "invalid tint `1,2,3`"
+        );
+        assert!(record.optional::<f32>("scale").is_err());
+    }
+
+    #[test]
+    fn rest_is_one_field() {
+        let mut record = Record::new(1, "name  Map 01 ");
+        assert_eq!(record.rest(), "Map 01");
+        assert!(record.end().is_ok());
+        assert_eq!(Record::new(1, "alone").remaining(), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn error_at_end_is_on_the_last_line() {
+        assert_eq!(RecordError::at_end("a\nb\nc\n", String::new()).line, 3);
+    }
+}
@@ -4,12 +4,25 @@
 
 use gdnative::prelude::*;
 
+use crate::utils::error::*;
+
 #[allow(dead_code)]
 #[inline]
 pub fn instance_scene(scene: Ref<PackedScene>, edit_state: i64) -> Ref<Node> {
-    unsafe { scene.assume_safe() }
-        .instance(edit_state)
-        .unwrap_or_else(|| panic!("Failed to create instance of `{:?}`", scene))
+    try_instance_scene(scene, edit_state).unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[allow(dead_code)]
+#[inline]
+pub fn try_instance_scene(scene: Ref<PackedScene>, edit_state: i64) -> NodeResult<Ref<Node>> {
+    let scene = unsafe { scene.assume_safe() };
+    scene.instance(edit_state).ok_or_else(|| {
+        NodeError::new(
+            NodeErrorKind::InstanceFailed,
+            scene.path(),
+            PackedScene::class_name(),
+        )
+    })
 }
 
 #[allow(dead_code)]
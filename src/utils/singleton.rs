@@ -2,6 +2,8 @@
 // Use of this source code is governed by a BSD-style
 // license that can be found in the LICENSE file.
 
+#![allow(dead_code)]
+
 use gdnative::prelude::*;
 
 pub trait SingletonInstance<T, U>
@@ -12,13 +14,13 @@ where
     fn node_path<'a>() -> &'a str;
 
     // guarantees the return of a RefInstance
-    fn singleton(node: &Node) -> RefInstance<T, Shared> {
+    fn singleton(node: &Node) -> RefInstance<'_, T, Shared> {
         Self::try_singleton(node)
             .unwrap_or_else(|| panic!("Failed to find instance from path `{}`", Self::node_path()))
     }
 
     // tries to get a RefInstance, might return None
-    fn try_singleton(node: &Node) -> Option<RefInstance<T, Shared>> {
+    fn try_singleton(node: &Node) -> Option<RefInstance<'_, T, Shared>> {
         node.get_node_or_null(Self::node_path())
             .map(|node| unsafe { node.assume_safe() })
             .and_then(|node| node.cast::<U>())
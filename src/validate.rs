@@ -0,0 +1,323 @@
+// Copyright (c) 2021, Roel Schut. All rights reserved.
+// Use of this source code is governed by a BSD-style
+// license that can be found in the LICENSE file.
+
+//! Checks the scenes of the Godot project against the nodes the native
+//! classes expect, so broken node paths are found before the game runs.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use gdnative::api::{Camera2D, TileMap};
+use gdnative::prelude::*;
+
+use crate::bullet::Bullet;
+use crate::camera::CameraController;
+use crate::enemies::{EnemyTank, GunTurret};
+use crate::level::LevelEditor;
+use crate::map::{self, Map};
+use crate::obstacle::{png_size, Obstacle, ObstacleAtlas, RES_OBSTACLE_ATLAS};
+use crate::pickup::Pickup;
+use crate::player::{self, Player};
+use crate::scenes::bullets::bullet_tscn;
+use crate::scenes::environment::obstacle_tscn;
+use crate::scenes::maps::level_editor_tscn;
+use crate::scenes::pickups::pickup_tscn;
+use crate::scenes::tanks::{enemy_tank_tscn, gun_turret_tscn, tank_tscn};
+use crate::scenes::ui::{hud_tscn, unit_display_tscn};
+use crate::scenes::ChildNodeRef;
+use crate::terrain::{self, TileSetDef, TileSetMaker, RES_TILE_DEFINITION};
+use crate::tscn::classes::{is_known_class, is_subclass};
+use crate::tscn::{join_path, ResolvedScene, SceneLoader};
+use crate::ui::{Hud, Minimap, UnitDisplay};
+
+/// A node a native class expects at `path`, relative to the node it is
+/// attached to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeRequirement {
+    pub path: &'static str,
+    pub class: &'static str,
+}
+
+/// The nodes a native class expects in the scenes it is used in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassRequirements {
+    pub class: &'static str,
+    /// The Godot class the native class inherits from.
+    pub base: &'static str,
+    pub nodes: Vec<NodeRequirement>,
+}
+
+impl ClassRequirements {
+    #[inline]
+    pub fn of<T: NativeClass>() -> Self {
+        ClassRequirements {
+            class: T::class_name(),
+            base: T::Base::class_name(),
+            nodes: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn node<U: GodotObject>(mut self, path: &'static str) -> Self {
+        self.nodes.push(NodeRequirement {
+            path,
+            class: U::class_name(),
+        });
+        self
+    }
+
+    /// Adds the node of scene node type `N`, see `crate::scenes`.
+    #[inline]
+    pub fn child<N: ChildNodeRef>(self) -> Self {
+        self.node::<N::Base>(N::path())
+    }
+}
+
+/// The nodes of all native classes that are attached to nodes in scenes.
+/// Most node types are generated from the scene a class is made for, the
+/// scenes that inherit from or instance that scene still need to be checked.
+pub fn requirements() -> Vec<ClassRequirements> {
+    vec![
+        ClassRequirements::of::<Map>()
+            .node::<KinematicBody2D>(player::NAME)
+            .node::<Camera2D>(map::CAMERA_NODE)
+            .node::<TileMap>(terrain::GROUND_NODE),
+        ClassRequirements::of::<CameraController>(),
+        tank_requirements(ClassRequirements::of::<Player>()),
+        tank_requirements(ClassRequirements::of::<EnemyTank>())
+            .child::<enemy_tank_tscn::DetectRadiusCollisionShape2DNode>()
+            .child::<enemy_tank_tscn::LookAhead1Node>()
+            .child::<enemy_tank_tscn::LookAhead2Node>(),
+        tank_requirements(ClassRequirements::of::<GunTurret>())
+            .child::<gun_turret_tscn::DetectRadiusCollisionShape2DNode>(),
+        ClassRequirements::of::<Bullet>()
+            .child::<bullet_tscn::SpriteNode>()
+            .child::<bullet_tscn::ExplosionNode>()
+            .child::<bullet_tscn::LifetimeNode>(),
+        ClassRequirements::of::<LevelEditor>().child::<level_editor_tscn::OverlayStatusNode>(),
+        ClassRequirements::of::<Obstacle>()
+            .child::<obstacle_tscn::SpriteNode>()
+            .child::<obstacle_tscn::CollisionShape2DNode>(),
+        ClassRequirements::of::<Pickup>()
+            .child::<pickup_tscn::SpriteNode>()
+            .child::<pickup_tscn::CollisionShape2DNode>()
+            .child::<pickup_tscn::RespawnTimerNode>(),
+        ClassRequirements::of::<Hud>()
+            .child::<hud_tscn::MarginHBoxContainerHealthBarNode>()
+            .child::<hud_tscn::MarginHBoxContainerHealthBarTweenNode>()
+            .child::<hud_tscn::MarginHBoxContainerHealthBarShieldBarNode>()
+            .child::<hud_tscn::MarginHBoxContainerScoreLabelNode>()
+            .child::<hud_tscn::AnimationPlayerNode>(),
+        ClassRequirements::of::<Minimap>(),
+        ClassRequirements::of::<TileSetMaker>(),
+        ClassRequirements::of::<UnitDisplay>()
+            .child::<unit_display_tscn::HealthBarNode>()
+            .child::<unit_display_tscn::ShieldBarNode>(),
+    ]
+}
+
+fn tank_requirements(requirements: ClassRequirements) -> ClassRequirements {
+    requirements
+        .child::<tank_tscn::BodyNode>()
+        .child::<tank_tscn::CollisionShape2DNode>()
+        .child::<tank_tscn::GunTimerNode>()
+        .child::<tank_tscn::TurretNode>()
+        .child::<tank_tscn::TurretMuzzleNode>()
+        .child::<tank_tscn::TurretFlashNode>()
+        .child::<tank_tscn::AnimationPlayerNode>()
+        .child::<tank_tscn::ExplosionNode>()
+}
+
+/// A node that does not match what its native class expects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    pub scene: String,
+    /// Path of the node with the native class attached.
+    pub node: String,
+    pub class: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} `{}`: {}",
+            self.scene, self.class, self.node, self.message
+        )
+    }
+}
+
+/// Checks the nodes of `scene` that are defined in its own file. Nodes that
+/// come from instanced scenes are checked with those scenes.
+pub fn validate_scene(scene: &ResolvedScene, requirements: &[ClassRequirements]) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    for node in scene.nodes.iter().filter(|node| node.is_local) {
+        let requirements = match node.script_class.as_deref().and_then(|class| {
+            requirements
+                .iter()
+                .find(|requirements| requirements.class == class)
+        }) {
+            Some(requirements) => requirements,
+            None => continue,
+        };
+
+        let mut mismatch = |message: String| {
+            mismatches.push(Mismatch {
+                scene: scene.res_path.clone(),
+                node: node.path.clone(),
+                class: requirements.class,
+                message,
+            })
+        };
+
+        if let Some(message) = check_class(node.class.as_deref(), requirements.base) {
+            mismatch(format!("the node itself {}", message));
+        }
+
+        for required in requirements.nodes.iter() {
+            let path = join_path(node.path.as_str(), required.path);
+            let message = match scene.get(path.as_str()) {
+                Some(child) => check_class(child.class.as_deref(), required.class),
+                None => Some(String::from("is missing")),
+            };
+            if let Some(message) = message {
+                mismatch(format!(
+                    "node `{}` ({}) {}",
+                    required.path, required.class, message
+                ));
+            }
+        }
+    }
+    mismatches
+}
+
+fn check_class(class: Option<&str>, expected: &str) -> Option<String> {
+    match class {
+        None => Some(String::from("has an unknown type")),
+        Some(class) if is_subclass(class, expected) => None,
+        Some(class) if !is_known_class(class) => Some(format!(
+            "has type {}, which is unknown to the validator",
+            class
+        )),
+        Some(class) => Some(format!("has type {}", class)),
+    }
+}
+
+/// The result of checking all scenes of a project.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub scenes: usize,
+    pub mismatches: Vec<Mismatch>,
+    /// Scenes that could not be loaded, and problems of the obstacle atlas
+    /// and the tile definition.
+    pub errors: Vec<String>,
+}
+
+impl Report {
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty() && self.errors.is_empty()
+    }
+}
+
+/// Checks every `.tscn` file in the Godot project directory `dir`.
+pub fn validate_project(dir: impl AsRef<Path>) -> Result<Report, String> {
+    let requirements = requirements();
+    let mut loader = SceneLoader::new(dir);
+    let mut report = Report::default();
+
+    for file in loader.scene_files()? {
+        let res_path = loader.res_path(file.as_path());
+        report.scenes += 1;
+
+        match loader.resolve(res_path.as_str()) {
+            Ok(scene) => report
+                .mismatches
+                .extend(validate_scene(&scene, requirements.as_slice())),
+            Err(err) => report.errors.push(err),
+        }
+    }
+    report.errors.extend(validate_atlas(&loader));
+    report.errors.extend(validate_tiles(&loader));
+    Ok(report)
+}
+
+/// Checks that the obstacle atlas can be read and that the regions of its
+/// obstacles lie inside its texture.
+fn validate_atlas(loader: &SceneLoader) -> Vec<String> {
+    let path = loader.file_path(RES_OBSTACLE_ATLAS);
+    let atlas = match fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| text.parse::<ObstacleAtlas>().map_err(|err| err.to_string()))
+    {
+        Ok(atlas) => atlas,
+        Err(err) => return vec![format!("{}: {}", RES_OBSTACLE_ATLAS, err)],
+    };
+
+    let texture = loader.file_path(atlas.texture.as_str());
+    match fs::read(&texture).ok().and_then(|data| png_size(&data)) {
+        Some(size) => atlas
+            .check_regions(size)
+            .into_iter()
+            .map(|err| format!("{}: {}", RES_OBSTACLE_ATLAS, err))
+            .collect(),
+        None => vec![format!(
+            "{}: `{}` is not a PNG image",
+            RES_OBSTACLE_ATLAS, atlas.texture
+        )],
+    }
+}
+
+/// Checks that the tile definition can be read and that its tile ids fit its
+/// texture.
+fn validate_tiles(loader: &SceneLoader) -> Vec<String> {
+    let path = loader.file_path(RES_TILE_DEFINITION);
+    let def = match fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| text.parse::<TileSetDef>().map_err(|err| err.to_string()))
+    {
+        Ok(def) => def,
+        Err(err) => return vec![format!("{}: {}", RES_TILE_DEFINITION, err)],
+    };
+
+    let texture = loader.file_path(def.texture.as_str());
+    match fs::read(&texture).ok().and_then(|data| png_size(&data)) {
+        Some(size) => def
+            .check(size)
+            .into_iter()
+            .map(|err| format!("{}: {}", RES_TILE_DEFINITION, err))
+            .collect(),
+        None => vec![format!(
+            "{}: `{}` is not a PNG image",
+            RES_TILE_DEFINITION, def.texture
+        )],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_scenes_match_the_native_classes() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("godot");
+        let report = validate_project(dir).unwrap();
+
+        let problems = report
+            .errors
+            .iter()
+            .cloned()
+            .chain(
+                report
+                    .mismatches
+                    .iter()
+                    .map(|mismatch| mismatch.to_string()),
+            )
+            .collect::<Vec<_>>();
+        assert!(report.scenes > 0);
+        assert!(report.is_ok(), "{}", problems.join("\n"));
+    }
+}